@@ -0,0 +1,159 @@
+#![allow(dead_code)]
+
+// parses the action lines that the command macros emit, such as "MoveCursor(1,2)" or "String(\"text\")"
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Action {
+    pub name: String,
+    pub arguments: Vec<String>
+}
+
+impl Action {
+    pub fn new(name: &str, arguments: Vec<String>) -> Self {
+        Action {
+            name: String::from(name),
+            arguments
+        }
+    }
+    pub fn parse_line(line: &str) -> Option<Vec<Action>> {
+        let characters = line.chars().collect::<Vec<char>>();
+        let mut actions = Vec::new();
+        let mut index = 0;
+        loop {
+            // skip the whitespace between actions
+            while index < characters.len() && characters[index].is_whitespace() {
+                index += 1;
+            }
+            if index == characters.len() {
+                break;
+            }
+
+            // read the action name
+            let name_start = index;
+            while index < characters.len() && (characters[index].is_ascii_alphanumeric() || characters[index] == '_' || characters[index] == '-') {
+                index += 1;
+            }
+            if name_start == index {
+                return None;
+            }
+            let name = characters[name_start..index].iter().collect::<String>();
+
+            // read the optional argument list
+            let mut arguments = Vec::new();
+            if index < characters.len() && characters[index] == '(' {
+                index += 1;
+                loop {
+                    while index < characters.len() && characters[index] == ' ' {
+                        index += 1;
+                    }
+                    if index == characters.len() {
+                        return None;
+                    }
+                    if characters[index] == ')' && arguments.is_empty() {
+                        index += 1;
+                        break;
+                    }
+                    let mut argument = String::new();
+                    if characters[index] == '"' {
+                        index += 1;
+                        loop {
+                            if index == characters.len() {
+                                return None;
+                            }
+                            match characters[index] {
+                                '"' => {
+                                    index += 1;
+                                    break;
+                                },
+                                '\\' => {
+                                    if index + 1 == characters.len() {
+                                        return None;
+                                    }
+                                    match characters[index + 1] {
+                                        'n' => argument.push('\n'),
+                                        't' => argument.push('\t'),
+                                        'b' => argument.push('\u{0008}'),
+                                        'f' => argument.push('\u{000C}'),
+                                        'r' => argument.push('\r'),
+                                        other => argument.push(other)
+                                    }
+                                    index += 2;
+                                },
+                                other => {
+                                    argument.push(other);
+                                    index += 1;
+                                }
+                            }
+                        }
+                    }
+                    else {
+                        while index < characters.len() && characters[index] != ',' && characters[index] != ')' {
+                            argument.push(characters[index]);
+                            index += 1;
+                        }
+                        argument = String::from(argument.trim());
+                    }
+                    arguments.push(argument);
+                    while index < characters.len() && characters[index] == ' ' {
+                        index += 1;
+                    }
+                    if index == characters.len() {
+                        return None;
+                    }
+                    match characters[index] {
+                        ',' => {
+                            index += 1;
+                        },
+                        ')' => {
+                            index += 1;
+                            break;
+                        },
+                        _ => {
+                            return None;
+                        }
+                    }
+                }
+            }
+            actions.push(Action {
+                name,
+                arguments
+            });
+        }
+        Some(actions)
+    }
+    pub fn get_argument_as<T: std::str::FromStr>(&self, index: usize) -> Option<T> {
+        self.arguments
+            .get(index)
+            .and_then(|argument| argument.parse::<T>().ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_actions_with_and_without_arguments() {
+        let actions = Action::parse_line("MoveCursor(1, 2) Tab Query(Cursor) Home()").unwrap();
+        assert_eq!(vec![
+            Action::new("MoveCursor", vec![String::from("1"), String::from("2")]),
+            Action::new("Tab", vec![]),
+            Action::new("Query", vec![String::from("Cursor")]),
+            Action::new("Home", vec![])
+        ], actions);
+    }
+
+    #[test]
+    fn parse_quoted_argument_with_escapes() {
+        let actions = Action::parse_line("String(\"say \\\"hi\\\", then\\n\")").unwrap();
+        assert_eq!(vec![
+            Action::new("String", vec![String::from("say \"hi\", then\n")])
+        ], actions);
+    }
+
+    #[test]
+    fn reject_unterminated_arguments() {
+        assert!(Action::parse_line("MoveCursor(1,2").is_none());
+        assert!(Action::parse_line("String(\"text)").is_none());
+    }
+}
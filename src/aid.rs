@@ -0,0 +1,123 @@
+#![allow(dead_code)]
+
+use crate::screen::*;
+
+// the attention identifier keys, each of which sends the screen to the host and locks the keyboard until it answers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Aid {
+    Enter,
+    Clear,
+    Pf1,
+    Pf2,
+    Pf3,
+    Pf4,
+    Pf5,
+    Pf6,
+    Pf7,
+    Pf8,
+    Pf9,
+    Pf10,
+    Pf11,
+    Pf12,
+    Pf13,
+    Pf14,
+    Pf15,
+    Pf16,
+    Pf17,
+    Pf18,
+    Pf19,
+    Pf20,
+    Pf21,
+    Pf22,
+    Pf23,
+    Pf24,
+    Pa1,
+    Pa2,
+    Pa3,
+    SysReq
+}
+
+const PROGRAM_FUNCTION_KEYS: [Aid; 24] = [
+    Aid::Pf1, Aid::Pf2, Aid::Pf3, Aid::Pf4, Aid::Pf5, Aid::Pf6, Aid::Pf7, Aid::Pf8, Aid::Pf9, Aid::Pf10, Aid::Pf11, Aid::Pf12,
+    Aid::Pf13, Aid::Pf14, Aid::Pf15, Aid::Pf16, Aid::Pf17, Aid::Pf18, Aid::Pf19, Aid::Pf20, Aid::Pf21, Aid::Pf22, Aid::Pf23, Aid::Pf24
+];
+
+impl Aid {
+    // PF1 through PF24
+    pub fn from_program_function_number(number: u8) -> Option<Aid> {
+        PROGRAM_FUNCTION_KEYS.get((number as usize).checked_sub(1)?)
+            .cloned()
+    }
+    pub fn get_program_function_number(&self) -> Option<u8> {
+        PROGRAM_FUNCTION_KEYS.iter()
+            .position(|aid| aid == self)
+            .map(|index| index as u8 + 1)
+    }
+    // the byte that leads the inbound record
+    pub fn get_code(&self) -> u8 {
+        match self {
+            Aid::Enter => AID_ENTER,
+            Aid::Clear => AID_CLEAR,
+            Aid::Pa1 => AID_PA1,
+            Aid::Pa2 => AID_PA2,
+            Aid::Pa3 => AID_PA3,
+            Aid::SysReq => AID_SYSTEM_REQUEST,
+            program_function_key => {
+                let number = program_function_key.get_program_function_number()
+                    .expect("every other key should be a PF key");
+                get_program_function_aid(number)
+                    .expect("every PF key should have an AID")
+            }
+        }
+    }
+    pub fn from_code(code: u8) -> Option<Aid> {
+        match code {
+            AID_ENTER => Some(Aid::Enter),
+            AID_CLEAR => Some(Aid::Clear),
+            AID_PA1 => Some(Aid::Pa1),
+            AID_PA2 => Some(Aid::Pa2),
+            AID_PA3 => Some(Aid::Pa3),
+            AID_SYSTEM_REQUEST => Some(Aid::SysReq),
+            code => {
+                PROGRAM_FUNCTION_KEYS.iter()
+                    .find(|aid| aid.get_code() == code)
+                    .cloned()
+            }
+        }
+    }
+    // the x3270 action that presses the key, such as "PF(3)"
+    pub fn get_action(&self) -> String {
+        match self {
+            Aid::Enter => String::from("Enter"),
+            Aid::Clear => String::from("Clear"),
+            Aid::Pa1 => String::from("PA(1)"),
+            Aid::Pa2 => String::from("PA(2)"),
+            Aid::Pa3 => String::from("PA(3)"),
+            Aid::SysReq => String::from("SysReq"),
+            program_function_key => {
+                let number = program_function_key.get_program_function_number()
+                    .expect("every other key should be a PF key");
+                format!("PF({})", number)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_keys_to_codes_and_actions() {
+        assert_eq!(Some(Aid::Pf12), Aid::from_program_function_number(12));
+        assert_eq!(None, Aid::from_program_function_number(25));
+        assert_eq!(0x7C, Aid::Pf12.get_code());
+        assert_eq!(0x4C, Aid::Pf24.get_code());
+        assert_eq!("PF(24)", Aid::Pf24.get_action());
+        assert_eq!("PA(2)", Aid::Pa2.get_action());
+        for code in [AID_ENTER, AID_CLEAR, AID_PA3, AID_SYSTEM_REQUEST, 0xF1, 0xC9] {
+            assert_eq!(code, Aid::from_code(code).unwrap().get_code());
+        }
+        assert_eq!(None, Aid::from_code(AID_STRUCTURED_FIELD));
+    }
+}
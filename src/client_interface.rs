@@ -1,123 +1,76 @@
 #![allow(dead_code)]
 
-use std::{net::TcpStream, io::{Write, BufReader}, io::BufRead, cell::RefCell, process::Child};
+use std::{net::{TcpStream, ToSocketAddrs}, io::{Read, Write}, cell::RefCell, path::PathBuf, process::{Child, ChildStdin, ChildStdout, ExitStatus, Stdio}, sync::{mpsc::{Receiver, RecvTimeoutError}, Arc, Mutex}, time::{Duration, Instant}};
+use crate::{aid::Aid, field::{parse_read_buffer, Field}, session_query::*, client_status::{ClientStatus, ConnectionState}, code_page::CodePage, command_batch::*, error::Rs3270Error, logging::*, proxy::ProxyConfiguration, screen::ScreenModel, script_codec::*, transcript::TranscriptRecorder, tls::{TlsConfiguration, TlsMode}};
 
-// TODO always check the status for "ok" or "error"
-
-#[derive(Debug)]
-pub enum ExecutionResult<T> {
-    Unset,
-    Success(T),
-    IoError(std::io::Error),
-    CommandFailure(Option<String>),
-}
-
-fn unwrap_failed(msg: &str, error: &dyn std::fmt::Debug) -> ! {
-    panic!("{msg}: {error:?}")
-}
+macro_rules! command {
+    ($command_name:ty,
+        command: $client_message_block:block) => {
+        paste::paste! {
+            pub struct [<$command_name Command>] {}
 
-impl<T: std::fmt::Debug> ExecutionResult<T> {
-    pub fn unwrap(self) -> T {
-        match self {
-            ExecutionResult::Success(item) => {
-                item
-            },
-            ExecutionResult::IoError(e) => {
-                unwrap_failed("called `ExecutionResult::unwrap()` on an `IoError` value", &e)
-            },
-            ExecutionResult::CommandFailure(cf) => {
-                unwrap_failed("called `ExecutionResult::unwrap()` on a `CommandFailure` value", &cf)
-            }
-            ExecutionResult::Unset => {
-                unwrap_failed("called `ExecutionResult::unwrap()` on an `Unset` value", &self)
-            }
-        }
-    }
-    pub fn expect(self, message: &str) -> T {
-        match self {
-            ExecutionResult::Success(item) => {
-                item
-            },
-            ExecutionResult::IoError(e) => {
-                unwrap_failed(message, &e)
-            },
-            ExecutionResult::CommandFailure(cf) => {
-                unwrap_failed(message, &cf)
-            },
-            ExecutionResult::Unset => {
-                unwrap_failed(message, &self)
-            }
-        }
-    }
-    pub fn is_err(&self) -> bool {
-        match self {
-            ExecutionResult::Success(_) => {
-                false
-            },
-            ExecutionResult::IoError(_) => {
-                true
-            },
-            ExecutionResult::CommandFailure(_) => {
-                true
-            },
-            ExecutionResult::Unset => {
-                true
-            }
-        }
-    }
-    pub fn is_ok(&self) -> bool {
-        !self.is_err()
-    }
-    pub fn err(self) -> Option<String> {
-        match self {
-            ExecutionResult::Success(_) => {
-                None
-            },
-            ExecutionResult::IoError(e) => {
-                Some(format!("{e:?}"))
-            }
-            ExecutionResult::CommandFailure(cf) => {
-                Some(format!("{cf:?}"))
+            impl [<$command_name Command>] {
+                pub fn new() -> Self {
+                    [<$command_name Command>] {}
+                }
             }
-            ExecutionResult::Unset => {
-                Some(format!("ExecutionResult::Unset"))
+
+            impl CommandBuilder<()> for [<$command_name Command>] {
+                fn get_client_message(&self) -> String {
+                    $client_message_block
+                }
+                fn append_client_data_response(&self, _: String) -> Result<(), String> {
+                    Ok(())
+                }
+                fn build(self) -> Result<(), String> {
+                    Ok(())
+                }
             }
         }
-    }
-}
-
-macro_rules! command {
+    };
     ($command_name:ty,
+        [$($arg_name:ident: $arg_type:ty),*],
         command: $client_message_block:block) => {
         paste::paste! {
             pub struct [<$command_name Command>] {
-                [<$command_name:camel _is_successful>]: RefCell<ExecutionResult<()>>
+                $(
+                    $arg_name: $arg_type,
+                )*
             }
 
             impl [<$command_name Command>] {
-                pub fn new() -> Self {
+                pub fn new($($arg_name: $arg_type),*) -> Self {
                     [<$command_name Command>] {
-                        [<$command_name:camel _is_successful>]: RefCell::new(ExecutionResult::Unset)
+                        $(
+                            $arg_name,
+                        )*
                     }
                 }
             }
 
             impl CommandBuilder<()> for [<$command_name Command>] {
                 fn get_client_message(&self) -> String {
+                    $(
+                        let $arg_name: &$arg_type = &self.$arg_name;
+                    )*
                     $client_message_block
                 }
-                fn append_client_data_response(&self, _: String) {
-                    // NOP
+                fn append_client_data_response(&self, _: String) -> Result<(), String> {
+                    Ok(())
                 }
-                fn build(self) -> () {
-                    // NOP
+                fn build(self) -> Result<(), String> {
+                    Ok(())
                 }
             }
         }
     };
     ($command_name:ty,
         [$($arg_name:ident: $arg_type:ty),*],
-        command: $client_message_block:block) => {
+        command: $client_message_block:block,
+        validate: (
+            $code_page_name:ident,
+            $validate_block:block
+        )) => {
         paste::paste! {
             pub struct [<$command_name Command>] {
                 $(
@@ -142,11 +95,19 @@ macro_rules! command {
                     )*
                     $client_message_block
                 }
-                fn append_client_data_response(&self, _: String) {
-                    // NOP
+                fn append_client_data_response(&self, _: String) -> Result<(), String> {
+                    Ok(())
                 }
-                fn build(self) -> () {
-                    // NOP
+                // a command need not validate every argument
+                #[allow(unused_variables)]
+                fn validate(&self, $code_page_name: &CodePage) -> Result<(), String> {
+                    $(
+                        let $arg_name: &$arg_type = &self.$arg_name;
+                    )*
+                    $validate_block
+                }
+                fn build(self) -> Result<(), String> {
+                    Ok(())
                 }
             }
         }
@@ -181,12 +142,12 @@ macro_rules! command {
                     )*
                     $client_message_block
                 }
-                fn append_client_data_response(&self, _: String) {
-                    // NOP
+                fn append_client_data_response(&self, _: String) -> Result<(), String> {
+                    Ok(())
                 }
-                fn build(self) -> $return_type {
+                fn build(self) -> Result<$return_type, String> {
                     let $return_name: Option<$return_type> = self.$return_name.into_inner();
-                    $return_name.unwrap()
+                    $return_name.ok_or_else(|| String::from("The client did not return any data."))
                 }
             }
         }
@@ -219,19 +180,25 @@ macro_rules! command {
             }
 
             impl CommandBuilder<$return_type> for [<$command_name Command>] {
+                #[allow(unused_variables)]
                 fn get_client_message(&self) -> String {
                     $(
                         let $arg_name: &$arg_type = &self.$arg_name;
                     )*
                     $client_message_block
                 }
-                fn append_client_data_response(&self, $data_name: String) {
+                #[allow(unused_variables)]
+                fn append_client_data_response(&self, $data_name: String) -> Result<(), String> {
+                    $(
+                        let $arg_name: &$arg_type = &self.$arg_name;
+                    )*
                     let $return_name: &mut Option<$return_type> = &mut self.$return_name.borrow_mut();
                     $data_block
+                    Ok(())
                 }
-                fn build(self) -> $return_type {
+                fn build(self) -> Result<$return_type, String> {
                     let $return_name: Option<$return_type> = self.$return_name.into_inner();
-                    $return_name.unwrap()
+                    $return_name.ok_or_else(|| String::from("The client did not return any data."))
                 }
             }
         }
@@ -274,9 +241,10 @@ macro_rules! command {
                     )*
                     $client_message_block
                 }
-                fn append_client_data_response(&self, $data_name: String) {
+                fn append_client_data_response(&self, $data_name: String) -> Result<(), String> {
                     let $return_name: &mut Option<$return_type> = &mut self.$return_name.borrow_mut();
                     $data_block
+                    Ok(())
                 }
                 fn set_client_status_response(&self, $status_name: String) {
                     let $return_name: &mut Option<$return_type> = &mut self.$return_name.borrow_mut();
@@ -285,9 +253,9 @@ macro_rules! command {
                 fn set_client_conclusion_response(&self, _: String) {
                     // NOP
                 }
-                fn build(self) -> $return_type {
+                fn build(self) -> Result<$return_type, String> {
                     let $return_name: Option<$return_type> = self.$return_name.into_inner();
-                    $return_name.unwrap()
+                    $return_name.ok_or_else(|| String::from("The client did not return any data."))
                 }
             }
         }
@@ -316,13 +284,14 @@ macro_rules! command {
                 fn get_client_message(&self) -> String {
                     $client_message_block
                 }
-                fn append_client_data_response(&self, $data_name: String) {
+                fn append_client_data_response(&self, $data_name: String) -> Result<(), String> {
                     let $return_name: &mut Option<$return_type> = &mut self.$return_name.borrow_mut();
                     $data_block
+                    Ok(())
                 }
-                fn build(self) -> $return_type {
+                fn build(self) -> Result<$return_type, String> {
                     let $return_name: Option<$return_type> = self.$return_name.into_inner();
-                    $return_name.unwrap()
+                    $return_name.ok_or_else(|| String::from("The client did not return any data."))
                 }
             }
         }
@@ -347,12 +316,12 @@ macro_rules! command {
                 fn get_client_message(&self) -> String {
                     $client_message_block
                 }
-                fn append_client_data_response(&self, _: String) {
-                    // NOP
+                fn append_client_data_response(&self, _: String) -> Result<(), String> {
+                    Ok(())
                 }
-                fn build(self) -> $return_type {
+                fn build(self) -> Result<$return_type, String> {
                     let $return_name: Option<$return_type> = self.$return_name.into_inner();
-                    $return_name.unwrap()
+                    $return_name.ok_or_else(|| String::from("The client did not return any data."))
                 }
             }
         }
@@ -391,13 +360,14 @@ macro_rules! command {
                     )*
                     $client_message_block
                 }
-                fn append_client_data_response(&self, $data_name: String) {
+                fn append_client_data_response(&self, $data_name: String) -> Result<(), String> {
                     let $return_name: &mut Option<$return_type> = &mut self.$return_name.borrow_mut();
                     $data_block
+                    Ok(())
                 }
-                fn build(self) -> $return_type {
+                fn build(self) -> Result<$return_type, String> {
                     let $return_name: Option<$return_type> = self.$return_name.into_inner();
-                    $return_name.unwrap()
+                    $return_name.ok_or_else(|| String::from("The client did not return any data."))
                 }
             }
         }
@@ -405,88 +375,101 @@ macro_rules! command {
 }
 
 pub trait CommandBuilder<TOutput> {
-    fn execute(self, stream: &mut TcpStream) -> ExecutionResult<TOutput> where Self:Sized {
-
-        // get the client message and prepare to send it
+    fn execute<TStream: Read + Write>(self, codec: &mut ScriptCodec<TStream>) -> Result<TOutput, Rs3270Error> where Self:Sized {
+        self.execute_with_status(codec).0
+    }
+    // also returns the status line that follows the data, if it could be parsed
+    fn execute_with_status<TStream: Read + Write>(self, codec: &mut ScriptCodec<TStream>) -> (Result<TOutput, Rs3270Error>, Option<ClientStatus>) where Self:Sized {
         let client_message = self.get_client_message();
-        let client_message = format!("{}\n", client_message);
+        let span = start_command_span(&client_message);
+        let _entered = span.enter();
+        let start_time = Instant::now();
+        let (execution_result, status) = self.exchange(codec, &client_message);
+        record_command_outcome(&span, start_time, &execution_result);
+        (execution_result, status)
+    }
+    // sends the client message and reads the frame of data, status and conclusion lines that answers it
+    fn exchange<TStream: Read + Write>(self, codec: &mut ScriptCodec<TStream>, client_message: &str) -> (Result<TOutput, Rs3270Error>, Option<ClientStatus>) where Self:Sized {
+
+        // errors only carry the redacted form of the command
+        let command = redact_command(client_message);
 
         // send the client message to the running/connected program
-        println!("CommandBuilder: execute: sending client message: \"{}\"", &client_message);
-        let write_all_result = stream.write_all(client_message.as_bytes());
-        if let Err(error) = write_all_result {
-            println!("CommandBuilder: execute: write_all: error: {}", error);
-            return ExecutionResult::IoError(error);
+        tracing::debug!(%command, "sending command");
+        if let Err(error) = codec.write_line(client_message) {
+            return (Err(Rs3270Error::from_io_error(&command, error)), None);
         }
-        let flush_result = stream.flush();
-        if let Err(error) = flush_result {
-            println!("CommandBuilder: execute: flush: error: {}", error);
-            return ExecutionResult::IoError(error);
-        }
-
-        // begin reading the response from the running/connected program
-        let mut reader = BufReader::new(stream.try_clone().unwrap());
-
-        let mut is_finished_reading = false;
-        let mut is_status_message_received = false;
-        let mut iteration_count = 0;
-        let mut is_conclusion_successful = false;
-        let mut first_line: Option<String> = None;
-        while !is_finished_reading {
-            println!("read iteration: {}", iteration_count);
-
-            let mut line = String::new();
-            let read_line_result = reader.read_line(&mut line);
-            if let Err(error) = read_line_result {
-                println!("CommandBuilder: execute: read_line: error: {}", error);
-                return ExecutionResult::IoError(error);
+        self.receive(codec, command)
+    }
+    // reads the frame that answers this command once it has been sent, where the command is already redacted
+    fn receive<TStream: Read + Write>(self, codec: &mut ScriptCodec<TStream>, command: String) -> (Result<TOutput, Rs3270Error>, Option<ClientStatus>) where Self:Sized {
+        let frame = match codec.read_frame() {
+            Ok(frame) => {
+                frame
+            },
+            Err(FrameError::Io(error)) => {
+                return (Err(Rs3270Error::from_io_error(&command, error)), None);
+            },
+            Err(FrameError::Protocol(message)) => {
+                return (Err(Rs3270Error::Protocol { command, message }), None);
             }
-
-            println!("line: {line}");
-
-            if line.starts_with("data: ") {
-                // the line contains data to be processed by the command
-                line = line
-                    .replacen("data: ", "", 1)
-                    .replace("\n", "");
-                if iteration_count == 0 {
-                    first_line = Some(line.clone());
+        };
+        let status = Some(frame.status.clone());
+        (self.complete(frame, command), status)
+    }
+    // turns the frame that answered this command into its output or error
+    fn complete(self, frame: ResponseFrame, command: String) -> Result<TOutput, Rs3270Error> where Self:Sized {
+
+        // the data lines were held until the conclusion said whether they are output or error messages
+        match frame.conclusion {
+            Conclusion::Ok => {
+                for data in frame.data_lines {
+                    if let Err(message) = self.append_client_data_response(data) {
+                        return Err(Rs3270Error::Parse { command, message });
+                    }
                 }
-                self.append_client_data_response(line);
-            }
-            else if !is_status_message_received {
-                // NOP
-                is_status_message_received = true;
-            }
-            else {
-                if line.as_str().trim() == "ok" {
-                    is_conclusion_successful = true;
+                self.build()
+                    .map_err(|message| Rs3270Error::Parse { command, message })
+            },
+            Conclusion::Error => {
+                let is_disconnected = frame.status.connection_state == ConnectionState::NotConnected;
+                let error = if is_disconnected {
+                    Rs3270Error::Disconnected { command }
                 }
-                else {
-                    println!("client_interface: CommandBuilder: execute: error: \"{}\"", line);
+                else if frame.data_lines.iter().any(|line| line.trim().eq_ignore_ascii_case("keyboard locked")) {
+                    Rs3270Error::KeyboardLocked { command }
                 }
-                is_finished_reading = true;
+                else {
+                    Rs3270Error::Emulator { command, lines: frame.data_lines }
+                };
+                Err(error)
             }
-
-            iteration_count += 1;
-        }
-
-        if is_conclusion_successful {
-            ExecutionResult::Success(self.build())
-        }
-        else {
-            ExecutionResult::CommandFailure(first_line)
         }
     }
     fn get_client_message(&self) -> String;
-    fn append_client_data_response(&self, data: String);
-    fn build(self) -> TOutput;
+    // returns a message when the data cannot be read as part of the output
+    fn append_client_data_response(&self, data: String) -> Result<(), String>;
+    // checked before anything is sent to the client
+    fn validate(&self, _code_page: &CodePage) -> Result<(), String> {
+        Ok(())
+    }
+    fn build(self) -> Result<TOutput, String>;
+}
+
+// the Ebcdic() action reports each byte as hexadecimal, such as "0xc1 0xc2"
+fn parse_ebcdic_data(data: &str) -> Result<Vec<u8>, String> {
+    data.split_whitespace()
+        .map(|item| {
+            u8::from_str_radix(item.trim_start_matches("0x"), 16)
+                .map_err(|_| format!("\"{}\" is not a hexadecimal EBCDIC byte.", item))
+        })
+        .collect()
 }
 
 command!(GetText, [
-        row: u8,
-        column: u8,
-        length: u8
+        row: usize,
+        column: usize,
+        length: usize
     ],
     command: {
         format!("Ascii({},{},{})", row, column, length)
@@ -495,7 +478,7 @@ command!(GetText, [
     data: (
         data, {
             if text.is_some() {
-                panic!("Unexpected additional text from the client.");
+                return Err(format!("Unexpected additional text \"{}\" from the client.", data));
             }
             *text = Some(data);
         }
@@ -503,10 +486,10 @@ command!(GetText, [
 );
 
 command!(GetTextRange, [
-        row: u8,
-        column: u8,
-        width: u8,
-        height: u8
+        row: usize,
+        column: usize,
+        width: usize,
+        height: usize
     ],
     command: {
         format!("Ascii({},{},{},{})", row, column, height, width)
@@ -517,38 +500,97 @@ command!(GetTextRange, [
             if lines.is_none() {
                 *lines = Some(Vec::<String>::new());
             }
-            let lines: &mut Vec<String> = &mut lines.as_mut().unwrap();
+            let lines: &mut Vec<String> = lines.as_mut().unwrap();
             lines.push(data);
         }
     )
 );
 
 command!(MoveCursor, [
-        row: u8,
-        column: u8
+        row: usize,
+        column: usize
     ],
     command: {
         format!("MoveCursor({},{})", row, column)
     }
 );
 
+command!(GetEbcdicText, [
+        row: usize,
+        column: usize,
+        length: usize,
+        code_page: CodePage
+    ],
+    command: {
+        format!("Ebcdic({},{},{})", row, column, length)
+    },
+    output => text: String,
+    data: (
+        data, {
+            if text.is_some() {
+                return Err(format!("Unexpected additional text \"{}\" from the client.", data));
+            }
+            *text = Some(code_page.decode(&parse_ebcdic_data(&data)?));
+        }
+    )
+);
+
+command!(GetEbcdicTextRange, [
+        row: usize,
+        column: usize,
+        width: usize,
+        height: usize,
+        code_page: CodePage
+    ],
+    command: {
+        format!("Ebcdic({},{},{},{})", row, column, height, width)
+    },
+    output => lines: Vec<String>,
+    data: (
+        data, {
+            if lines.is_none() {
+                *lines = Some(Vec::<String>::new());
+            }
+            let lines: &mut Vec<String> = lines.as_mut().unwrap();
+            lines.push(code_page.decode(&parse_ebcdic_data(&data)?));
+        }
+    )
+);
+
 command!(SetText, [
         text: String
     ],
     command: {
-        format!("String(\"{}\")", text)
-    }
+        // escaped so that the text cannot end the string early and be read as further actions
+        format!("String(\"{}\")", text.replace('\\', "\\\\").replace('"', "\\\""))
+    },
+    validate: (
+        code_page, {
+            // a line break would end the script line, and the other controls would be read as keys
+            if let Some(character) = text.chars().find(|character| character.is_control()) {
+                return Err(format!("{:?} is a control character, which cannot be typed.", character));
+            }
+            match code_page.encode(text) {
+                Ok(_) => {
+                    Ok(())
+                },
+                Err(character) => {
+                    Err(format!("'{}' cannot be represented in code page {}.", character, code_page.get_name()))
+                }
+            }
+        }
+    )
 );
 
 command!(MoveCursorToNextField,
     command: {
-        format!("Tab")
+        String::from("Tab")
     }
 );
 
 command!(MoveCursorToPreviousField,
     command: {
-        format!("BackTab")
+        String::from("BackTab")
     }
 );
 
@@ -560,175 +602,1638 @@ command!(MoveCursorToFirstField,
 
 command!(SendEnterKey,
     command: {
-        format!("Enter")
+        String::from("Enter")
     }
 );
 
 command!(ClearTextFromField,
     command: {
-        format!("DeleteField")
+        String::from("DeleteField")
     }
 );
 
 command!(MoveCursorToFieldEnd,
     command: {
-        format!("FieldEnd")
+        String::from("FieldEnd")
     }
 );
 
-command!(WaitForCurrentField,
-    command: {
-        format!("Wait(InputField)")
-    },
-    output => is_successful: bool
-);
-
-command!(WaitForUnlock,
+command!(SendProgramFunctionKey, [
+        number: u8
+    ],
     command: {
-        format!("Wait(Unlock)")
+        format!("PF({})", number)
     },
-    output => is_successful: bool
+    validate: (
+        _code_page, {
+            if (1..=24).contains(number) {
+                Ok(())
+            }
+            else {
+                Err(format!("There is no PF{} key.", number))
+            }
+        }
+    )
 );
 
-command!(GetCursor,
+command!(SendProgramAttentionKey, [
+        number: u8
+    ],
     command: {
-        format!("Query(Cursor)")
+        format!("PA({})", number)
     },
-    output => position: (u8, u8),
-    data: (
-        data, {
-            let position_vector = data
-                .split(" ")
-                .map(|item| {
-                    println!("GetCursorCommand: parsing \"{}\"", &item); 
-                    item.parse::<u8>().expect("The coordinate should be parsable as a u8")
-                })
-                .collect::<Vec<u8>>();
-            let position_tuple = (position_vector[0], position_vector[1]);
-            
-            if position.is_some() {
-                panic!("Unexpected additional client data response with \"{}\" while already having \"{:?}\".", data, position.as_ref().unwrap());
+    validate: (
+        _code_page, {
+            if (1..=3).contains(number) {
+                Ok(())
+            }
+            else {
+                Err(format!("There is no PA{} key.", number))
             }
-            *position = Some(position_tuple);
         }
     )
 );
 
-pub struct Client {
-    process: Child
-}
-
-impl Client {
-    fn new(process: Child) -> Self {
-        Client {
-            process
-        }
+command!(SendClearKey,
+    command: {
+        String::from("Clear")
     }
-    pub fn kill(&mut self) -> Result<(), std::io::Error> {
-        self.process.kill()
+);
+
+command!(ResetKeyboard,
+    command: {
+        String::from("Reset")
     }
-}
+);
 
+command!(SendAttentionKey,
+    command: {
+        String::from("Attn")
+    }
+);
 
-#[derive(Debug)]
-pub struct TerminalConfiguration {
-    pub mainframe_address: String,
-    pub client_address: String
-}
+command!(SendSystemRequestKey,
+    command: {
+        String::from("SysReq")
+    }
+);
 
-impl TerminalConfiguration {
-    pub fn new(mainframe_address: &str, client_address: &str) -> Self {
-        TerminalConfiguration {
-            mainframe_address: String::from(mainframe_address),
-            client_address: String::from(client_address)
-        }
+command!(EraseToEndOfField,
+    command: {
+        String::from("EraseEOF")
     }
-}
+);
 
-pub trait ClientSpawner {
-    fn spawn(terminal_configuration: &TerminalConfiguration) -> Option<Client>;
-}
+command!(EraseAllInput,
+    command: {
+        String::from("EraseInput")
+    }
+);
 
-pub struct X3270ClientSpawner {}
+command!(ErasePreviousCharacter,
+    command: {
+        String::from("Erase")
+    }
+);
 
-impl ClientSpawner for X3270ClientSpawner {
-    fn spawn(terminal_configuration: &TerminalConfiguration) -> Option<Client> {
-        let client_result = std::process::Command::new("x3270")
-            .arg("-scriptport")
-            .arg(&terminal_configuration.client_address)
-            .arg("-model")
-            .arg("3279-4")
-            .arg(&terminal_configuration.mainframe_address)
-            .spawn()
-            .map(|process| {
-                Client {
-                    process
-                }
-            });
-        match client_result {
-            Ok(client) => {
-                Some(client)
-            },
-            Err(error) => {
-                println!("try_start_client_process: error starting client process with terminal configuration {:?} via error: {}", terminal_configuration, error);
-                None
-            }
-        }
+command!(DeleteCharacter,
+    command: {
+        String::from("Delete")
     }
-}
+);
 
-pub trait CommandExecutor {
-    fn connect_to_client_process(client_address: &str) -> Option<Self> where Self:Sized;
-    fn execute<TOutput>(&mut self, command: impl CommandBuilder<TOutput>) -> ExecutionResult<TOutput>;
-    fn disconnect(&mut self);
-}
+command!(EnableInsertMode,
+    command: {
+        String::from("Insert")
+    }
+);
 
-pub struct StreamCommandExecutor {
-    stream: TcpStream
-}
+command!(ToggleInsertMode,
+    command: {
+        String::from("ToggleInsert")
+    }
+);
 
-impl CommandExecutor for StreamCommandExecutor {
-    fn connect_to_client_process(client_address: &str) -> Option<Self> {
-        let stream_result = TcpStream::connect(client_address);
-        match stream_result {
-            Ok(stream) => {
-                Some(StreamCommandExecutor {
-                    stream
-                })
-            },
-            Err(error) => {
-                println!("try_connect_to_client_process: error connecting to {} via error: {}", client_address, error);
-                None
-            }
-        }
+command!(MoveCursorLeft,
+    command: {
+        String::from("Left")
     }
-    fn execute<TOutput>(&mut self, command: impl CommandBuilder<TOutput>) -> ExecutionResult<TOutput> {
-        command.execute(&mut self.stream)
+);
+
+command!(MoveCursorRight,
+    command: {
+        String::from("Right")
     }
-    fn disconnect(&mut self) {
-        let shutdown_result = self.stream.shutdown(std::net::Shutdown::Both);
-        if let Err(shutdown_error) = shutdown_result {
-            println!("Failed to disconnect via shutdown: {}", shutdown_error);
-        }
+);
+
+command!(MoveCursorUp,
+    command: {
+        String::from("Up")
     }
-}
+);
 
-#[cfg(test)]
-mod tests {
-    use std::{time::Duration, sync::Mutex};
+command!(MoveCursorDown,
+    command: {
+        String::from("Down")
+    }
+);
+
+command!(MoveCursorLeftTwice,
+    command: {
+        String::from("Left2")
+    }
+);
+
+command!(MoveCursorRightTwice,
+    command: {
+        String::from("Right2")
+    }
+);
+
+command!(MoveCursorToNextLine,
+    command: {
+        String::from("Newline")
+    }
+);
+
+command!(SendDuplicate,
+    command: {
+        String::from("Dup")
+    }
+);
+
+command!(SendFieldMark,
+    command: {
+        String::from("FieldMark")
+    }
+);
+
+command!(SelectAtCursor,
+    command: {
+        String::from("CursorSelect")
+    }
+);
+
+command!(SendAid, [
+        aid: Aid
+    ],
+    command: {
+        aid.get_action()
+    }
+);
+
+const WAIT_CONNECTED_REFUSAL: &str = "x3270 has no Wait keyword for a connection, so wait with MainframeProvider::wait, which polls Query(ConnectionState).";
+
+command!(Wait, [
+        condition: WaitCondition
+    ],
+    command: {
+        format!("Wait({})", condition.get_keyword())
+    },
+    validate: (
+        _code_page, {
+            match condition {
+                WaitCondition::Seconds => {
+                    Err(String::from("Waiting for seconds needs a timeout."))
+                },
+                WaitCondition::Connected => {
+                    Err(String::from(WAIT_CONNECTED_REFUSAL))
+                },
+                _ => {
+                    Ok(())
+                }
+            }
+        }
+    )
+);
+
+// x3270 gives up on the condition once the timeout passes, failing with "Wait timed out"
+command!(WaitWithTimeout, [
+        condition: WaitCondition,
+        timeout: Duration
+    ],
+    command: {
+        format!("Wait({},{})", format_seconds(timeout), condition.get_keyword())
+    },
+    validate: (
+        _code_page, {
+            if *condition == WaitCondition::Connected {
+                Err(String::from(WAIT_CONNECTED_REFUSAL))
+            }
+            else {
+                Ok(())
+            }
+        }
+    )
+);
+
+// a wait that ends in time succeeds without data, while one that runs out of time fails
+command!(WaitForCurrentField,
+    command: {
+        String::from("Wait(InputField)")
+    }
+);
+
+command!(WaitForUnlock,
+    command: {
+        String::from("Wait(Unlock)")
+    }
+);
+
+// every field with its attributes and content, read in one round trip without moving the cursor
+pub struct ReadBufferCommand {
+    lines: RefCell<Vec<String>>
+}
+
+impl ReadBufferCommand {
+    pub fn new() -> Self {
+        ReadBufferCommand {
+            lines: RefCell::new(Vec::new())
+        }
+    }
+}
+
+impl CommandBuilder<Vec<Field>> for ReadBufferCommand {
+    fn get_client_message(&self) -> String {
+        String::from("ReadBuffer(Ascii)")
+    }
+    fn append_client_data_response(&self, data: String) -> Result<(), String> {
+        self.lines.borrow_mut().push(data);
+        Ok(())
+    }
+    fn build(self) -> Result<Vec<Field>, String> {
+        parse_read_buffer(&self.lines.into_inner())
+    }
+}
+
+command!(GetCursor,
+    command: {
+        String::from("Query(Cursor)")
+    },
+    output => position: (usize, usize),
+    data: (
+        data, {
+            let position_vector = data
+                .split(' ')
+                .map(|item| item.parse::<usize>())
+                .collect::<Result<Vec<usize>, _>>()
+                .map_err(|_| format!("The cursor position \"{}\" should be two numbers.", data))?;
+            if position_vector.len() != 2 {
+                return Err(format!("The cursor position \"{}\" should be two numbers.", data));
+            }
+            let position_tuple = (position_vector[0], position_vector[1]);
+
+            if position.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\" while already having \"{:?}\".", data, position.as_ref().unwrap()));
+            }
+            *position = Some(position_tuple);
+        }
+    )
+);
+
+command!(QueryHost,
+    command: {
+        String::from("Query(Host)")
+    },
+    output => host: Option<HostInfo>,
+    data: (
+        data, {
+            if host.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\".", data));
+            }
+            *host = Some(HostInfo::parse(&data)?);
+        }
+    )
+);
+
+command!(QueryLuName,
+    command: {
+        String::from("Query(LuName)")
+    },
+    output => lu_name: Option<String>,
+    data: (
+        data, {
+            if lu_name.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\".", data));
+            }
+            *lu_name = Some(parse_lu_name(&data));
+        }
+    )
+);
+
+command!(QueryModel,
+    command: {
+        String::from("Query(Model)")
+    },
+    output => model: TerminalModel,
+    data: (
+        data, {
+            if model.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\".", data));
+            }
+            *model = Some(TerminalModel::parse(&data)?);
+        }
+    )
+);
+
+command!(QueryScreenSize,
+    command: {
+        String::from("Query(ScreenCurSize)")
+    },
+    output => screen_size: ScreenSize,
+    data: (
+        data, {
+            if screen_size.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\".", data));
+            }
+            *screen_size = Some(ScreenSize::parse(&data)?);
+        }
+    )
+);
+
+command!(QueryMaximumScreenSize,
+    command: {
+        String::from("Query(ScreenMaxSize)")
+    },
+    output => screen_size: ScreenSize,
+    data: (
+        data, {
+            if screen_size.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\".", data));
+            }
+            *screen_size = Some(ScreenSize::parse(&data)?);
+        }
+    )
+);
+
+command!(QueryFormatted,
+    command: {
+        String::from("Query(Formatted)")
+    },
+    output => is_formatted: bool,
+    data: (
+        data, {
+            if is_formatted.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\".", data));
+            }
+            *is_formatted = Some(parse_formatted(&data)?);
+        }
+    )
+);
+
+command!(QueryCodePage,
+    command: {
+        String::from("Query(CodePage)")
+    },
+    output => code_page: CodePage,
+    data: (
+        data, {
+            if code_page.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\".", data));
+            }
+            *code_page = Some(CodePage::from_name(data.trim())
+                .ok_or_else(|| format!("\"{}\" is not a supported code page.", data))?);
+        }
+    )
+);
+
+command!(QueryTls,
+    command: {
+        String::from("Query(Tls)")
+    },
+    output => tls_state: TlsState,
+    data: (
+        data, {
+            if tls_state.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\".", data));
+            }
+            *tls_state = Some(TlsState::parse(&data)?);
+        }
+    )
+);
+
+command!(QueryConnectionState,
+    command: {
+        String::from("Query(ConnectionState)")
+    },
+    output => session_state: SessionState,
+    data: (
+        data, {
+            if session_state.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\".", data));
+            }
+            *session_state = Some(SessionState::parse(&data)?);
+        }
+    )
+);
+
+// the same as GetCursor, counting rows and columns from 1
+command!(GetCursorFromOne,
+    command: {
+        String::from("Query(Cursor1)")
+    },
+    output => position: (usize, usize),
+    data: (
+        data, {
+            let position_vector = data
+                .split(' ')
+                .map(|item| item.parse::<usize>())
+                .collect::<Result<Vec<usize>, _>>()
+                .map_err(|_| format!("The cursor position \"{}\" should be two numbers.", data))?;
+            if position_vector.len() != 2 {
+                return Err(format!("The cursor position \"{}\" should be two numbers.", data));
+            }
+            if position.is_some() {
+                return Err(format!("Unexpected additional client data response with \"{}\" while already having \"{:?}\".", data, position.as_ref().unwrap()));
+            }
+            *position = Some((position_vector[0], position_vector[1]));
+        }
+    )
+);
+
+// how long to wait for the last of stderr once the process has exited
+const STDERR_DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+// how long an emulator has to exit after Quit before it is killed
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+// how often a shutdown checks whether the emulator has exited
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+pub struct Client {
+    process: Child,
+    // everything the process has written to stderr so far, when it was spawned with stderr piped
+    stderr: Arc<Mutex<Vec<u8>>>,
+    // signalled once stderr is closed
+    stderr_closed: Option<Receiver<()>>
+}
+
+impl Client {
+    fn new(mut process: Child) -> Self {
+        let stderr = Arc::new(Mutex::new(Vec::new()));
+        let stderr_closed = process.stderr.take().map(|mut process_stderr| {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            let stderr = stderr.clone();
+            std::thread::spawn(move || {
+                let mut buffer = [0u8; 4096];
+                while let Ok(read_length @ 1..) = process_stderr.read(&mut buffer) {
+                    stderr.lock()
+                        .expect("The stderr buffer should not be poisoned.")
+                        .extend_from_slice(&buffer[..read_length]);
+                }
+                let _ = sender.send(());
+            });
+            receiver
+        });
+        Client {
+            process,
+            stderr,
+            stderr_closed
+        }
+    }
+    pub fn kill(&mut self) -> Result<(), std::io::Error> {
+        self.process.kill()
+    }
+    pub fn get_id(&self) -> u32 {
+        self.process.id()
+    }
+    // whether the process is still running, where a process that cannot be checked counts as exited
+    pub fn is_alive(&mut self) -> bool {
+        matches!(self.process.try_wait(), Ok(None))
+    }
+    // the exit status once the process has exited, or None while it is still running
+    pub fn try_get_exit_status(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        self.process.try_wait()
+    }
+    // asks the emulator to exit with Quit through its executor, then kills it if it has not exited within the timeout
+    pub fn shutdown(&mut self, command_executor: &mut impl CommandExecutor, timeout: Duration) -> std::io::Result<ExitStatus> {
+        command_executor.quit();
+        self.wait_or_kill(timeout)
+    }
+    // waits for the process to exit, killing it once the timeout passes
+    pub fn wait_or_kill(&mut self, timeout: Duration) -> std::io::Result<ExitStatus> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(status) = self.process.try_wait()? {
+                return Ok(status);
+            }
+            let remaining_time = deadline.saturating_duration_since(Instant::now());
+            if remaining_time.is_zero() {
+                break;
+            }
+            std::thread::sleep(remaining_time.min(SHUTDOWN_POLL_INTERVAL));
+        }
+        tracing::warn!(id = self.process.id(), ?timeout, "the client did not exit in time, so it is being killed");
+        self.process.kill()?;
+        self.process.wait()
+    }
+    // what the process has written to stderr so far
+    pub fn get_stderr(&self) -> String {
+        let stderr = self.stderr.lock()
+            .expect("The stderr buffer should not be poisoned.");
+        String::from_utf8_lossy(&stderr).into_owned()
+    }
+    // all of stderr once the process has exited, waiting briefly for the reader to catch up
+    pub fn drain_stderr(&mut self) -> String {
+        if let Some(stderr_closed) = self.stderr_closed.take() {
+            let _ = stderr_closed.recv_timeout(STDERR_DRAIN_TIMEOUT);
+        }
+        self.get_stderr()
+    }
+    // the scripting stream of a client spawned with piped stdin and stdout, such as by the S3270ClientSpawner
+    pub fn take_pipe(&mut self) -> Option<ClientPipe> {
+        let stdin = self.process.stdin.take()?;
+        let stdout = self.process.stdout.take()?;
+        Some(ClientPipe::new(stdin, stdout))
+    }
+}
+
+// a client that was never shut down, such as when a test panics, is killed rather than orphaned
+impl Drop for Client {
+    fn drop(&mut self) {
+        if let Ok(None) = self.process.try_wait() {
+            tracing::warn!(id = self.process.id(), "killing the client that was still running when dropped");
+            if let Err(kill_error) = self.process.kill() {
+                tracing::warn!(%kill_error, "unable to kill the client process");
+            }
+        }
+        if let Err(wait_error) = self.process.wait() {
+            tracing::warn!(%wait_error, "unable to reap the client process");
+        }
+    }
+}
+
+// the stdin and stdout of a client process as a single stream
+pub struct ClientPipe {
+    stdin: ChildStdin,
+    // stdout cannot time out, so a thread reads it and hands over each chunk
+    receiver: Receiver<std::io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    chunk_offset: usize,
+    read_timeout: Option<Duration>
+}
+
+impl ClientPipe {
+    fn new(stdin: ChildStdin, mut stdout: ChildStdout) -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+            loop {
+                let read_result = stdout.read(&mut buffer)
+                    .map(|read_length| buffer[..read_length].to_vec());
+                let is_finished = !matches!(&read_result, Ok(chunk) if !chunk.is_empty());
+                if sender.send(read_result).is_err() || is_finished {
+                    break;
+                }
+            }
+        });
+        ClientPipe {
+            stdin,
+            receiver,
+            chunk: Vec::new(),
+            chunk_offset: 0,
+            read_timeout: None
+        }
+    }
+}
+
+impl Read for ClientPipe {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.chunk_offset == self.chunk.len() {
+            let receive_result = match self.read_timeout {
+                Some(read_timeout) => {
+                    self.receiver.recv_timeout(read_timeout)
+                },
+                None => {
+                    self.receiver.recv()
+                        .map_err(|_| RecvTimeoutError::Disconnected)
+                }
+            };
+            match receive_result {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.chunk_offset = 0;
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "The client did not respond in time."));
+                },
+                Err(RecvTimeoutError::Disconnected) => {
+                    // the client closed its stdout
+                    return Ok(0);
+                }
+            }
+        }
+        let read_length = buf.len().min(self.chunk.len() - self.chunk_offset);
+        buf[..read_length].copy_from_slice(&self.chunk[self.chunk_offset..self.chunk_offset + read_length]);
+        self.chunk_offset += read_length;
+        Ok(read_length)
+    }
+}
+
+impl Write for ClientPipe {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.stdin.write(buf)
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stdin.flush()
+    }
+}
+
+// a scripting stream whose reads and writes can be bounded
+pub trait ScriptStream: Read + Write {
+    fn set_timeouts(&mut self, timeouts: &Timeouts) -> std::io::Result<()>;
+}
+
+impl ScriptStream for TcpStream {
+    fn set_timeouts(&mut self, timeouts: &Timeouts) -> std::io::Result<()> {
+        self.set_read_timeout(timeouts.read)?;
+        self.set_write_timeout(timeouts.write)
+    }
+}
+
+impl ScriptStream for ClientPipe {
+    // writes to stdin are not bounded since the client drains them as it reads each command
+    fn set_timeouts(&mut self, timeouts: &Timeouts) -> std::io::Result<()> {
+        self.read_timeout = timeouts.read;
+        Ok(())
+    }
+}
+
+// what a Wait action waits for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitCondition {
+    // the keyboard is unlocked on a formatted screen
+    InputField,
+    Unlock,
+    // the host has written to the screen since the previous wait for output
+    Output,
+    Mode3270,
+    NvtMode,
+    // connected in any mode, which x3270 has no Wait keyword for, so the Wait commands refuse it and MainframeProvider::wait polls Query(ConnectionState) instead
+    Connected,
+    Disconnected,
+    // only the timeout, which must be given
+    Seconds
+}
+
+impl WaitCondition {
+    pub fn get_keyword(&self) -> &'static str {
+        match self {
+            WaitCondition::InputField => "InputField",
+            WaitCondition::Unlock => "Unlock",
+            WaitCondition::Output => "Output",
+            WaitCondition::Mode3270 => "3270Mode",
+            WaitCondition::Connected => "Connected",
+            WaitCondition::NvtMode => "NVTMode",
+            WaitCondition::Disconnected => "Disconnect",
+            WaitCondition::Seconds => "Seconds"
+        }
+    }
+    pub fn from_keyword(keyword: &str) -> Option<WaitCondition> {
+        match keyword {
+            "InputField" => Some(WaitCondition::InputField),
+            "Unlock" => Some(WaitCondition::Unlock),
+            "Output" => Some(WaitCondition::Output),
+            "3270Mode" => Some(WaitCondition::Mode3270),
+            "NVTMode" => Some(WaitCondition::NvtMode),
+            "Disconnect" => Some(WaitCondition::Disconnected),
+            "Seconds" => Some(WaitCondition::Seconds),
+            _ => None
+        }
+    }
+}
+
+// how a wait ended, for the endings that are not failures of the command itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitOutcome {
+    ConditionMet,
+    TimedOut,
+    Disconnected
+}
+
+impl WaitOutcome {
+    // sorts the result of a wait command, leaving other failures such as a rejected condition as errors
+    pub fn from_result(result: Result<(), Rs3270Error>) -> Result<WaitOutcome, Rs3270Error> {
+        match result {
+            Ok(()) => {
+                Ok(WaitOutcome::ConditionMet)
+            },
+            Err(Rs3270Error::Timeout { .. }) => {
+                Ok(WaitOutcome::TimedOut)
+            },
+            Err(Rs3270Error::Emulator { lines, .. }) if lines.iter().any(|line| line.to_lowercase().contains("timed out")) => {
+                Ok(WaitOutcome::TimedOut)
+            },
+            Err(Rs3270Error::Disconnected { .. }) => {
+                Ok(WaitOutcome::Disconnected)
+            },
+            Err(error) => {
+                Err(error)
+            }
+        }
+    }
+}
+
+// whole seconds where possible, since older x3270 releases only accept those
+fn format_seconds(duration: &Duration) -> String {
+    if duration.subsec_nanos() == 0 {
+        duration.as_secs().to_string()
+    }
+    else {
+        format!("{:.3}", duration.as_secs_f64())
+    }
+}
+
+// None waits without limit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Timeouts {
+    pub connect: Option<Duration>,
+    pub write: Option<Duration>,
+    // how long a command may wait for its response, including waits for the host to unlock the keyboard
+    pub read: Option<Duration>
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            connect: Some(Duration::from_secs(30)),
+            write: Some(Duration::from_secs(30)),
+            read: Some(Duration::from_secs(60))
+        }
+    }
+}
+
+// connects to the first address that accepts within the timeout, as TcpStream::connect does without one
+pub(crate) fn connect_with_timeout(address: &str, timeout: Option<Duration>) -> std::io::Result<TcpStream> {
+    let timeout = match timeout {
+        Some(timeout) => {
+            timeout
+        },
+        None => {
+            return TcpStream::connect(address);
+        }
+    };
+    let mut last_error = None;
+    for socket_address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&socket_address, timeout) {
+            Ok(stream) => {
+                return Ok(stream);
+            },
+            Err(error) => {
+                last_error = Some(error);
+            }
+        }
+    }
+    Err(last_error.unwrap_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} did not resolve to any address.", address))))
+}
+
+#[derive(Debug)]
+pub struct TerminalConfiguration {
+    pub mainframe_address: String,
+    pub client_address: String,
+    // None leaves the emulator on its own default code page, while the executors check text against CP037
+    pub code_page: Option<CodePage>,
+    pub lu_name: Option<String>,
+    pub tls: Option<TlsConfiguration>,
+    pub timeouts: Timeouts,
+    pub screen_model: ScreenModel,
+    // the port used when the mainframe address has none
+    pub port: Option<u16>,
+    // the emulator's own -connecttimeout, separate from the timeouts of the executor's socket
+    pub emulator_connect_timeout: Option<Duration>,
+    // an x3270 keymap name, such as "rctrl"
+    pub keymap: Option<String>,
+    // the SOCKS5 or HTTP proxy that the mainframe is reached through
+    pub proxy: Option<ProxyConfiguration>,
+    // where the emulator traces the data stream and its actions
+    pub trace_file_path: Option<PathBuf>,
+    // the emulator binary to run instead of the one found on the PATH
+    pub executable_path: Option<PathBuf>,
+    // passed to the emulator as they are, after every other option and before the host
+    pub extra_arguments: Vec<String>
+}
+
+impl TerminalConfiguration {
+    pub fn new(mainframe_address: &str, client_address: &str) -> Self {
+        TerminalConfiguration {
+            mainframe_address: String::from(mainframe_address),
+            client_address: String::from(client_address),
+            code_page: None,
+            lu_name: None,
+            tls: None,
+            timeouts: Timeouts::default(),
+            screen_model: ScreenModel::Model4,
+            port: None,
+            emulator_connect_timeout: None,
+            keymap: None,
+            proxy: None,
+            trace_file_path: None,
+            executable_path: None,
+            extra_arguments: Vec::new()
+        }
+    }
+    pub fn builder(mainframe_address: &str) -> TerminalConfigurationBuilder {
+        TerminalConfigurationBuilder::new(mainframe_address)
+    }
+    // the code page that the executors check text against
+    pub fn get_code_page(&self) -> CodePage {
+        self.code_page.unwrap_or(CodePage::Cp037)
+    }
+    // x3270 takes the LU and implicit TLS as prefixes of the host, such as "L:LU01@host:port"
+    pub fn get_x3270_host(&self) -> String {
+        let mut host = match &self.lu_name {
+            Some(lu_name) => {
+                format!("{}@{}", lu_name, self.mainframe_address)
+            },
+            None => {
+                self.mainframe_address.clone()
+            }
+        };
+        if let Some(TlsMode::Implicit) = self.tls.as_ref().map(|tls| tls.mode) {
+            host = format!("L:{}", host);
+        }
+        host
+    }
+}
+
+pub struct TerminalConfigurationBuilder {
+    terminal_configuration: TerminalConfiguration
+}
+
+impl TerminalConfigurationBuilder {
+    pub fn new(mainframe_address: &str) -> Self {
+        TerminalConfigurationBuilder {
+            terminal_configuration: TerminalConfiguration::new(mainframe_address, "")
+        }
+    }
+    // the script port of an x3270 client, such as "localhost:3271"
+    pub fn with_client_address(mut self, client_address: &str) -> Self {
+        self.terminal_configuration.client_address = String::from(client_address);
+        self
+    }
+    pub fn with_screen_model(mut self, screen_model: ScreenModel) -> Self {
+        self.terminal_configuration.screen_model = screen_model;
+        self
+    }
+    pub fn with_oversize(mut self, rows: usize, columns: usize) -> Self {
+        self.terminal_configuration.screen_model = ScreenModel::Oversize { rows, columns };
+        self
+    }
+    pub fn with_code_page(mut self, code_page: CodePage) -> Self {
+        self.terminal_configuration.code_page = Some(code_page);
+        self
+    }
+    pub fn with_lu_name(mut self, lu_name: &str) -> Self {
+        self.terminal_configuration.lu_name = Some(String::from(lu_name));
+        self
+    }
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.terminal_configuration.port = Some(port);
+        self
+    }
+    pub fn with_tls(mut self, tls_configuration: TlsConfiguration) -> Self {
+        self.terminal_configuration.tls = Some(tls_configuration);
+        self
+    }
+    pub fn with_keymap(mut self, keymap: &str) -> Self {
+        self.terminal_configuration.keymap = Some(String::from(keymap));
+        self
+    }
+    pub fn with_proxy(mut self, proxy_configuration: ProxyConfiguration) -> Self {
+        self.terminal_configuration.proxy = Some(proxy_configuration);
+        self
+    }
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.terminal_configuration.timeouts = timeouts;
+        self
+    }
+    // how long the executor waits to connect, and how long spawn_and_wait waits for the emulator to answer
+    pub fn with_connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.terminal_configuration.timeouts.connect = Some(connect_timeout);
+        self
+    }
+    // how long the emulator itself waits to connect to the mainframe
+    pub fn with_emulator_connect_timeout(mut self, emulator_connect_timeout: Duration) -> Self {
+        self.terminal_configuration.emulator_connect_timeout = Some(emulator_connect_timeout);
+        self
+    }
+    pub fn with_trace_file(mut self, trace_file_path: impl Into<PathBuf>) -> Self {
+        self.terminal_configuration.trace_file_path = Some(trace_file_path.into());
+        self
+    }
+    pub fn with_executable_path(mut self, executable_path: impl Into<PathBuf>) -> Self {
+        self.terminal_configuration.executable_path = Some(executable_path.into());
+        self
+    }
+    pub fn with_extra_argument(mut self, argument: &str) -> Self {
+        self.terminal_configuration.extra_arguments.push(String::from(argument));
+        self
+    }
+    pub fn build(self) -> Result<TerminalConfiguration, String> {
+        let terminal_configuration = self.terminal_configuration;
+        if terminal_configuration.mainframe_address.is_empty() {
+            return Err(String::from("The mainframe address is empty."));
+        }
+        terminal_configuration.screen_model.validate()?;
+        if terminal_configuration.port == Some(0) {
+            return Err(String::from("The port must not be 0."));
+        }
+        if terminal_configuration.timeouts.connect.is_some_and(|connect| connect.is_zero()) {
+            return Err(String::from("The connect timeout must not be zero."));
+        }
+        if terminal_configuration.emulator_connect_timeout.is_some_and(|emulator_connect_timeout| emulator_connect_timeout.is_zero()) {
+            return Err(String::from("The emulator connect timeout must not be zero."));
+        }
+        Ok(terminal_configuration)
+    }
+}
+
+// how long spawn_and_wait pauses between attempts, doubling from the first pause up to the longest
+const READINESS_FIRST_BACKOFF: Duration = Duration::from_millis(50);
+const READINESS_LONGEST_BACKOFF: Duration = Duration::from_secs(1);
+// how long a single readiness query may wait for its answer
+const READINESS_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug)]
+pub enum SpawnError {
+    // the spawner cannot honor the configuration
+    Configuration(String),
+    // the emulator executable could not be started
+    Start(std::io::Error),
+    // the emulator exited before answering, with everything it wrote to stderr
+    Exited {
+        status: ExitStatus,
+        stderr: String
+    },
+    // the emulator was still running but had not answered by the connect timeout
+    NotReady {
+        message: String,
+        stderr: String
+    }
+}
+
+impl std::fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpawnError::Configuration(message) => {
+                write!(f, "The emulator cannot be configured: {}", message)
+            },
+            SpawnError::Start(error) => {
+                write!(f, "The emulator could not be started: {}", error)
+            },
+            SpawnError::Exited { status, stderr } => {
+                write!(f, "The emulator exited with {} before it was ready: {}", status, stderr.trim())
+            },
+            SpawnError::NotReady { message, stderr } => {
+                write!(f, "The emulator was not ready in time: {} {}", message, stderr.trim())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpawnError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SpawnError::Start(error) => {
+                Some(error)
+            },
+            _ => {
+                None
+            }
+        }
+    }
+}
+
+pub trait ClientSpawner {
+    type CommandExecutor: CommandExecutor;
+    // the emulator's command line, or a message naming the first configured option that this emulator cannot honor
+    fn get_arguments(terminal_configuration: &TerminalConfiguration) -> Result<Vec<String>, String>;
+    // starts the emulator without waiting for it to be ready
+    fn start(terminal_configuration: &TerminalConfiguration) -> Result<Client, SpawnError>;
+    fn spawn(terminal_configuration: &TerminalConfiguration) -> Option<Client> {
+        match Self::start(terminal_configuration) {
+            Ok(client) => {
+                Some(client)
+            },
+            Err(error) => {
+                tracing::error!(?terminal_configuration, %error, "unable to spawn the client");
+                None
+            }
+        }
+    }
+    // starts the emulator and polls it with backoff until it answers a no-op query, giving up at the connect timeout
+    fn spawn_and_wait(terminal_configuration: &TerminalConfiguration) -> Result<(Client, Self::CommandExecutor), SpawnError>;
+}
+
+// retries the attempt with backoff until it succeeds, the client exits or the timeout passes
+fn wait_until_ready<TOutput>(client: &mut Client, timeout: Option<Duration>, mut attempt: impl FnMut(&mut Client) -> Result<TOutput, String>) -> Result<TOutput, SpawnError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut backoff = READINESS_FIRST_BACKOFF;
+    loop {
+        if let Some(status) = client.try_get_exit_status().map_err(SpawnError::Start)? {
+            return Err(SpawnError::Exited {
+                status,
+                stderr: client.drain_stderr()
+            });
+        }
+        let message = match attempt(client) {
+            Ok(output) => {
+                return Ok(output);
+            },
+            Err(message) => {
+                message
+            }
+        };
+        let remaining_time = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if remaining_time.is_some_and(|remaining_time| remaining_time.is_zero()) {
+            if let Some(status) = client.try_get_exit_status().map_err(SpawnError::Start)? {
+                return Err(SpawnError::Exited {
+                    status,
+                    stderr: client.drain_stderr()
+                });
+            }
+            return Err(SpawnError::NotReady {
+                message,
+                stderr: client.get_stderr()
+            });
+        }
+        tracing::debug!(%message, ?backoff, "the client is not ready yet");
+        std::thread::sleep(remaining_time.map_or(backoff, |remaining_time| remaining_time.min(backoff)));
+        backoff = (backoff * 2).min(READINESS_LONGEST_BACKOFF);
+    }
+}
+
+// the options shared by x3270 and s3270 around the ones only one of them takes, ending with the host to connect to
+// only the options that were chosen are passed, so that the emulator keeps its own defaults for the rest
+fn get_emulator_arguments(terminal_configuration: &TerminalConfiguration, spawner_arguments: Vec<String>) -> Vec<String> {
+    let screen_model = terminal_configuration.screen_model;
+    let mut arguments = vec![
+        String::from("-model"),
+        format!("3279-{}", screen_model.get_number())
+    ];
+    if let Some(code_page) = terminal_configuration.code_page {
+        arguments.push(String::from("-codepage"));
+        arguments.push(String::from(code_page.get_name()));
+    }
+    if let ScreenModel::Oversize { rows, columns } = screen_model {
+        arguments.push(String::from("-oversize"));
+        arguments.push(format!("{columns}x{rows}"));
+    }
+    arguments.extend(spawner_arguments);
+    if let Some(port) = terminal_configuration.port {
+        arguments.push(String::from("-port"));
+        arguments.push(port.to_string());
+    }
+    if let Some(proxy) = &terminal_configuration.proxy {
+        arguments.push(String::from("-proxy"));
+        arguments.push(proxy.get_x3270_argument());
+    }
+    if let Some(connect) = terminal_configuration.emulator_connect_timeout {
+        // whole seconds, rounded up so that a short timeout is not turned into none
+        let seconds = connect.as_secs() + u64::from(connect.subsec_nanos() > 0);
+        arguments.push(String::from("-connecttimeout"));
+        arguments.push(seconds.to_string());
+    }
+    if let Some(trace_file_path) = &terminal_configuration.trace_file_path {
+        arguments.push(String::from("-trace"));
+        arguments.push(String::from("-tracefile"));
+        arguments.push(trace_file_path.display().to_string());
+    }
+    if let Some(tls) = &terminal_configuration.tls {
+        arguments.extend(tls.get_x3270_arguments());
+    }
+    arguments.extend(terminal_configuration.extra_arguments.iter().cloned());
+    arguments.push(terminal_configuration.get_x3270_host());
+    arguments
+}
+
+fn get_executable(terminal_configuration: &TerminalConfiguration, default_executable: &str) -> PathBuf {
+    terminal_configuration.executable_path
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(default_executable))
+}
+
+pub struct X3270ClientSpawner {}
+
+impl ClientSpawner for X3270ClientSpawner {
+    type CommandExecutor = StreamCommandExecutor;
+    fn get_arguments(terminal_configuration: &TerminalConfiguration) -> Result<Vec<String>, String> {
+        if terminal_configuration.client_address.is_empty() {
+            return Err(String::from("x3270 needs a client address for its script port."));
+        }
+        let mut spawner_arguments = vec![
+            String::from("-scriptport"),
+            terminal_configuration.client_address.clone()
+        ];
+        if let Some(keymap) = &terminal_configuration.keymap {
+            spawner_arguments.push(String::from("-keymap"));
+            spawner_arguments.push(keymap.clone());
+        }
+        Ok(get_emulator_arguments(terminal_configuration, spawner_arguments))
+    }
+    fn start(terminal_configuration: &TerminalConfiguration) -> Result<Client, SpawnError> {
+        let arguments = Self::get_arguments(terminal_configuration)
+            .map_err(SpawnError::Configuration)?;
+        let process = std::process::Command::new(get_executable(terminal_configuration, "x3270"))
+            .args(arguments)
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(SpawnError::Start)?;
+        Ok(Client::new(process))
+    }
+    // polls the script port, since x3270 opens it only once it has started
+    fn spawn_and_wait(terminal_configuration: &TerminalConfiguration) -> Result<(Client, StreamCommandExecutor), SpawnError> {
+        let mut client = Self::start(terminal_configuration)?;
+        let timeouts = &terminal_configuration.timeouts;
+        let readiness_timeouts = Timeouts {
+            read: Some(READINESS_QUERY_TIMEOUT),
+            ..timeouts.clone()
+        };
+        let wait_result = wait_until_ready(&mut client, timeouts.connect, |_| {
+            let mut stream_command_executor = StreamCommandExecutor::connect(&terminal_configuration.client_address, timeouts.clone())
+                .map_err(|error| format!("the script port {} did not accept a connection: {}", terminal_configuration.client_address, error))?;
+            stream_command_executor.execute_with_timeouts(QueryConnectionStateCommand::new(), &readiness_timeouts)
+                .map_err(|error| error.to_string())?;
+            Ok(stream_command_executor)
+        });
+        match wait_result {
+            Ok(mut stream_command_executor) => {
+                stream_command_executor.set_code_page(terminal_configuration.get_code_page());
+                Ok((client, stream_command_executor))
+            },
+            Err(error) => {
+                let _ = client.kill();
+                Err(error)
+            }
+        }
+    }
+}
+
+// runs the headless s3270 with its scripting stream on stdin and stdout, so the client_address is not used
+pub struct S3270ClientSpawner {}
+
+impl ClientSpawner for S3270ClientSpawner {
+    type CommandExecutor = PipeCommandExecutor;
+    fn get_arguments(terminal_configuration: &TerminalConfiguration) -> Result<Vec<String>, String> {
+        if terminal_configuration.keymap.is_some() {
+            return Err(String::from("s3270 has no keyboard, so it does not take a keymap."));
+        }
+        Ok(get_emulator_arguments(terminal_configuration, Vec::new()))
+    }
+    fn start(terminal_configuration: &TerminalConfiguration) -> Result<Client, SpawnError> {
+        let arguments = Self::get_arguments(terminal_configuration)
+            .map_err(SpawnError::Configuration)?;
+        let process = std::process::Command::new(get_executable(terminal_configuration, "s3270"))
+            .args(arguments)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(SpawnError::Start)?;
+        Ok(Client::new(process))
+    }
+    // s3270 reads its stdin once it has started, so the same query waits for longer on each attempt and a late answer is discarded
+    fn spawn_and_wait(terminal_configuration: &TerminalConfiguration) -> Result<(Client, PipeCommandExecutor), SpawnError> {
+        let mut client = Self::start(terminal_configuration)?;
+        let mut pipe_command_executor = PipeCommandExecutor::new(&mut client)
+            .expect("s3270 should be spawned with piped stdin and stdout");
+        pipe_command_executor.set_timeouts(terminal_configuration.timeouts.clone());
+        let mut query_timeout = READINESS_FIRST_BACKOFF;
+        let wait_result = wait_until_ready(&mut client, terminal_configuration.timeouts.connect, |_| {
+            let readiness_timeouts = Timeouts {
+                read: Some(query_timeout),
+                ..terminal_configuration.timeouts.clone()
+            };
+            query_timeout = (query_timeout * 2).min(READINESS_QUERY_TIMEOUT);
+            pipe_command_executor.execute_with_timeouts(QueryConnectionStateCommand::new(), &readiness_timeouts)
+                .map_err(|error| error.to_string())
+        });
+        match wait_result {
+            Ok(_) => {
+                pipe_command_executor.set_code_page(terminal_configuration.get_code_page());
+                Ok((client, pipe_command_executor))
+            },
+            Err(error) => {
+                let _ = client.kill();
+                Err(error)
+            }
+        }
+    }
+}
+
+// keeps an emulator running, respawning it and reconnecting its executor whenever it is found to have exited
+pub struct ClientSupervisor<TClientSpawner: ClientSpawner> {
+    terminal_configuration: TerminalConfiguration,
+    client: Client,
+    command_executor: TClientSpawner::CommandExecutor,
+    // None to respawn without limit
+    maximum_restart_count: Option<usize>,
+    restart_count: usize,
+    shutdown_timeout: Duration
+}
+
+impl<TClientSpawner: ClientSpawner> ClientSupervisor<TClientSpawner> {
+    pub fn start(terminal_configuration: TerminalConfiguration) -> Result<Self, SpawnError> {
+        let (client, command_executor) = TClientSpawner::spawn_and_wait(&terminal_configuration)?;
+        Ok(ClientSupervisor {
+            terminal_configuration,
+            client,
+            command_executor,
+            maximum_restart_count: Some(3),
+            restart_count: 0,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT
+        })
+    }
+    pub fn set_maximum_restart_count(&mut self, maximum_restart_count: Option<usize>) {
+        self.maximum_restart_count = maximum_restart_count;
+    }
+    pub fn set_shutdown_timeout(&mut self, shutdown_timeout: Duration) {
+        self.shutdown_timeout = shutdown_timeout;
+    }
+    pub fn get_restart_count(&self) -> usize {
+        self.restart_count
+    }
+    pub fn get_client(&mut self) -> &mut Client {
+        &mut self.client
+    }
+    // respawns the emulator if it has exited, returning whether it did, or the exit of the last one once the restarts are used up
+    // a respawn that fails still uses up a restart, so the limit caps every attempt
+    pub fn ensure_running(&mut self) -> Result<bool, SpawnError> {
+        let status = match self.client.try_get_exit_status().map_err(SpawnError::Start)? {
+            Some(status) => {
+                status
+            },
+            None => {
+                return Ok(false);
+            }
+        };
+        let stderr = self.client.drain_stderr();
+        tracing::warn!(%status, stderr = stderr.trim(), restart_count = self.restart_count, "the client exited");
+        if self.maximum_restart_count.is_some_and(|maximum_restart_count| self.restart_count >= maximum_restart_count) {
+            return Err(SpawnError::Exited {
+                status,
+                stderr
+            });
+        }
+        self.restart_count += 1;
+        let (client, command_executor) = TClientSpawner::spawn_and_wait(&self.terminal_configuration)?;
+        self.client = client;
+        self.command_executor = command_executor;
+        tracing::info!(restart_count = self.restart_count, "respawned the client");
+        Ok(true)
+    }
+    // the executor of a running emulator, respawning it first if it has exited
+    pub fn get_command_executor(&mut self) -> Result<&mut TClientSpawner::CommandExecutor, SpawnError> {
+        self.ensure_running()?;
+        Ok(&mut self.command_executor)
+    }
+    // quits the emulator, killing it if it has not exited within the shutdown timeout
+    pub fn shutdown(mut self) -> std::io::Result<ExitStatus> {
+        self.client.shutdown(&mut self.command_executor, self.shutdown_timeout)
+    }
+}
+
+pub trait CommandExecutor {
+    fn connect_to_client_process(client_address: &str) -> Option<Self> where Self:Sized;
+    fn execute<TOutput>(&mut self, command: impl CommandBuilder<TOutput>) -> Result<TOutput, Rs3270Error> {
+        let timeouts = self.get_timeouts().clone();
+        self.execute_with_timeouts(command, &timeouts)
+    }
+    // overrides the executor's timeouts for a single command
+    fn execute_with_timeouts<TOutput>(&mut self, command: impl CommandBuilder<TOutput>, timeouts: &Timeouts) -> Result<TOutput, Rs3270Error>;
+    fn get_timeouts(&self) -> &Timeouts;
+    fn set_timeouts(&mut self, timeouts: Timeouts);
+    // discards whatever is still owed for a command that timed out, so that the next command reads its own response
+    fn resynchronize(&mut self) -> Result<(), Rs3270Error>;
+    // the status reported alongside the most recent command, whether or not it succeeded
+    fn get_status(&self) -> Option<&ClientStatus>;
+    fn execute_with_status<TOutput>(&mut self, command: impl CommandBuilder<TOutput>) -> (Result<TOutput, Rs3270Error>, Option<ClientStatus>) {
+        let execution_result = self.execute(command);
+        (execution_result, self.get_status().cloned())
+    }
+    // the script executors send every command before reading any response, answering the whole batch in one round trip, while the rest run the commands one at a time
+    // each command keeps its own result, and later commands still run when an earlier one fails
+    // the batch is refused as a whole, with nothing run, when any of its commands fails validation
+    fn execute_batch(&mut self, batch: CommandBatch) -> Result<BatchResults, Rs3270Error> {
+        let commands = batch.into_commands();
+        let code_page = self.get_code_page();
+        for command in commands.iter() {
+            if let Err(message) = command.validate(&code_page) {
+                return Err(Rs3270Error::Rejected { command: redact_command(&command.get_client_message()), message });
+            }
+        }
+        let results = commands.into_iter()
+            .map(|command| self.execute(command))
+            .collect();
+        Ok(BatchResults::new(results))
+    }
+    fn get_code_page(&self) -> CodePage;
+    // the host code page of the session, used to reject text before it is sent
+    fn set_code_page(&mut self, code_page: CodePage);
+    fn disconnect(&mut self);
+    // asks the emulator to exit, where executors without an emulator process only disconnect
+    fn quit(&mut self) {
+        self.disconnect();
+    }
+}
+
+const RESYNCHRONIZE_COMMAND: &str = "(resynchronize)";
+
+// the scripting connection to x3270 or s3270 that the StreamCommandExecutor and PipeCommandExecutor share
+struct ScriptSession<TStream: ScriptStream> {
+    codec: ScriptCodec<TStream>,
+    code_page: CodePage,
+    status: Option<ClientStatus>,
+    timeouts: Timeouts,
+    // the responses still owed for commands that timed out, which are discarded before the next command
+    unread_frame_count: usize
+}
+
+impl<TStream: ScriptStream> ScriptSession<TStream> {
+    fn new(stream: TStream, timeouts: Timeouts) -> Self {
+        ScriptSession {
+            codec: ScriptCodec::new(stream),
+            code_page: CodePage::Cp037,
+            status: None,
+            timeouts,
+            unread_frame_count: 0
+        }
+    }
+    fn execute<TOutput>(&mut self, command: impl CommandBuilder<TOutput>, timeouts: &Timeouts) -> Result<TOutput, Rs3270Error> {
+        if let Err(message) = command.validate(&self.code_page) {
+            return Err(Rs3270Error::Rejected { command: redact_command(&command.get_client_message()), message });
+        }
+        self.resynchronize(timeouts)?;
+        if let Err(error) = self.codec.get_mut().set_timeouts(timeouts) {
+            return Err(Rs3270Error::from_io_error(&redact_command(&command.get_client_message()), error));
+        }
+        let (execution_result, status) = command.execute_with_status(&mut self.codec);
+        if let Err(Rs3270Error::Timeout { .. }) = &execution_result {
+            self.unread_frame_count = 1;
+        }
+        self.status = status;
+        execution_result
+    }
+    fn execute_batch(&mut self, batch: CommandBatch, timeouts: &Timeouts) -> Result<BatchResults, Rs3270Error> {
+        let commands = batch.into_commands();
+        let client_messages = commands.iter()
+            .map(|command| command.get_client_message())
+            .collect::<Vec<String>>();
+        for (command, client_message) in commands.iter().zip(client_messages.iter()) {
+            if let Err(message) = command.validate(&self.code_page) {
+                return Err(Rs3270Error::Rejected { command: redact_command(client_message), message });
+            }
+        }
+        let batch_command = redact_command(&client_messages.join(" "));
+        self.resynchronize(timeouts)?;
+        self.codec.get_mut().set_timeouts(timeouts)
+            .and_then(|_| self.codec.write_lines(&client_messages))
+            .map_err(|error| Rs3270Error::from_io_error(&batch_command, error))?;
+        tracing::debug!(command = %batch_command, command_count = commands.len(), "sent batch");
+
+        let command_count = commands.len();
+        let mut results = Vec::with_capacity(command_count);
+        // once a response is lost the rest cannot be read, so they fail the same way
+        let mut lost_response_error: Option<fn(String) -> Rs3270Error> = None;
+        for (index, (command, client_message)) in commands.into_iter().zip(client_messages.iter()).enumerate() {
+            let span = start_command_span(client_message);
+            let _entered = span.enter();
+            let start_time = Instant::now();
+            let execution_result = match lost_response_error {
+                Some(lost_response_error) => {
+                    Err(lost_response_error(redact_command(client_message)))
+                },
+                None => {
+                    let (execution_result, status) = command.receive(&mut self.codec, redact_command(client_message));
+                    match &execution_result {
+                        Err(Rs3270Error::Timeout { .. }) => {
+                            self.unread_frame_count = command_count - index;
+                            lost_response_error = Some(|command| Rs3270Error::Timeout { command });
+                        },
+                        Err(Rs3270Error::Io { .. } | Rs3270Error::Disconnected { .. } | Rs3270Error::Certificate { .. }) => {
+                            lost_response_error = Some(|command| Rs3270Error::Disconnected { command });
+                        },
+                        _ => {
+                            self.status = status;
+                        }
+                    }
+                    execution_result
+                }
+            };
+            record_command_outcome(&span, start_time, &execution_result);
+            results.push(execution_result);
+        }
+        Ok(BatchResults::new(results))
+    }
+    // Quit is never answered, so it is not recorded
+    fn quit(&mut self) {
+        self.codec.set_recorder(None);
+        let quit_result = self.codec.write_line("Quit");
+        if let Err(quit_error) = quit_result {
+            tracing::warn!(%quit_error, "unable to disconnect via Quit");
+        }
+    }
+    // the codec kept whatever part of a late frame had arrived, so reading the rest of each owed frame catches up
+    fn resynchronize(&mut self, timeouts: &Timeouts) -> Result<(), Rs3270Error> {
+        if self.unread_frame_count == 0 {
+            return Ok(());
+        }
+        self.codec.get_mut().set_timeouts(timeouts)
+            .map_err(|error| Rs3270Error::from_io_error(RESYNCHRONIZE_COMMAND, error))?;
+        while self.unread_frame_count > 0 {
+            match self.codec.read_frame() {
+                Ok(frame) => {
+                    tracing::debug!(data_lines = frame.data_lines.len(), "discarded a late response");
+                },
+                Err(FrameError::Io(error)) => {
+                    return Err(Rs3270Error::from_io_error(RESYNCHRONIZE_COMMAND, error));
+                },
+                Err(FrameError::Protocol(message)) => {
+                    // the malformed frame was dropped, so it is no longer owed
+                    self.unread_frame_count -= 1;
+                    return Err(Rs3270Error::Protocol { command: String::from(RESYNCHRONIZE_COMMAND), message });
+                }
+            }
+            self.unread_frame_count -= 1;
+        }
+        tracing::debug!("resynchronized after a timeout");
+        Ok(())
+    }
+}
+
+pub struct StreamCommandExecutor {
+    session: ScriptSession<TcpStream>
+}
+
+impl StreamCommandExecutor {
+    pub fn connect(client_address: &str, timeouts: Timeouts) -> std::io::Result<Self> {
+        let stream = connect_with_timeout(client_address, timeouts.connect)?;
+        Ok(StreamCommandExecutor {
+            session: ScriptSession::new(stream, timeouts)
+        })
+    }
+    // writes a transcript of every command from now on, with the response, status and timing of each
+    pub fn start_recording(&mut self, writer: impl Write + 'static) {
+        self.session.codec.set_recorder(Some(TranscriptRecorder::new(writer)));
+    }
+    pub fn stop_recording(&mut self) {
+        self.session.codec.set_recorder(None);
+    }
+}
+
+impl CommandExecutor for StreamCommandExecutor {
+    fn connect_to_client_process(client_address: &str) -> Option<Self> {
+        match StreamCommandExecutor::connect(client_address, Timeouts::default()) {
+            Ok(stream_command_executor) => {
+                Some(stream_command_executor)
+            },
+            Err(error) => {
+                tracing::error!(client_address, %error, "unable to connect to the client script port");
+                None
+            }
+        }
+    }
+    fn execute_with_timeouts<TOutput>(&mut self, command: impl CommandBuilder<TOutput>, timeouts: &Timeouts) -> Result<TOutput, Rs3270Error> {
+        self.session.execute(command, timeouts)
+    }
+    fn get_timeouts(&self) -> &Timeouts {
+        &self.session.timeouts
+    }
+    fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.session.timeouts = timeouts;
+    }
+    fn resynchronize(&mut self) -> Result<(), Rs3270Error> {
+        let timeouts = self.session.timeouts.clone();
+        self.session.resynchronize(&timeouts)
+    }
+    fn execute_batch(&mut self, batch: CommandBatch) -> Result<BatchResults, Rs3270Error> {
+        let timeouts = self.session.timeouts.clone();
+        self.session.execute_batch(batch, &timeouts)
+    }
+    fn get_status(&self) -> Option<&ClientStatus> {
+        self.session.status.as_ref()
+    }
+    fn get_code_page(&self) -> CodePage {
+        self.session.code_page
+    }
+    fn set_code_page(&mut self, code_page: CodePage) {
+        self.session.code_page = code_page;
+    }
+    fn disconnect(&mut self) {
+        let shutdown_result = self.session.codec.get_ref().shutdown(std::net::Shutdown::Both);
+        if let Err(shutdown_error) = shutdown_result {
+            tracing::warn!(%shutdown_error, "unable to disconnect via shutdown");
+        }
+    }
+    // x3270 exits after the Quit action, where disconnect leaves it running
+    fn quit(&mut self) {
+        self.session.quit();
+        self.disconnect();
+    }
+}
+
+// a CommandExecutor over the stdin and stdout of an s3270 process, needing neither a display server nor a script port
+pub struct PipeCommandExecutor {
+    session: ScriptSession<ClientPipe>,
+    // the client spawned by connect_to_client_process, which this executor then owns
+    client: Option<Client>
+}
+
+impl PipeCommandExecutor {
+    pub fn new(client: &mut Client) -> Option<Self> {
+        match client.take_pipe() {
+            Some(pipe) => {
+                Some(PipeCommandExecutor {
+                    session: ScriptSession::new(pipe, Timeouts::default()),
+                    client: None
+                })
+            },
+            None => {
+                tracing::error!("the client was not spawned with piped stdin and stdout or its pipe was already taken");
+                None
+            }
+        }
+    }
+    // writes a transcript of every command from now on, with the response, status and timing of each
+    pub fn start_recording(&mut self, writer: impl Write + 'static) {
+        self.session.codec.set_recorder(Some(TranscriptRecorder::new(writer)));
+    }
+    pub fn stop_recording(&mut self) {
+        self.session.codec.set_recorder(None);
+    }
+}
+
+impl CommandExecutor for PipeCommandExecutor {
+    // there is no script port to connect to, so this spawns s3270 against the mainframe at the given address
+    fn connect_to_client_process(mainframe_address: &str) -> Option<Self> {
+        let terminal_configuration = TerminalConfiguration::new(mainframe_address, "");
+        match S3270ClientSpawner::spawn_and_wait(&terminal_configuration) {
+            Ok((client, mut pipe_command_executor)) => {
+                pipe_command_executor.client = Some(client);
+                Some(pipe_command_executor)
+            },
+            Err(error) => {
+                tracing::error!(mainframe_address, %error, "unable to start s3270");
+                None
+            }
+        }
+    }
+    fn execute_with_timeouts<TOutput>(&mut self, command: impl CommandBuilder<TOutput>, timeouts: &Timeouts) -> Result<TOutput, Rs3270Error> {
+        self.session.execute(command, timeouts)
+    }
+    fn get_timeouts(&self) -> &Timeouts {
+        &self.session.timeouts
+    }
+    fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.session.timeouts = timeouts;
+    }
+    fn resynchronize(&mut self) -> Result<(), Rs3270Error> {
+        let timeouts = self.session.timeouts.clone();
+        self.session.resynchronize(&timeouts)
+    }
+    fn execute_batch(&mut self, batch: CommandBatch) -> Result<BatchResults, Rs3270Error> {
+        let timeouts = self.session.timeouts.clone();
+        self.session.execute_batch(batch, &timeouts)
+    }
+    fn get_status(&self) -> Option<&ClientStatus> {
+        self.session.status.as_ref()
+    }
+    fn get_code_page(&self) -> CodePage {
+        self.session.code_page
+    }
+    fn set_code_page(&mut self, code_page: CodePage) {
+        self.session.code_page = code_page;
+    }
+    fn disconnect(&mut self) {
+        // s3270 exits after the Quit action
+        self.session.quit();
+        if let Some(client) = self.client.as_mut() {
+            if let Err(wait_error) = client.wait_or_kill(DEFAULT_SHUTDOWN_TIMEOUT) {
+                tracing::warn!(%wait_error, "unable to wait for the client process to exit");
+            }
+        }
+    }
+    fn quit(&mut self) {
+        self.session.quit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::{BufRead, BufReader}, time::Duration, sync::Mutex};
+
+    use crate::transcript::ReplayCommandExecutor;
 
     use super::*;
 
-    static is_previous_still_running: Mutex<bool> = Mutex::new(false);
+    static IS_PREVIOUS_STILL_RUNNING: Mutex<bool> = Mutex::new(false);
 
     fn init() {
         std::env::set_var("RUST_BACKTRACE", "1");
-        assert!(!*is_previous_still_running.lock().unwrap());
-        let _ = std::mem::replace(&mut *is_previous_still_running.lock().unwrap(), true);
+        assert!(!*IS_PREVIOUS_STILL_RUNNING.lock().unwrap());
+        let _ = std::mem::replace(&mut *IS_PREVIOUS_STILL_RUNNING.lock().unwrap(), true);
     }
 
     fn cleanup() {
-        let _ = std::mem::replace(&mut *is_previous_still_running.lock().unwrap(), false);
+        let _ = std::mem::replace(&mut *IS_PREVIOUS_STILL_RUNNING.lock().unwrap(), false);
     }
 
     #[test]
@@ -737,13 +2242,8 @@ mod tests {
 
         let terminal_configuration = TerminalConfiguration::new("localhost:3270", "localhost:3271");
         
-        // spawn client
-        let client = X3270ClientSpawner::spawn(&terminal_configuration);
-        assert!(client.is_some());
-        let mut client = client.unwrap();
-        
-        // wait a second
-        std::thread::sleep(Duration::from_secs(1));
+        // spawn client and wait until it answers
+        let (mut client, _) = X3270ClientSpawner::spawn_and_wait(&terminal_configuration).unwrap();
 
         // kill client
         let kill_result = client.kill();
@@ -758,26 +2258,8 @@ mod tests {
 
         let terminal_configuration = TerminalConfiguration::new("localhost:3270", "localhost:3271");
         
-        // spawn client
-        let client = X3270ClientSpawner::spawn(&terminal_configuration);
-        assert!(client.is_some());
-        let mut client = client.unwrap();
-        
-        // wait a second
-        println!("waiting for client to be ready...");
-        std::thread::sleep(Duration::from_secs(1));
-
-        // create interface
-        let interface = StreamCommandExecutor::connect_to_client_process(&terminal_configuration.client_address);
-
-        if interface.is_none() {
-            // kill client
-            let kill_result = client.kill();
-            assert!(kill_result.is_ok());
-        }
-
-        assert!(interface.is_some());
-        let mut interface = interface.unwrap();
+        // spawn client and wait until it answers
+        let (mut client, mut interface) = X3270ClientSpawner::spawn_and_wait(&terminal_configuration).unwrap();
         
         let execute_result = interface.execute(GetTextRangeCommand::new(0, 0, 80, 24));
 
@@ -814,26 +2296,8 @@ mod tests {
 
         let terminal_configuration = TerminalConfiguration::new("localhost:3270", "localhost:3271");
         
-        // spawn client
-        let client = X3270ClientSpawner::spawn(&terminal_configuration);
-        assert!(client.is_some());
-        let mut client = client.unwrap();
-        
-        // wait a second
-        println!("waiting for client to be ready...");
-        std::thread::sleep(Duration::from_secs(1));
-
-        // create interface
-        let interface = StreamCommandExecutor::connect_to_client_process(&terminal_configuration.client_address);
-
-        if interface.is_none() {
-            // kill client
-            let kill_result = client.kill();
-            assert!(kill_result.is_ok());
-        }
-
-        assert!(interface.is_some());
-        let mut interface = interface.unwrap();
+        // spawn client and wait until it answers
+        let (mut client, mut interface) = X3270ClientSpawner::spawn_and_wait(&terminal_configuration).unwrap();
         
         // move forward
         let execute_result = interface.execute(MoveCursorToNextFieldCommand::new());
@@ -886,26 +2350,8 @@ mod tests {
 
         let terminal_configuration = TerminalConfiguration::new("localhost:3270", "localhost:3271");
         
-        // spawn client
-        let client = X3270ClientSpawner::spawn(&terminal_configuration);
-        assert!(client.is_some());
-        let mut client = client.unwrap();
-        
-        // wait a second
-        println!("waiting for client to be ready...");
-        std::thread::sleep(Duration::from_secs(1));
-
-        // create interface
-        let interface = StreamCommandExecutor::connect_to_client_process(&terminal_configuration.client_address);
-
-        if interface.is_none() {
-            // kill client
-            let kill_result = client.kill();
-            assert!(kill_result.is_ok());
-        }
-
-        assert!(interface.is_some());
-        let mut interface = interface.unwrap();
+        // spawn client and wait until it answers
+        let (mut client, mut interface) = X3270ClientSpawner::spawn_and_wait(&terminal_configuration).unwrap();
         
         // move forward
         let execute_result = interface.execute(MoveCursorToFieldEndCommand::new());
@@ -939,26 +2385,8 @@ mod tests {
 
         let terminal_configuration = TerminalConfiguration::new("localhost:3270", "localhost:3271");
         
-        // spawn client
-        let client = X3270ClientSpawner::spawn(&terminal_configuration);
-        assert!(client.is_some());
-        let mut client = client.unwrap();
-        
-        // wait a second
-        println!("waiting for client to be ready...");
-        std::thread::sleep(Duration::from_secs(1));
-
-        // create interface
-        let interface = StreamCommandExecutor::connect_to_client_process(&terminal_configuration.client_address);
-
-        if interface.is_none() {
-            // kill client
-            let kill_result = client.kill();
-            assert!(kill_result.is_ok());
-        }
-
-        assert!(interface.is_some());
-        let mut interface = interface.unwrap();
+        // spawn client and wait until it answers
+        let (mut client, mut interface) = X3270ClientSpawner::spawn_and_wait(&terminal_configuration).unwrap();
         
         let execute_result = interface.execute(GetCursorCommand::new());
 
@@ -984,4 +2412,379 @@ mod tests {
 
         cleanup();
     }
-}
\ No newline at end of file
+
+    // the shell commands with which the s3270 stand-in answers, its data lines expanded by the shell, such as "$count"
+    fn reply(data_lines: &[&str], is_keyboard_locked: bool, is_ok: bool) -> String {
+        let mut reply = data_lines.iter()
+            .map(|data_line| format!("printf 'data: %s\\n' \"{}\"; ", data_line))
+            .collect::<String>();
+        let keyboard_state = if is_keyboard_locked { "L" } else { "U" };
+        let conclusion = if is_ok { "ok" } else { "error" };
+        reply.push_str(&format!("printf '{} F U C(localhost) I 4 43 80 3 4 0x0 0.000\\n{}\\n'", keyboard_state, conclusion));
+        reply
+    }
+
+    // a shell script that stands in for s3270, running the commands of the first case pattern that matches each line it reads
+    // it exits on Quit unless a pattern matches that first, and keeps the number of lines read so far in $count
+    fn get_stand_in_script(responses: &[(&str, String)]) -> String {
+        let cases = responses.iter()
+            .map(|(pattern, response)| format!("{}) {};; ", pattern, response))
+            .collect::<String>();
+        format!("count=0; while read line; do count=$((count+1)); case \"$line\" in {}Quit) exit 0;; esac; done", cases)
+    }
+
+    fn spawn_stand_in(responses: &[(&str, String)]) -> Client {
+        let process = std::process::Command::new("sh")
+            .arg("-c")
+            .arg(get_stand_in_script(responses))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        Client::new(process)
+    }
+
+    #[test]
+    fn get_cursor_position_over_pipe() {
+        let mut client = spawn_stand_in(&[
+            ("Query*", reply(&["3 4"], false, true)),
+            ("Tab", reply(&["Tab failed", "with detail"], false, false)),
+            ("Enter", reply(&["Keyboard locked"], true, false))
+        ]);
+        let mut command_executor = PipeCommandExecutor::new(&mut client).unwrap();
+        assert!(client.take_pipe().is_none());
+
+        let position = command_executor.execute(GetCursorCommand::new()).unwrap();
+        assert_eq!((3, 4), position);
+        assert_eq!(Some((3, 4)), command_executor.get_status().map(|status| (status.cursor_row, status.cursor_column)));
+        let (execution_result, status) = command_executor.execute_with_status(MoveCursorToNextFieldCommand::new());
+        match execution_result {
+            Err(Rs3270Error::Emulator { command, lines }) => {
+                assert_eq!("Tab", command);
+                assert_eq!(vec![String::from("Tab failed"), String::from("with detail")], lines);
+            },
+            _ => {
+                panic!("The error lines should be returned.");
+            }
+        }
+        assert!(status.unwrap().is_connected());
+        let error = command_executor.execute(SendEnterKeyCommand::new()).unwrap_err();
+        assert!(matches!(error, Rs3270Error::KeyboardLocked { .. }));
+
+        command_executor.disconnect();
+        assert!(client.process.wait().unwrap().success());
+    }
+
+    #[test]
+    fn time_out_then_resynchronize_over_pipe() {
+        // answers Enter only after a second
+        let mut client = spawn_stand_in(&[
+            ("Enter", format!("sleep 1; {}", reply(&["late"], true, true))),
+            ("Query*", reply(&["3 4"], false, true))
+        ]);
+        let mut command_executor = PipeCommandExecutor::new(&mut client).unwrap();
+        let short_timeouts = Timeouts {
+            read: Some(Duration::from_millis(100)),
+            ..Timeouts::default()
+        };
+
+        let error = command_executor.execute_with_timeouts(SendEnterKeyCommand::new(), &short_timeouts).unwrap_err();
+        assert!(matches!(error, Rs3270Error::Timeout { .. }));
+        // the late response to Enter is discarded rather than read as the cursor position
+        assert_eq!((3, 4), command_executor.execute(GetCursorCommand::new()).unwrap());
+
+        command_executor.disconnect();
+        assert!(client.process.wait().unwrap().success());
+    }
+
+    #[test]
+    fn time_out_then_resynchronize_over_socket() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let client_address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut stream = stream;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                if line.trim() == "Enter" {
+                    std::thread::sleep(Duration::from_millis(500));
+                    stream.write_all(b"L F U C(localhost) I 4 43 80 0 0 0x0 0.500\nok\n").unwrap();
+                }
+                else {
+                    stream.write_all(b"data: 3 4\nU F U C(localhost) I 4 43 80 3 4 0x0 0.000\nok\n").unwrap();
+                }
+                line.clear();
+            }
+        });
+        let timeouts = Timeouts {
+            read: Some(Duration::from_millis(100)),
+            ..Timeouts::default()
+        };
+        let mut command_executor = StreamCommandExecutor::connect(&client_address, timeouts).unwrap();
+
+        let error = command_executor.execute(SendEnterKeyCommand::new()).unwrap_err();
+        assert!(matches!(error, Rs3270Error::Timeout { .. }));
+        command_executor.set_timeouts(Timeouts::default());
+        command_executor.resynchronize().unwrap();
+        assert_eq!((3, 4), command_executor.execute(GetCursorCommand::new()).unwrap());
+        command_executor.disconnect();
+    }
+
+    #[test]
+    fn execute_batch_over_pipe() {
+        // answers with the number of lines it has read so that each response can be told apart
+        let mut client = spawn_stand_in(&[
+            ("Tab", reply(&["Tab failed"], false, false)),
+            ("Query*", reply(&["$count 1"], false, true))
+        ]);
+        let mut command_executor = PipeCommandExecutor::new(&mut client).unwrap();
+
+        let mut batch = CommandBatch::new();
+        let first_position = batch.add(GetCursorCommand::new());
+        let tab = batch.add(MoveCursorToNextFieldCommand::new());
+        let second_position = batch.add(GetCursorCommand::new());
+        let mut results = command_executor.execute_batch(batch).unwrap();
+        assert!(!results.is_ok());
+        assert_eq!((1, 1), results.take(first_position).unwrap());
+        assert!(matches!(results.take(tab), Err(Rs3270Error::Emulator { .. })));
+        // the failed Tab did not stop the commands after it
+        assert_eq!((3, 1), results.take(second_position).unwrap());
+
+        // nothing is sent when any command of the batch is refused
+        let mut batch = CommandBatch::new();
+        batch.add(GetCursorCommand::new());
+        batch.add(SetTextCommand::new(String::from("costs 5€")));
+        assert!(matches!(command_executor.execute_batch(batch), Err(Rs3270Error::Rejected { .. })));
+        assert_eq!((4, 1), command_executor.execute(GetCursorCommand::new()).unwrap());
+
+        command_executor.disconnect();
+        assert!(client.process.wait().unwrap().success());
+    }
+
+    #[test]
+    fn record_over_pipe_then_replay() {
+        let mut client = spawn_stand_in(&[
+            ("Query*", reply(&["3 4"], false, true)),
+            ("String*", reply(&[], false, true))
+        ]);
+        let mut command_executor = PipeCommandExecutor::new(&mut client).unwrap();
+        let transcript_path = std::env::temp_dir().join(format!("rs3270-transcript-{}.txt", std::process::id()));
+        command_executor.start_recording(std::fs::File::create(&transcript_path).unwrap());
+
+        assert_eq!((3, 4), command_executor.execute(GetCursorCommand::new()).unwrap());
+        command_executor.execute(SetTextCommand::new(String::from("hunter2"))).unwrap();
+        command_executor.disconnect();
+        assert!(client.process.wait().unwrap().success());
+
+        let mut replay_command_executor = ReplayCommandExecutor::open(&transcript_path).unwrap();
+        assert_eq!((3, 4), replay_command_executor.execute(GetCursorCommand::new()).unwrap());
+        replay_command_executor.execute(SetTextCommand::new(String::from("hunter2"))).unwrap();
+        assert_eq!(0, replay_command_executor.get_remaining_count());
+
+        // typed text is redacted in the transcript but still compared through its digest
+        let mut replay_command_executor = ReplayCommandExecutor::open(&transcript_path).unwrap();
+        std::fs::remove_file(&transcript_path).unwrap();
+        assert_eq!((3, 4), replay_command_executor.execute(GetCursorCommand::new()).unwrap());
+        let error = replay_command_executor.execute(SetTextCommand::new(String::from("other"))).unwrap_err();
+        assert!(matches!(error, Rs3270Error::Protocol { .. }));
+        assert_eq!(1, replay_command_executor.get_remaining_count());
+    }
+
+    #[test]
+    fn build_configuration_into_emulator_arguments() {
+        let terminal_configuration = TerminalConfiguration::builder("mainframe.example.com")
+            .with_client_address("localhost:3271")
+            .with_oversize(62, 160)
+            .with_code_page(CodePage::Cp500)
+            .with_lu_name("LU01")
+            .with_port(2023)
+            .with_keymap("rctrl")
+            .with_proxy(ProxyConfiguration::parse("socks5:proxy.example.com").unwrap())
+            .with_emulator_connect_timeout(Duration::from_millis(2500))
+            .with_trace_file("/tmp/x3270.trace")
+            .with_executable_path("/opt/x3270/bin/x3270")
+            .with_extra_argument("-nvt")
+            .build()
+            .unwrap();
+        assert_eq!(vec![
+            "-model", "3279-2", "-codepage", "cp500", "-oversize", "160x62",
+            "-scriptport", "localhost:3271", "-keymap", "rctrl",
+            "-port", "2023", "-proxy", "socks5:proxy.example.com:1080", "-connecttimeout", "3",
+            "-trace", "-tracefile", "/tmp/x3270.trace",
+            "-nvt", "LU01@mainframe.example.com"
+        ], X3270ClientSpawner::get_arguments(&terminal_configuration).unwrap());
+        assert_eq!(PathBuf::from("/opt/x3270/bin/x3270"), get_executable(&terminal_configuration, "x3270"));
+        // s3270 has no keyboard to map
+        assert!(S3270ClientSpawner::get_arguments(&terminal_configuration).is_err());
+        assert!(S3270ClientSpawner::spawn(&terminal_configuration).is_none());
+
+        // an emulator option that was not chosen is left to the emulator's own default
+        let terminal_configuration = TerminalConfiguration::new("mainframe.example.com", "localhost:3271");
+        assert_eq!(vec!["-model", "3279-4", "-scriptport", "localhost:3271", "mainframe.example.com"], X3270ClientSpawner::get_arguments(&terminal_configuration).unwrap());
+        assert_eq!(CodePage::Cp037, terminal_configuration.get_code_page());
+
+        assert!(TerminalConfiguration::builder("mainframe.example.com").with_oversize(200, 200).build().is_err());
+        assert!(TerminalConfiguration::builder("mainframe.example.com").with_port(0).build().is_err());
+    }
+
+    // stands in for an emulator executable, which ignores its arguments and runs the script
+    fn write_emulator_script(name: &str, script: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let script_path = std::env::temp_dir().join(format!("rs3270-{}-{}.sh", name, std::process::id()));
+        std::fs::write(&script_path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&script_path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        script_path
+    }
+
+    #[test]
+    fn spawn_and_wait_until_ready_over_pipe() {
+        // answers only after starting up, so the first queries time out and their late answers are discarded
+        let script_path = write_emulator_script("ready", &format!("sleep 0.3; {}", get_stand_in_script(&[
+            ("Query*", reply(&["connected-3270"], false, true))
+        ])));
+        let terminal_configuration = TerminalConfiguration::builder("localhost")
+            .with_executable_path(&script_path)
+            .with_connect_timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+
+        let (mut client, mut command_executor) = S3270ClientSpawner::spawn_and_wait(&terminal_configuration).unwrap();
+        assert_eq!("connected-3270", command_executor.execute(QueryConnectionStateCommand::new()).unwrap().get_name());
+        command_executor.disconnect();
+        assert!(client.process.wait().unwrap().success());
+        std::fs::remove_file(&script_path).unwrap();
+    }
+
+    #[test]
+    fn report_early_exit_with_stderr() {
+        let script_path = write_emulator_script("exit", "echo 'boom: no such host' >&2; exit 3");
+        let terminal_configuration = TerminalConfiguration::builder("localhost")
+            .with_executable_path(&script_path)
+            .build()
+            .unwrap();
+
+        match S3270ClientSpawner::spawn_and_wait(&terminal_configuration) {
+            Err(SpawnError::Exited { status, stderr }) => {
+                assert_eq!(Some(3), status.code());
+                assert!(stderr.contains("boom: no such host"));
+            },
+            _ => {
+                panic!("The early exit should be reported.");
+            }
+        }
+        std::fs::remove_file(&script_path).unwrap();
+
+        // x3270 that never opens its script port
+        let script_path = write_emulator_script("silent", "echo 'still starting' >&2; sleep 5");
+        let terminal_configuration = TerminalConfiguration::builder("localhost")
+            .with_client_address("127.0.0.1:1")
+            .with_executable_path(&script_path)
+            .with_connect_timeout(Duration::from_millis(300))
+            .build()
+            .unwrap();
+        match X3270ClientSpawner::spawn_and_wait(&terminal_configuration) {
+            Err(SpawnError::NotReady { stderr, .. }) => {
+                assert!(stderr.contains("still starting"));
+            },
+            _ => {
+                panic!("The emulator should not be ready.");
+            }
+        }
+        std::fs::remove_file(&script_path).unwrap();
+    }
+
+    #[test]
+    fn shut_down_then_reap_clients() {
+        // exits on Quit, saying so on stderr
+        let mut client = spawn_stand_in(&[
+            ("Quit", String::from("echo 'bye' >&2; exit 0"))
+        ]);
+        let mut command_executor = PipeCommandExecutor::new(&mut client).unwrap();
+        assert!(client.is_alive());
+        assert!(client.shutdown(&mut command_executor, Duration::from_secs(5)).unwrap().success());
+        assert!(!client.is_alive());
+        assert_eq!("bye\n", client.drain_stderr());
+
+        // ignores Quit, so it is killed once the timeout passes
+        let mut client = spawn_stand_in(&[
+            ("*", String::from(":"))
+        ]);
+        let mut command_executor = PipeCommandExecutor::new(&mut client).unwrap();
+        let status = client.shutdown(&mut command_executor, Duration::from_millis(200)).unwrap();
+        assert_eq!(None, status.code());
+
+        // dropping a running client kills and reaps it, so not even a zombie is left
+        let process = std::process::Command::new("sleep")
+            .arg("30")
+            .spawn()
+            .unwrap();
+        let client = Client::new(process);
+        let process_path = PathBuf::from(format!("/proc/{}", client.get_id()));
+        assert!(process_path.exists());
+        drop(client);
+        assert!(!process_path.exists());
+    }
+
+    #[test]
+    fn supervise_then_respawn_a_crashed_client() {
+        // crashes on Enter
+        let script = get_stand_in_script(&[
+            ("Enter", String::from("echo 'crashed' >&2; exit 1")),
+            ("Query*", reply(&["connected-3270"], false, true))
+        ]);
+        let script_path = write_emulator_script("crash", &script);
+        let get_terminal_configuration = || TerminalConfiguration::builder("localhost")
+            .with_executable_path(&script_path)
+            .build()
+            .unwrap();
+        let crash = |supervisor: &mut ClientSupervisor<S3270ClientSpawner>| {
+            let error = supervisor.get_command_executor().unwrap().execute(SendEnterKeyCommand::new()).unwrap_err();
+            assert!(matches!(error, Rs3270Error::Disconnected { .. }));
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while supervisor.get_client().is_alive() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        };
+
+        let mut supervisor = ClientSupervisor::<S3270ClientSpawner>::start(get_terminal_configuration()).unwrap();
+        assert!(!supervisor.ensure_running().unwrap());
+        crash(&mut supervisor);
+        assert!(supervisor.ensure_running().unwrap());
+        assert_eq!(1, supervisor.get_restart_count());
+        assert!(supervisor.get_command_executor().unwrap().execute(QueryConnectionStateCommand::new()).unwrap().is_connected());
+
+        // the crash is reported once the restarts are used up
+        supervisor.set_maximum_restart_count(Some(1));
+        crash(&mut supervisor);
+        match supervisor.get_command_executor() {
+            Err(SpawnError::Exited { status, stderr }) => {
+                assert_eq!(Some(1), status.code());
+                assert_eq!("crashed", stderr.trim());
+            },
+            _ => {
+                panic!("The crash should be reported.");
+            }
+        }
+
+        supervisor.set_maximum_restart_count(None);
+        assert!(supervisor.ensure_running().unwrap());
+        assert_eq!(2, supervisor.get_restart_count());
+
+        // a respawn that fails still counts, so it is not attempted again once the restarts are used up
+        supervisor.set_maximum_restart_count(Some(3));
+        crash(&mut supervisor);
+        std::fs::remove_file(&script_path).unwrap();
+        assert!(matches!(supervisor.ensure_running(), Err(SpawnError::Start(_))));
+        assert_eq!(3, supervisor.get_restart_count());
+        assert!(matches!(supervisor.ensure_running(), Err(SpawnError::Exited { .. })));
+        assert_eq!(3, supervisor.get_restart_count());
+
+        write_emulator_script("crash", &script);
+        let supervisor = ClientSupervisor::<S3270ClientSpawner>::start(get_terminal_configuration()).unwrap();
+        assert!(supervisor.shutdown().unwrap().success());
+        std::fs::remove_file(&script_path).unwrap();
+    }
+}
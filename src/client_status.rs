@@ -0,0 +1,150 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyboardState {
+    Unlocked,
+    Locked,
+    // locked by an operator error, such as typing into a protected field
+    Error
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionState {
+    Connected(String),
+    NotConnected
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EmulatorMode {
+    Tn3270,
+    NvtLine,
+    NvtCharacter,
+    // connected but the host has not negotiated a mode yet
+    Unnegotiated,
+    NotConnected
+}
+
+// the status line that x3270 writes after the data of every action, such as "U F U C(host) I 4 43 80 0 7 0x0 0.001"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientStatus {
+    pub keyboard_state: KeyboardState,
+    pub is_formatted: bool,
+    pub is_field_protected: bool,
+    pub connection_state: ConnectionState,
+    pub emulator_mode: EmulatorMode,
+    pub model_number: u8,
+    pub rows: usize,
+    pub columns: usize,
+    pub cursor_row: usize,
+    pub cursor_column: usize,
+    pub window_id: u64,
+    // how long the action took, which is missing for actions that did not reach the host
+    pub execution_time: Option<Duration>
+}
+
+impl ClientStatus {
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        if fields.len() != 12 {
+            return None;
+        }
+        let keyboard_state = match fields[0] {
+            "U" => KeyboardState::Unlocked,
+            "L" => KeyboardState::Locked,
+            "E" => KeyboardState::Error,
+            _ => return None
+        };
+        let is_formatted = match fields[1] {
+            "F" => true,
+            "U" => false,
+            _ => return None
+        };
+        let is_field_protected = match fields[2] {
+            "P" => true,
+            "U" => false,
+            _ => return None
+        };
+        let connection_state = match fields[3] {
+            "N" => {
+                ConnectionState::NotConnected
+            },
+            connection => {
+                let host = connection.strip_prefix("C(")?.strip_suffix(')')?;
+                ConnectionState::Connected(String::from(host))
+            }
+        };
+        let emulator_mode = match fields[4] {
+            "I" => EmulatorMode::Tn3270,
+            "L" => EmulatorMode::NvtLine,
+            "C" => EmulatorMode::NvtCharacter,
+            "P" => EmulatorMode::Unnegotiated,
+            "N" => EmulatorMode::NotConnected,
+            _ => return None
+        };
+        let window_id = u64::from_str_radix(fields[10].trim_start_matches("0x"), 16).ok()?;
+        let execution_time = match fields[11] {
+            "-" => {
+                None
+            },
+            seconds => {
+                Some(Duration::from_secs_f64(seconds.parse::<f64>().ok()?))
+            }
+        };
+        Some(ClientStatus {
+            keyboard_state,
+            is_formatted,
+            is_field_protected,
+            connection_state,
+            emulator_mode,
+            model_number: fields[5].parse().ok()?,
+            rows: fields[6].parse().ok()?,
+            columns: fields[7].parse().ok()?,
+            cursor_row: fields[8].parse().ok()?,
+            cursor_column: fields[9].parse().ok()?,
+            window_id,
+            execution_time
+        })
+    }
+    pub fn is_keyboard_locked(&self) -> bool {
+        self.keyboard_state != KeyboardState::Unlocked
+    }
+    pub fn is_connected(&self) -> bool {
+        matches!(self.connection_state, ConnectionState::Connected(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_connected_status() {
+        let status = ClientStatus::parse("U F P C(mainframe.example.com) I 4 43 80 0 7 0x2a00007 0.125\n").unwrap();
+        assert_eq!(KeyboardState::Unlocked, status.keyboard_state);
+        assert!(status.is_formatted);
+        assert!(status.is_field_protected);
+        assert_eq!(ConnectionState::Connected(String::from("mainframe.example.com")), status.connection_state);
+        assert_eq!(EmulatorMode::Tn3270, status.emulator_mode);
+        assert_eq!((4, 43, 80), (status.model_number, status.rows, status.columns));
+        assert_eq!((0, 7), (status.cursor_row, status.cursor_column));
+        assert_eq!(0x2a00007, status.window_id);
+        assert_eq!(Some(Duration::from_millis(125)), status.execution_time);
+    }
+
+    #[test]
+    fn parse_disconnected_status() {
+        let status = ClientStatus::parse("L U U N N 4 43 80 0 0 0x0 -").unwrap();
+        assert!(status.is_keyboard_locked());
+        assert!(!status.is_connected());
+        assert_eq!(EmulatorMode::NotConnected, status.emulator_mode);
+        assert_eq!(None, status.execution_time);
+    }
+
+    #[test]
+    fn reject_other_lines() {
+        assert!(ClientStatus::parse("ok").is_none());
+        assert!(ClientStatus::parse("X F U N N 4 43 80 0 0 0x0 -").is_none());
+    }
+}
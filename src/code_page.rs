@@ -0,0 +1,171 @@
+#![allow(dead_code)]
+
+// table-driven EBCDIC translation for the common host code pages, indexed by EBCDIC byte
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodePage {
+    Cp037,
+    Cp273,
+    Cp500,
+    Cp1047
+}
+
+impl CodePage {
+    fn get_table(&self) -> &'static [char; 256] {
+        match self {
+            CodePage::Cp037 => &CP037,
+            CodePage::Cp273 => &CP273,
+            CodePage::Cp500 => &CP500,
+            CodePage::Cp1047 => &CP1047
+        }
+    }
+    // the name that x3270 accepts for its -codepage option
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            CodePage::Cp037 => "cp037",
+            CodePage::Cp273 => "cp273",
+            CodePage::Cp500 => "cp500",
+            CodePage::Cp1047 => "cp1047"
+        }
+    }
+    pub fn from_name(name: &str) -> Option<CodePage> {
+        match name.to_lowercase().as_str() {
+            "cp037" | "037" | "us-intl" => Some(CodePage::Cp037),
+            "cp273" | "273" | "german" => Some(CodePage::Cp273),
+            "cp500" | "500" | "belgian" | "international" => Some(CodePage::Cp500),
+            "cp1047" | "1047" => Some(CodePage::Cp1047),
+            _ => None
+        }
+    }
+    pub fn get_char(&self, byte: u8) -> char {
+        self.get_table()[byte as usize]
+    }
+    pub fn get_byte(&self, character: char) -> Option<u8> {
+        self.get_table()
+            .iter()
+            .position(|table_character| *table_character == character)
+            .map(|index| index as u8)
+    }
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        bytes.iter()
+            .map(|byte| self.get_char(*byte))
+            .collect()
+    }
+    // returns the first character that the code page cannot represent on failure
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, char> {
+        text.chars()
+            .map(|character| self.get_byte(character).ok_or(character))
+            .collect()
+    }
+}
+
+const CP037: [char; 256] = [
+    '\u{0000}', '\u{0001}', '\u{0002}', '\u{0003}', '\u{009C}', '\u{0009}', '\u{0086}', '\u{007F}', '\u{0097}', '\u{008D}', '\u{008E}', '\u{000B}', '\u{000C}', '\u{000D}', '\u{000E}', '\u{000F}',
+    '\u{0010}', '\u{0011}', '\u{0012}', '\u{0013}', '\u{009D}', '\u{0085}', '\u{0008}', '\u{0087}', '\u{0018}', '\u{0019}', '\u{0092}', '\u{008F}', '\u{001C}', '\u{001D}', '\u{001E}', '\u{001F}',
+    '\u{0080}', '\u{0081}', '\u{0082}', '\u{0083}', '\u{0084}', '\u{000A}', '\u{0017}', '\u{001B}', '\u{0088}', '\u{0089}', '\u{008A}', '\u{008B}', '\u{008C}', '\u{0005}', '\u{0006}', '\u{0007}',
+    '\u{0090}', '\u{0091}', '\u{0016}', '\u{0093}', '\u{0094}', '\u{0095}', '\u{0096}', '\u{0004}', '\u{0098}', '\u{0099}', '\u{009A}', '\u{009B}', '\u{0014}', '\u{0015}', '\u{009E}', '\u{001A}',
+    ' ', '\u{00A0}', 'â', 'ä', 'à', 'á', 'ã', 'å', 'ç', 'ñ', '¢', '.', '<', '(', '+', '|',
+    '&', 'é', 'ê', 'ë', 'è', 'í', 'î', 'ï', 'ì', 'ß', '!', '$', '*', ')', ';', '¬',
+    '-', '/', 'Â', 'Ä', 'À', 'Á', 'Ã', 'Å', 'Ç', 'Ñ', '¦', ',', '%', '_', '>', '?',
+    'ø', 'É', 'Ê', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', '`', ':', '#', '@', '\'', '=', '"',
+    'Ø', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', '«', '»', 'ð', 'ý', 'þ', '±',
+    '°', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 'ª', 'º', 'æ', '¸', 'Æ', '¤',
+    'µ', '~', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '¡', '¿', 'Ð', 'Ý', 'Þ', '®',
+    '^', '£', '¥', '·', '©', '§', '¶', '¼', '½', '¾', '[', ']', '¯', '¨', '´', '×',
+    '{', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', '\u{00AD}', 'ô', 'ö', 'ò', 'ó', 'õ',
+    '}', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', '¹', 'û', 'ü', 'ù', 'ú', 'ÿ',
+    '\\', '÷', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '²', 'Ô', 'Ö', 'Ò', 'Ó', 'Õ',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '³', 'Û', 'Ü', 'Ù', 'Ú', '\u{009F}',
+];
+
+const CP273: [char; 256] = [
+    '\u{0000}', '\u{0001}', '\u{0002}', '\u{0003}', '\u{009C}', '\u{0009}', '\u{0086}', '\u{007F}', '\u{0097}', '\u{008D}', '\u{008E}', '\u{000B}', '\u{000C}', '\u{000D}', '\u{000E}', '\u{000F}',
+    '\u{0010}', '\u{0011}', '\u{0012}', '\u{0013}', '\u{009D}', '\u{0085}', '\u{0008}', '\u{0087}', '\u{0018}', '\u{0019}', '\u{0092}', '\u{008F}', '\u{001C}', '\u{001D}', '\u{001E}', '\u{001F}',
+    '\u{0080}', '\u{0081}', '\u{0082}', '\u{0083}', '\u{0084}', '\u{000A}', '\u{0017}', '\u{001B}', '\u{0088}', '\u{0089}', '\u{008A}', '\u{008B}', '\u{008C}', '\u{0005}', '\u{0006}', '\u{0007}',
+    '\u{0090}', '\u{0091}', '\u{0016}', '\u{0093}', '\u{0094}', '\u{0095}', '\u{0096}', '\u{0004}', '\u{0098}', '\u{0099}', '\u{009A}', '\u{009B}', '\u{0014}', '\u{0015}', '\u{009E}', '\u{001A}',
+    ' ', '\u{00A0}', 'â', '{', 'à', 'á', 'ã', 'å', 'ç', 'ñ', 'Ä', '.', '<', '(', '+', '!',
+    '&', 'é', 'ê', 'ë', 'è', 'í', 'î', 'ï', 'ì', '~', 'Ü', '$', '*', ')', ';', '^',
+    '-', '/', 'Â', '[', 'À', 'Á', 'Ã', 'Å', 'Ç', 'Ñ', 'ö', ',', '%', '_', '>', '?',
+    'ø', 'É', 'Ê', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', '`', ':', '#', '§', '\'', '=', '"',
+    'Ø', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', '«', '»', 'ð', 'ý', 'þ', '±',
+    '°', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 'ª', 'º', 'æ', '¸', 'Æ', '¤',
+    'µ', 'ß', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '¡', '¿', 'Ð', 'Ý', 'Þ', '®',
+    '¢', '£', '¥', '·', '©', '@', '¶', '¼', '½', '¾', '¬', '|', '‾', '¨', '´', '×',
+    'ä', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', '\u{00AD}', 'ô', '¦', 'ò', 'ó', 'õ',
+    'ü', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', '¹', 'û', '}', 'ù', 'ú', 'ÿ',
+    'Ö', '÷', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '²', 'Ô', '\\', 'Ò', 'Ó', 'Õ',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '³', 'Û', ']', 'Ù', 'Ú', '\u{009F}',
+];
+
+const CP500: [char; 256] = [
+    '\u{0000}', '\u{0001}', '\u{0002}', '\u{0003}', '\u{009C}', '\u{0009}', '\u{0086}', '\u{007F}', '\u{0097}', '\u{008D}', '\u{008E}', '\u{000B}', '\u{000C}', '\u{000D}', '\u{000E}', '\u{000F}',
+    '\u{0010}', '\u{0011}', '\u{0012}', '\u{0013}', '\u{009D}', '\u{0085}', '\u{0008}', '\u{0087}', '\u{0018}', '\u{0019}', '\u{0092}', '\u{008F}', '\u{001C}', '\u{001D}', '\u{001E}', '\u{001F}',
+    '\u{0080}', '\u{0081}', '\u{0082}', '\u{0083}', '\u{0084}', '\u{000A}', '\u{0017}', '\u{001B}', '\u{0088}', '\u{0089}', '\u{008A}', '\u{008B}', '\u{008C}', '\u{0005}', '\u{0006}', '\u{0007}',
+    '\u{0090}', '\u{0091}', '\u{0016}', '\u{0093}', '\u{0094}', '\u{0095}', '\u{0096}', '\u{0004}', '\u{0098}', '\u{0099}', '\u{009A}', '\u{009B}', '\u{0014}', '\u{0015}', '\u{009E}', '\u{001A}',
+    ' ', '\u{00A0}', 'â', 'ä', 'à', 'á', 'ã', 'å', 'ç', 'ñ', '[', '.', '<', '(', '+', '!',
+    '&', 'é', 'ê', 'ë', 'è', 'í', 'î', 'ï', 'ì', 'ß', ']', '$', '*', ')', ';', '^',
+    '-', '/', 'Â', 'Ä', 'À', 'Á', 'Ã', 'Å', 'Ç', 'Ñ', '¦', ',', '%', '_', '>', '?',
+    'ø', 'É', 'Ê', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', '`', ':', '#', '@', '\'', '=', '"',
+    'Ø', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', '«', '»', 'ð', 'ý', 'þ', '±',
+    '°', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 'ª', 'º', 'æ', '¸', 'Æ', '¤',
+    'µ', '~', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '¡', '¿', 'Ð', 'Ý', 'Þ', '®',
+    '¢', '£', '¥', '·', '©', '§', '¶', '¼', '½', '¾', '¬', '|', '¯', '¨', '´', '×',
+    '{', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', '\u{00AD}', 'ô', 'ö', 'ò', 'ó', 'õ',
+    '}', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', '¹', 'û', 'ü', 'ù', 'ú', 'ÿ',
+    '\\', '÷', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '²', 'Ô', 'Ö', 'Ò', 'Ó', 'Õ',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '³', 'Û', 'Ü', 'Ù', 'Ú', '\u{009F}',
+];
+
+const CP1047: [char; 256] = [
+    '\u{0000}', '\u{0001}', '\u{0002}', '\u{0003}', '\u{009C}', '\u{0009}', '\u{0086}', '\u{007F}', '\u{0097}', '\u{008D}', '\u{008E}', '\u{000B}', '\u{000C}', '\u{000D}', '\u{000E}', '\u{000F}',
+    '\u{0010}', '\u{0011}', '\u{0012}', '\u{0013}', '\u{009D}', '\u{000A}', '\u{0008}', '\u{0087}', '\u{0018}', '\u{0019}', '\u{0092}', '\u{008F}', '\u{001C}', '\u{001D}', '\u{001E}', '\u{001F}',
+    '\u{0080}', '\u{0081}', '\u{0082}', '\u{0083}', '\u{0084}', '\u{0085}', '\u{0017}', '\u{001B}', '\u{0088}', '\u{0089}', '\u{008A}', '\u{008B}', '\u{008C}', '\u{0005}', '\u{0006}', '\u{0007}',
+    '\u{0090}', '\u{0091}', '\u{0016}', '\u{0093}', '\u{0094}', '\u{0095}', '\u{0096}', '\u{0004}', '\u{0098}', '\u{0099}', '\u{009A}', '\u{009B}', '\u{0014}', '\u{0015}', '\u{009E}', '\u{001A}',
+    ' ', '\u{00A0}', 'â', 'ä', 'à', 'á', 'ã', 'å', 'ç', 'ñ', '¢', '.', '<', '(', '+', '|',
+    '&', 'é', 'ê', 'ë', 'è', 'í', 'î', 'ï', 'ì', 'ß', '!', '$', '*', ')', ';', '^',
+    '-', '/', 'Â', 'Ä', 'À', 'Á', 'Ã', 'Å', 'Ç', 'Ñ', '¦', ',', '%', '_', '>', '?',
+    'ø', 'É', 'Ê', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', '`', ':', '#', '@', '\'', '=', '"',
+    'Ø', 'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', '«', '»', 'ð', 'ý', 'þ', '±',
+    '°', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 'ª', 'º', 'æ', '¸', 'Æ', '¤',
+    'µ', '~', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z', '¡', '¿', 'Ð', '[', 'Þ', '®',
+    '¬', '£', '¥', '·', '©', '§', '¶', '¼', '½', '¾', 'Ý', '¨', '¯', ']', '´', '×',
+    '{', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', '\u{00AD}', 'ô', 'ö', 'ò', 'ó', 'õ',
+    '}', 'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', '¹', 'û', 'ü', 'ù', 'ú', 'ÿ',
+    '\\', '÷', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', '²', 'Ô', 'Ö', 'Ò', 'Ó', 'Õ',
+    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', '³', 'Û', 'Ü', 'Ù', 'Ú', '\u{009F}',
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_printable_ascii() {
+        for code_page in [CodePage::Cp037, CodePage::Cp273, CodePage::Cp500, CodePage::Cp1047] {
+            for character in (0x20u8..0x7F).map(|byte| byte as char) {
+                let byte = code_page.get_byte(character).expect("Every printable ASCII character should be representable.");
+                assert_eq!(character, code_page.get_char(byte));
+            }
+        }
+        assert_eq!(Some(0xC1), CodePage::Cp037.get_byte('A'));
+        assert_eq!(Some(0xF0), CodePage::Cp037.get_byte('0'));
+        assert_eq!(Some(0x40), CodePage::Cp037.get_byte(' '));
+    }
+
+    #[test]
+    fn code_pages_differ_on_national_characters() {
+        assert_eq!('[', CodePage::Cp037.get_char(0xBA));
+        assert_eq!('Ý', CodePage::Cp1047.get_char(0xBA));
+        assert_eq!('[', CodePage::Cp1047.get_char(0xAD));
+        assert_eq!('[', CodePage::Cp500.get_char(0x4A));
+        assert_eq!('Ä', CodePage::Cp273.get_char(0x4A));
+        assert_eq!(String::from("Grüße"), CodePage::Cp273.decode(&CodePage::Cp273.encode("Grüße").unwrap()));
+    }
+
+    #[test]
+    fn reject_unrepresentable_characters() {
+        assert_eq!(Err('€'), CodePage::Cp037.encode("costs 5€"));
+        assert_eq!(Some(CodePage::Cp1047), CodePage::from_name("CP1047"));
+        assert_eq!(None, CodePage::from_name("cp9999"));
+    }
+}
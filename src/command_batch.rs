@@ -0,0 +1,147 @@
+#![allow(dead_code)]
+
+use std::{any::Any, marker::PhantomData};
+use crate::{client_interface::CommandBuilder, code_page::CodePage, error::Rs3270Error};
+
+// a command whose output type is hidden so that commands with different outputs can share a batch
+trait ErasedCommandBuilder {
+    fn get_client_message(&self) -> String;
+    fn append_client_data_response(&self, data: String) -> Result<(), String>;
+    fn validate(&self, code_page: &CodePage) -> Result<(), String>;
+    fn build(self: Box<Self>) -> Result<Box<dyn Any>, String>;
+}
+
+struct TypedCommand<TCommand, TOutput> {
+    command: TCommand,
+    output: PhantomData<fn() -> TOutput>
+}
+
+impl<TCommand: CommandBuilder<TOutput>, TOutput: 'static> ErasedCommandBuilder for TypedCommand<TCommand, TOutput> {
+    fn get_client_message(&self) -> String {
+        self.command.get_client_message()
+    }
+    fn append_client_data_response(&self, data: String) -> Result<(), String> {
+        self.command.append_client_data_response(data)
+    }
+    fn validate(&self, code_page: &CodePage) -> Result<(), String> {
+        self.command.validate(code_page)
+    }
+    fn build(self: Box<Self>) -> Result<Box<dyn Any>, String> {
+        self.command.build()
+            .map(|output| Box::new(output) as Box<dyn Any>)
+    }
+}
+
+// one command of a batch, which an executor runs like any other command
+pub struct BatchedCommand {
+    command: Box<dyn ErasedCommandBuilder>
+}
+
+impl CommandBuilder<Box<dyn Any>> for BatchedCommand {
+    fn get_client_message(&self) -> String {
+        self.command.get_client_message()
+    }
+    fn append_client_data_response(&self, data: String) -> Result<(), String> {
+        self.command.append_client_data_response(data)
+    }
+    fn validate(&self, code_page: &CodePage) -> Result<(), String> {
+        self.command.validate(code_page)
+    }
+    fn build(self) -> Result<Box<dyn Any>, String> {
+        self.command.build()
+    }
+}
+
+// commands to be sent together, in order, by CommandExecutor::execute_batch
+#[derive(Default)]
+pub struct CommandBatch {
+    commands: Vec<BatchedCommand>
+}
+
+impl CommandBatch {
+    pub fn new() -> Self {
+        CommandBatch {
+            commands: Vec::new()
+        }
+    }
+    // the handle takes the command's output from the BatchResults
+    pub fn add<TOutput: 'static>(&mut self, command: impl CommandBuilder<TOutput> + 'static) -> BatchHandle<TOutput> {
+        self.commands.push(BatchedCommand {
+            command: Box::new(TypedCommand {
+                command,
+                output: PhantomData
+            })
+        });
+        BatchHandle {
+            index: self.commands.len() - 1,
+            output: PhantomData
+        }
+    }
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+    pub fn into_commands(self) -> Vec<BatchedCommand> {
+        self.commands
+    }
+}
+
+pub struct BatchHandle<TOutput> {
+    index: usize,
+    output: PhantomData<fn() -> TOutput>
+}
+
+// the result of each command of a batch, in the order they were added
+pub struct BatchResults {
+    results: Vec<Option<Result<Box<dyn Any>, Rs3270Error>>>
+}
+
+impl BatchResults {
+    pub fn new(results: Vec<Result<Box<dyn Any>, Rs3270Error>>) -> Self {
+        BatchResults {
+            results: results.into_iter()
+                .map(Some)
+                .collect()
+        }
+    }
+    pub fn take<TOutput: 'static>(&mut self, handle: BatchHandle<TOutput>) -> Result<TOutput, Rs3270Error> {
+        let result = self.results.get_mut(handle.index)
+            .and_then(|result| result.take())
+            .expect("The handle should belong to the batch that produced these results.");
+        result.map(|output| {
+            *output.downcast::<TOutput>()
+                .expect("The output should have the type of the handle's command.")
+        })
+    }
+    pub fn is_ok(&self) -> bool {
+        self.results.iter()
+            .all(|result| !matches!(result, Some(Err(_))))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::client_interface::*;
+
+    use super::*;
+
+    #[test]
+    fn take_typed_outputs_by_handle() {
+        let mut batch = CommandBatch::new();
+        let cursor = batch.add(GetCursorCommand::new());
+        let tab = batch.add(MoveCursorToNextFieldCommand::new());
+        assert_eq!(2, batch.len());
+
+        let mut commands = batch.into_commands().into_iter();
+        let get_cursor_command = commands.next().unwrap();
+        assert_eq!("Query(Cursor)", get_cursor_command.get_client_message());
+        get_cursor_command.append_client_data_response(String::from("3 4")).unwrap();
+        let mut results = BatchResults::new(vec![get_cursor_command.build().map_err(|message| Rs3270Error::Parse { command: String::new(), message }), Err(Rs3270Error::Timeout { command: String::from("Tab") })]);
+
+        assert!(!results.is_ok());
+        assert_eq!((3, 4), results.take(cursor).unwrap());
+        assert!(matches!(results.take(tab), Err(Rs3270Error::Timeout { .. })));
+    }
+}
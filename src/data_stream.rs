@@ -0,0 +1,528 @@
+#![allow(dead_code)]
+
+// decodes host-to-terminal 3270 data streams into events that a screen model can apply
+
+pub const COMMAND_WRITE: u8 = 0xF1;
+pub const COMMAND_ERASE_WRITE: u8 = 0xF5;
+pub const COMMAND_ERASE_WRITE_ALTERNATE: u8 = 0x7E;
+pub const COMMAND_ERASE_ALL_UNPROTECTED: u8 = 0x6F;
+pub const COMMAND_WRITE_STRUCTURED_FIELD: u8 = 0xF3;
+pub const COMMAND_READ_BUFFER: u8 = 0xF2;
+pub const COMMAND_READ_MODIFIED: u8 = 0xF6;
+pub const COMMAND_READ_MODIFIED_ALL: u8 = 0x6E;
+
+pub const ORDER_START_FIELD: u8 = 0x1D;
+pub const ORDER_START_FIELD_EXTENDED: u8 = 0x29;
+pub const ORDER_SET_BUFFER_ADDRESS: u8 = 0x11;
+pub const ORDER_SET_ATTRIBUTE: u8 = 0x28;
+pub const ORDER_MODIFY_FIELD: u8 = 0x2C;
+pub const ORDER_INSERT_CURSOR: u8 = 0x13;
+pub const ORDER_PROGRAM_TAB: u8 = 0x05;
+pub const ORDER_REPEAT_TO_ADDRESS: u8 = 0x3C;
+pub const ORDER_ERASE_UNPROTECTED_TO_ADDRESS: u8 = 0x12;
+pub const ORDER_GRAPHIC_ESCAPE: u8 = 0x08;
+
+pub const WCC_RESET_MDT: u8 = 0x01;
+pub const WCC_KEYBOARD_RESTORE: u8 = 0x02;
+
+pub const EXTENDED_ATTRIBUTE_ALL: u8 = 0x00;
+pub const EXTENDED_ATTRIBUTE_FIELD: u8 = 0xC0;
+pub const EXTENDED_ATTRIBUTE_HIGHLIGHTING: u8 = 0x41;
+pub const EXTENDED_ATTRIBUTE_FOREGROUND_COLOR: u8 = 0x42;
+pub const EXTENDED_ATTRIBUTE_CHARACTER_SET: u8 = 0x43;
+pub const EXTENDED_ATTRIBUTE_BACKGROUND_COLOR: u8 = 0x45;
+pub const EXTENDED_ATTRIBUTE_TRANSPARENCY: u8 = 0x46;
+
+pub const STRUCTURED_FIELD_READ_PARTITION: u8 = 0x01;
+pub const STRUCTURED_FIELD_ERASE_RESET: u8 = 0x03;
+pub const STRUCTURED_FIELD_SET_REPLY_MODE: u8 = 0x09;
+pub const STRUCTURED_FIELD_OUTBOUND_3270DS: u8 = 0x40;
+pub const STRUCTURED_FIELD_QUERY_REPLY: u8 = 0x81;
+
+pub const READ_PARTITION_QUERY: u8 = 0x02;
+pub const READ_PARTITION_QUERY_LIST: u8 = 0x03;
+
+pub const QUERY_CODE_SUMMARY: u8 = 0x80;
+pub const QUERY_CODE_USABLE_AREA: u8 = 0x81;
+pub const QUERY_CODE_IMPLICIT_PARTITION: u8 = 0xA6;
+
+// the 6-bit code table used for 12-bit buffer addresses and field attribute bytes
+pub const ADDRESS_CODES: [u8; 64] = [
+    0x40, 0xC1, 0xC2, 0xC3, 0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0x4A, 0x4B, 0x4C, 0x4D, 0x4E, 0x4F,
+    0x50, 0xD1, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0x5A, 0x5B, 0x5C, 0x5D, 0x5E, 0x5F,
+    0x60, 0x61, 0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0x6A, 0x6B, 0x6C, 0x6D, 0x6E, 0x6F,
+    0xF0, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8, 0xF9, 0x7A, 0x7B, 0x7C, 0x7D, 0x7E, 0x7F
+];
+
+pub fn decode_address(first: u8, second: u8) -> usize {
+    if first & 0xC0 == 0 {
+        // 14-bit binary address
+        (((first & 0x3F) as usize) << 8) | second as usize
+    }
+    else {
+        // 12-bit coded address
+        (((first & 0x3F) as usize) << 6) | (second & 0x3F) as usize
+    }
+}
+
+pub fn encode_address(address: usize) -> [u8; 2] {
+    if address < 4096 {
+        [ADDRESS_CODES[(address >> 6) & 0x3F], ADDRESS_CODES[address & 0x3F]]
+    }
+    else {
+        [((address >> 8) & 0x3F) as u8, (address & 0xFF) as u8]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteControlCharacter(pub u8);
+
+impl WriteControlCharacter {
+    pub fn is_reset(&self) -> bool {
+        self.0 & 0x40 != 0
+    }
+    pub fn is_start_printer(&self) -> bool {
+        self.0 & 0x08 != 0
+    }
+    pub fn is_sound_alarm(&self) -> bool {
+        self.0 & 0x04 != 0
+    }
+    pub fn is_keyboard_restore(&self) -> bool {
+        self.0 & WCC_KEYBOARD_RESTORE != 0
+    }
+    pub fn is_reset_modified(&self) -> bool {
+        self.0 & WCC_RESET_MDT != 0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StructuredField {
+    ReadPartition {
+        partition_id: u8,
+        read_type: u8
+    },
+    EraseReset {
+        is_alternate: bool
+    },
+    SetReplyMode {
+        partition_id: u8,
+        mode: u8,
+        attribute_types: Vec<u8>
+    },
+    Outbound3270Ds {
+        partition_id: u8,
+        events: Vec<DataStreamEvent>
+    },
+    Unknown {
+        id: u8,
+        data: Vec<u8>
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataStreamEvent {
+    // commands
+    Write(WriteControlCharacter),
+    EraseWrite(WriteControlCharacter),
+    EraseWriteAlternate(WriteControlCharacter),
+    EraseAllUnprotected,
+    ReadBuffer,
+    ReadModified,
+    ReadModifiedAll,
+    StructuredField(StructuredField),
+    // orders
+    SetBufferAddress(usize),
+    StartField(u8),
+    StartFieldExtended(Vec<(u8, u8)>),
+    SetAttribute(u8, u8),
+    ModifyField(Vec<(u8, u8)>),
+    InsertCursor,
+    ProgramTab,
+    RepeatToAddress {
+        address: usize,
+        character: u8,
+        is_graphic_escape: bool
+    },
+    EraseUnprotectedToAddress(usize),
+    GraphicEscape(u8),
+    // a run of EBCDIC characters
+    Text(Vec<u8>)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DataStreamError {
+    Empty,
+    UnknownCommand(u8),
+    TruncatedOrder {
+        order: u8,
+        offset: usize
+    },
+    TruncatedStructuredField {
+        offset: usize
+    }
+}
+
+pub struct DataStreamDecoder {
+    is_sixteen_bit_addressing: bool
+}
+
+impl DataStreamDecoder {
+    pub fn new() -> Self {
+        DataStreamDecoder {
+            is_sixteen_bit_addressing: false
+        }
+    }
+    // 16-bit binary addresses replace the 12/14-bit forms once the host enables them for the partition
+    pub fn set_sixteen_bit_addressing(&mut self, is_sixteen_bit_addressing: bool) {
+        self.is_sixteen_bit_addressing = is_sixteen_bit_addressing;
+    }
+    fn read_address(&self, first: u8, second: u8) -> usize {
+        if self.is_sixteen_bit_addressing {
+            ((first as usize) << 8) | second as usize
+        }
+        else {
+            decode_address(first, second)
+        }
+    }
+    pub fn decode(&self, record: &[u8]) -> Result<Vec<DataStreamEvent>, DataStreamError> {
+        if record.is_empty() {
+            return Err(DataStreamError::Empty);
+        }
+        let mut events = Vec::new();
+        match record[0] {
+            0x01 | COMMAND_WRITE => {
+                self.decode_write(&record[1..], 1, DataStreamEvent::Write, &mut events)?;
+            },
+            0x05 | COMMAND_ERASE_WRITE => {
+                self.decode_write(&record[1..], 1, DataStreamEvent::EraseWrite, &mut events)?;
+            },
+            0x0D | COMMAND_ERASE_WRITE_ALTERNATE => {
+                self.decode_write(&record[1..], 1, DataStreamEvent::EraseWriteAlternate, &mut events)?;
+            },
+            0x0F | COMMAND_ERASE_ALL_UNPROTECTED => {
+                events.push(DataStreamEvent::EraseAllUnprotected);
+            },
+            0x02 | COMMAND_READ_BUFFER => {
+                events.push(DataStreamEvent::ReadBuffer);
+            },
+            0x06 | COMMAND_READ_MODIFIED => {
+                events.push(DataStreamEvent::ReadModified);
+            },
+            0x0E | COMMAND_READ_MODIFIED_ALL => {
+                events.push(DataStreamEvent::ReadModifiedAll);
+            },
+            0x11 | COMMAND_WRITE_STRUCTURED_FIELD => {
+                self.decode_structured_fields(&record[1..], 1, &mut events)?;
+            },
+            command => {
+                return Err(DataStreamError::UnknownCommand(command));
+            }
+        }
+        Ok(events)
+    }
+    fn decode_write(&self, data: &[u8], base_offset: usize, command: fn(WriteControlCharacter) -> DataStreamEvent, events: &mut Vec<DataStreamEvent>) -> Result<(), DataStreamError> {
+        if data.is_empty() {
+            // a write without a WCC changes nothing
+            events.push(command(WriteControlCharacter(0)));
+            return Ok(());
+        }
+        events.push(command(WriteControlCharacter(data[0])));
+        let mut text = Vec::new();
+        let mut index = 1;
+        while index < data.len() {
+            let order = data[index];
+            let required_length = match order {
+                ORDER_START_FIELD | ORDER_GRAPHIC_ESCAPE | ORDER_START_FIELD_EXTENDED | ORDER_MODIFY_FIELD => 2,
+                ORDER_SET_BUFFER_ADDRESS | ORDER_SET_ATTRIBUTE | ORDER_ERASE_UNPROTECTED_TO_ADDRESS => 3,
+                ORDER_REPEAT_TO_ADDRESS => 4,
+                _ => 1
+            };
+            if index + required_length > data.len() {
+                return Err(DataStreamError::TruncatedOrder {
+                    order,
+                    offset: base_offset + index
+                });
+            }
+            let is_order = matches!(order,
+                ORDER_START_FIELD | ORDER_START_FIELD_EXTENDED | ORDER_SET_BUFFER_ADDRESS | ORDER_SET_ATTRIBUTE |
+                ORDER_MODIFY_FIELD | ORDER_INSERT_CURSOR | ORDER_PROGRAM_TAB | ORDER_REPEAT_TO_ADDRESS |
+                ORDER_ERASE_UNPROTECTED_TO_ADDRESS | ORDER_GRAPHIC_ESCAPE);
+            if !is_order {
+                text.push(order);
+                index += 1;
+                continue;
+            }
+            if !text.is_empty() {
+                events.push(DataStreamEvent::Text(std::mem::take(&mut text)));
+            }
+            match order {
+                ORDER_START_FIELD => {
+                    events.push(DataStreamEvent::StartField(data[index + 1]));
+                    index += 2;
+                },
+                ORDER_START_FIELD_EXTENDED | ORDER_MODIFY_FIELD => {
+                    let pair_count = data[index + 1] as usize;
+                    if index + 2 + pair_count * 2 > data.len() {
+                        return Err(DataStreamError::TruncatedOrder {
+                            order,
+                            offset: base_offset + index
+                        });
+                    }
+                    let pairs = data[index + 2..index + 2 + pair_count * 2]
+                        .chunks(2)
+                        .map(|pair| (pair[0], pair[1]))
+                        .collect::<Vec<(u8, u8)>>();
+                    if order == ORDER_START_FIELD_EXTENDED {
+                        events.push(DataStreamEvent::StartFieldExtended(pairs));
+                    }
+                    else {
+                        events.push(DataStreamEvent::ModifyField(pairs));
+                    }
+                    index += 2 + pair_count * 2;
+                },
+                ORDER_SET_BUFFER_ADDRESS => {
+                    events.push(DataStreamEvent::SetBufferAddress(self.read_address(data[index + 1], data[index + 2])));
+                    index += 3;
+                },
+                ORDER_SET_ATTRIBUTE => {
+                    events.push(DataStreamEvent::SetAttribute(data[index + 1], data[index + 2]));
+                    index += 3;
+                },
+                ORDER_INSERT_CURSOR => {
+                    events.push(DataStreamEvent::InsertCursor);
+                    index += 1;
+                },
+                ORDER_PROGRAM_TAB => {
+                    events.push(DataStreamEvent::ProgramTab);
+                    index += 1;
+                },
+                ORDER_REPEAT_TO_ADDRESS => {
+                    let address = self.read_address(data[index + 1], data[index + 2]);
+                    let is_graphic_escape = data[index + 3] == ORDER_GRAPHIC_ESCAPE;
+                    let character = if is_graphic_escape {
+                        if index + 4 >= data.len() {
+                            return Err(DataStreamError::TruncatedOrder {
+                                order,
+                                offset: base_offset + index
+                            });
+                        }
+                        data[index + 4]
+                    }
+                    else {
+                        data[index + 3]
+                    };
+                    events.push(DataStreamEvent::RepeatToAddress {
+                        address,
+                        character,
+                        is_graphic_escape
+                    });
+                    index += if is_graphic_escape { 5 } else { 4 };
+                },
+                ORDER_ERASE_UNPROTECTED_TO_ADDRESS => {
+                    events.push(DataStreamEvent::EraseUnprotectedToAddress(self.read_address(data[index + 1], data[index + 2])));
+                    index += 3;
+                },
+                ORDER_GRAPHIC_ESCAPE => {
+                    events.push(DataStreamEvent::GraphicEscape(data[index + 1]));
+                    index += 2;
+                },
+                _ => {
+                    unreachable!("Every order should have been matched above.");
+                }
+            }
+        }
+        if !text.is_empty() {
+            events.push(DataStreamEvent::Text(text));
+        }
+        Ok(())
+    }
+    fn decode_structured_fields(&self, data: &[u8], base_offset: usize, events: &mut Vec<DataStreamEvent>) -> Result<(), DataStreamError> {
+        let mut index = 0;
+        while index < data.len() {
+            if index + 3 > data.len() {
+                return Err(DataStreamError::TruncatedStructuredField {
+                    offset: base_offset + index
+                });
+            }
+            // a length of zero means the field runs to the end of the record
+            let length = match ((data[index] as usize) << 8) | data[index + 1] as usize {
+                0 => data.len() - index,
+                length => length
+            };
+            if length < 3 || index + length > data.len() {
+                return Err(DataStreamError::TruncatedStructuredField {
+                    offset: base_offset + index
+                });
+            }
+            let id = data[index + 2];
+            let field_data = &data[index + 3..index + length];
+            let structured_field = match id {
+                STRUCTURED_FIELD_READ_PARTITION if field_data.len() >= 2 => {
+                    StructuredField::ReadPartition {
+                        partition_id: field_data[0],
+                        read_type: field_data[1]
+                    }
+                },
+                STRUCTURED_FIELD_ERASE_RESET => {
+                    StructuredField::EraseReset {
+                        is_alternate: field_data.first().map(|flags| flags & 0x80 != 0).unwrap_or(false)
+                    }
+                },
+                STRUCTURED_FIELD_SET_REPLY_MODE if field_data.len() >= 2 => {
+                    StructuredField::SetReplyMode {
+                        partition_id: field_data[0],
+                        mode: field_data[1],
+                        attribute_types: field_data[2..].to_vec()
+                    }
+                },
+                STRUCTURED_FIELD_OUTBOUND_3270DS if field_data.len() >= 2 => {
+                    let mut nested_events = Vec::new();
+                    let nested_offset = base_offset + index + 5;
+                    match field_data[1] {
+                        COMMAND_WRITE => {
+                            self.decode_write(&field_data[2..], nested_offset, DataStreamEvent::Write, &mut nested_events)?;
+                        },
+                        COMMAND_ERASE_WRITE => {
+                            self.decode_write(&field_data[2..], nested_offset, DataStreamEvent::EraseWrite, &mut nested_events)?;
+                        },
+                        COMMAND_ERASE_WRITE_ALTERNATE => {
+                            self.decode_write(&field_data[2..], nested_offset, DataStreamEvent::EraseWriteAlternate, &mut nested_events)?;
+                        },
+                        COMMAND_ERASE_ALL_UNPROTECTED => {
+                            nested_events.push(DataStreamEvent::EraseAllUnprotected);
+                        },
+                        command => {
+                            return Err(DataStreamError::UnknownCommand(command));
+                        }
+                    }
+                    StructuredField::Outbound3270Ds {
+                        partition_id: field_data[0],
+                        events: nested_events
+                    }
+                },
+                _ => {
+                    StructuredField::Unknown {
+                        id,
+                        data: field_data.to_vec()
+                    }
+                }
+            };
+            events.push(DataStreamEvent::StructuredField(structured_field));
+            index += length;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_and_decode_addresses() {
+        for address in [0, 1, 79, 80, 1919, 3439, 4095] {
+            let encoded = encode_address(address);
+            assert_eq!(address, decode_address(encoded[0], encoded[1]));
+        }
+        assert_eq!([0x5D, 0x7F], encode_address(1919));
+        assert_eq!([0x10, 0x00], encode_address(4096));
+        assert_eq!(4096, decode_address(0x10, 0x00));
+    }
+
+    #[test]
+    fn decode_erase_write_with_orders() {
+        let mut record = vec![COMMAND_ERASE_WRITE, 0xC3, ORDER_SET_BUFFER_ADDRESS];
+        record.extend(encode_address(81));
+        record.extend([ORDER_START_FIELD, 0x60, 0xC1, 0xC2, ORDER_INSERT_CURSOR]);
+        record.extend([ORDER_START_FIELD_EXTENDED, 2, EXTENDED_ATTRIBUTE_FIELD, 0x40, EXTENDED_ATTRIBUTE_FOREGROUND_COLOR, 0xF2]);
+        record.extend([ORDER_SET_ATTRIBUTE, EXTENDED_ATTRIBUTE_HIGHLIGHTING, 0xF1, ORDER_PROGRAM_TAB]);
+        record.push(ORDER_REPEAT_TO_ADDRESS);
+        record.extend(encode_address(160));
+        record.extend([ORDER_GRAPHIC_ESCAPE, 0xAD, ORDER_ERASE_UNPROTECTED_TO_ADDRESS]);
+        record.extend(encode_address(200));
+        record.extend([ORDER_MODIFY_FIELD, 1, EXTENDED_ATTRIBUTE_FIELD, 0x20, ORDER_GRAPHIC_ESCAPE, 0xC5, 0xC3]);
+
+        let events = DataStreamDecoder::new().decode(&record).unwrap();
+        let write_control_character = WriteControlCharacter(0xC3);
+        assert!(write_control_character.is_reset());
+        assert!(write_control_character.is_keyboard_restore());
+        assert!(write_control_character.is_reset_modified());
+        assert!(!write_control_character.is_sound_alarm());
+        assert_eq!(vec![
+            DataStreamEvent::EraseWrite(write_control_character),
+            DataStreamEvent::SetBufferAddress(81),
+            DataStreamEvent::StartField(0x60),
+            DataStreamEvent::Text(vec![0xC1, 0xC2]),
+            DataStreamEvent::InsertCursor,
+            DataStreamEvent::StartFieldExtended(vec![(EXTENDED_ATTRIBUTE_FIELD, 0x40), (EXTENDED_ATTRIBUTE_FOREGROUND_COLOR, 0xF2)]),
+            DataStreamEvent::SetAttribute(EXTENDED_ATTRIBUTE_HIGHLIGHTING, 0xF1),
+            DataStreamEvent::ProgramTab,
+            DataStreamEvent::RepeatToAddress {
+                address: 160,
+                character: 0xAD,
+                is_graphic_escape: true
+            },
+            DataStreamEvent::EraseUnprotectedToAddress(200),
+            DataStreamEvent::ModifyField(vec![(EXTENDED_ATTRIBUTE_FIELD, 0x20)]),
+            DataStreamEvent::GraphicEscape(0xC5),
+            DataStreamEvent::Text(vec![0xC3])
+        ], events);
+    }
+
+    #[test]
+    fn decode_buffer_address_forms() {
+        let decoder = DataStreamDecoder::new();
+        let events = decoder.decode(&[COMMAND_WRITE, 0x00, ORDER_SET_BUFFER_ADDRESS, 0x5D, 0x7F, ORDER_SET_BUFFER_ADDRESS, 0x0D, 0xEB]).unwrap();
+        assert_eq!(vec![
+            DataStreamEvent::Write(WriteControlCharacter(0x00)),
+            DataStreamEvent::SetBufferAddress(1919),
+            DataStreamEvent::SetBufferAddress(3563)
+        ], events);
+
+        let mut decoder = DataStreamDecoder::new();
+        decoder.set_sixteen_bit_addressing(true);
+        let events = decoder.decode(&[COMMAND_WRITE, 0x00, ORDER_SET_BUFFER_ADDRESS, 0x5D, 0x7F]).unwrap();
+        assert_eq!(DataStreamEvent::SetBufferAddress(0x5D7F), events[1]);
+    }
+
+    #[test]
+    fn decode_structured_fields() {
+        let record = vec![
+            COMMAND_WRITE_STRUCTURED_FIELD,
+            0x00, 0x05, STRUCTURED_FIELD_READ_PARTITION, 0xFF, 0x02,
+            0x00, 0x04, STRUCTURED_FIELD_ERASE_RESET, 0x80,
+            0x00, 0x00, STRUCTURED_FIELD_OUTBOUND_3270DS, 0x00, COMMAND_WRITE, 0x02, 0xC1
+        ];
+        let events = DataStreamDecoder::new().decode(&record).unwrap();
+        assert_eq!(vec![
+            DataStreamEvent::StructuredField(StructuredField::ReadPartition {
+                partition_id: 0xFF,
+                read_type: 0x02
+            }),
+            DataStreamEvent::StructuredField(StructuredField::EraseReset {
+                is_alternate: true
+            }),
+            DataStreamEvent::StructuredField(StructuredField::Outbound3270Ds {
+                partition_id: 0x00,
+                events: vec![
+                    DataStreamEvent::Write(WriteControlCharacter(0x02)),
+                    DataStreamEvent::Text(vec![0xC1])
+                ]
+            })
+        ], events);
+    }
+
+    #[test]
+    fn reject_truncated_and_unknown_records() {
+        let decoder = DataStreamDecoder::new();
+        assert_eq!(Err(DataStreamError::Empty), decoder.decode(&[]));
+        assert_eq!(Err(DataStreamError::UnknownCommand(0x99)), decoder.decode(&[0x99]));
+        assert_eq!(Err(DataStreamError::TruncatedOrder {
+            order: ORDER_SET_BUFFER_ADDRESS,
+            offset: 3
+        }), decoder.decode(&[COMMAND_WRITE, 0x00, 0xC1, ORDER_SET_BUFFER_ADDRESS, 0x40]));
+        assert_eq!(Err(DataStreamError::TruncatedStructuredField {
+            offset: 1
+        }), decoder.decode(&[COMMAND_WRITE_STRUCTURED_FIELD, 0x00, 0x09, 0x01]));
+    }
+}
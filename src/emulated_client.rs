@@ -0,0 +1,279 @@
+#![allow(dead_code)]
+
+use std::time::{Duration, Instant};
+use crate::{client_interface::*, client_status::ClientStatus, code_page::CodePage, data_stream::*, emulator::*, error::Rs3270Error, logging::*, screen::*};
+
+const EMULATED_HOST: &str = "emulated";
+
+// answers an inbound record, such as the one an Enter produces, with the outbound records the host would write back
+type HostResponder = Box<dyn FnMut(&[u8]) -> Vec<Vec<u8>>>;
+
+// a CommandExecutor backed by an in-memory screen, for testing logic built on commands without a client or host
+// without a host responder every AID key is acknowledged straight away by unlocking the keyboard
+pub struct EmulatedCommandExecutor {
+    screen: Screen,
+    status: Option<ClientStatus>,
+    timeouts: Timeouts,
+    // every inbound record sent by an AID key, oldest first
+    inbound_records: Vec<Vec<u8>>,
+    host_responder: Option<HostResponder>,
+    is_output_pending: bool
+}
+
+impl EmulatedCommandExecutor {
+    pub fn new(rows: usize, columns: usize) -> Self {
+        EmulatedCommandExecutor {
+            screen: Screen::new(rows, columns),
+            status: None,
+            timeouts: Timeouts::default(),
+            inbound_records: Vec::new(),
+            host_responder: None,
+            is_output_pending: false
+        }
+    }
+    // starts a field at the position, whose attribute takes that position, followed by its text
+    pub fn add_field(&mut self, row: usize, column: usize, is_protected: bool, text: &str) -> Result<(), String> {
+        if row >= self.screen.get_rows() || column >= self.screen.get_columns() {
+            return Err(format!("Position ({row},{column}) is outside of the {}x{} screen.", self.screen.get_rows(), self.screen.get_columns()));
+        }
+        let text = self.screen.get_code_page().encode(text)
+            .map_err(|character| format!("'{character}' cannot be shown on the screen."))?;
+        let field_attribute = if is_protected { FIELD_ATTRIBUTE_PROTECTED } else { 0 };
+        let cursor = self.screen.get_cursor();
+        self.screen.apply_events(&[
+            DataStreamEvent::Write(WriteControlCharacter(0)),
+            DataStreamEvent::SetBufferAddress(row * self.screen.get_columns() + column),
+            DataStreamEvent::StartField(field_attribute),
+            DataStreamEvent::Text(text)
+        ]);
+        self.screen.move_cursor(cursor.0, cursor.1)
+    }
+    // applies an outbound record as if the host had sent it
+    pub fn apply_record(&mut self, record: &[u8]) {
+        self.is_output_pending = true;
+        if let Some(inbound) = self.screen.process_record(record) {
+            self.inbound_records.push(inbound);
+        }
+    }
+    pub fn set_host_responder(&mut self, host_responder: impl FnMut(&[u8]) -> Vec<Vec<u8>> + 'static) {
+        self.host_responder = Some(Box::new(host_responder));
+    }
+    pub fn get_screen(&self) -> &Screen {
+        &self.screen
+    }
+    pub fn get_screen_mut(&mut self) -> &mut Screen {
+        &mut self.screen
+    }
+    pub fn get_inbound_records(&self) -> &[Vec<u8>] {
+        &self.inbound_records
+    }
+}
+
+impl ScreenEmulator for EmulatedCommandExecutor {
+    fn get_screen(&self) -> &Screen {
+        &self.screen
+    }
+    fn get_screen_mut(&mut self) -> &mut Screen {
+        &mut self.screen
+    }
+    fn send_aid(&mut self, aid: u8) -> Result<(), ActionFailure> {
+        if self.screen.is_keyboard_locked() {
+            return Err(ActionFailure::KeyboardLocked);
+        }
+        let inbound = self.screen.read_modified(aid);
+        if aid == AID_CLEAR {
+            self.screen.erase();
+        }
+        self.screen.set_keyboard_locked(true);
+        self.is_output_pending = false;
+        match self.host_responder.as_mut() {
+            Some(host_responder) => {
+                let outbound_records = host_responder(&inbound);
+                self.inbound_records.push(inbound);
+                for outbound_record in outbound_records {
+                    self.apply_record(&outbound_record);
+                }
+            },
+            None => {
+                self.inbound_records.push(inbound);
+                self.screen.set_keyboard_locked(false);
+            }
+        }
+        Ok(())
+    }
+    // there is no network connection, so the host is named without a port
+    fn query_connection(&self, item: &str) -> Option<String> {
+        match item {
+            "Host" => Some(format!("host {EMULATED_HOST} 0")),
+            "LuName" => Some(String::from("none")),
+            "Tls" => Some(String::from("not secure")),
+            "ConnectionState" => Some(String::from("connected-3270")),
+            _ => None
+        }
+    }
+    // nothing changes the screen between actions, so a condition that does not hold now never will, and the timeout is not waited out
+    fn wait(&mut self, condition: WaitCondition, _timeout: Option<Duration>) -> Result<(), ActionFailure> {
+        let is_satisfied = match condition {
+            WaitCondition::InputField => {
+                self.screen.is_formatted() && !self.screen.is_keyboard_locked()
+            },
+            WaitCondition::Unlock => {
+                !self.screen.is_keyboard_locked()
+            },
+            WaitCondition::Output => {
+                std::mem::replace(&mut self.is_output_pending, false)
+            },
+            WaitCondition::Mode3270 | WaitCondition::Connected | WaitCondition::Seconds => {
+                true
+            },
+            WaitCondition::Disconnected => {
+                false
+            },
+            WaitCondition::NvtMode => {
+                return Err(ActionFailure::CommandFailure(String::from("Wait: the emulated host has no NVT mode.")));
+            }
+        };
+        if !is_satisfied {
+            return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, format!("Wait({}) cannot be satisfied by the emulated host.", condition.get_keyword())).into());
+        }
+        Ok(())
+    }
+}
+
+impl CommandExecutor for EmulatedCommandExecutor {
+    // there is no process to connect to, so this starts with a blank 24x80 screen
+    fn connect_to_client_process(_client_address: &str) -> Option<Self> {
+        Some(EmulatedCommandExecutor::new(24, 80))
+    }
+    fn execute_with_timeouts<TOutput>(&mut self, command: impl CommandBuilder<TOutput>, _timeouts: &Timeouts) -> Result<TOutput, Rs3270Error> {
+        let client_message = command.get_client_message();
+        let span = start_command_span(&client_message);
+        let _entered = span.enter();
+        let start_time = Instant::now();
+        let execution_result = self.execute_actions(command, &client_message);
+        record_command_outcome(&span, start_time, &execution_result);
+        self.status = Some(get_screen_status(&self.screen, Some(EMULATED_HOST), Some(start_time.elapsed())));
+        execution_result
+    }
+    fn get_timeouts(&self) -> &Timeouts {
+        &self.timeouts
+    }
+    fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+    fn resynchronize(&mut self) -> Result<(), Rs3270Error> {
+        Ok(())
+    }
+    fn get_status(&self) -> Option<&ClientStatus> {
+        self.status.as_ref()
+    }
+    fn get_code_page(&self) -> CodePage {
+        self.screen.get_code_page()
+    }
+    fn set_code_page(&mut self, code_page: CodePage) {
+        self.screen.set_code_page(code_page);
+    }
+    fn disconnect(&mut self) {
+        // nothing to release
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{command_batch::CommandBatch, field::Color};
+
+    use super::*;
+
+    fn get_login_executor() -> EmulatedCommandExecutor {
+        let mut executor = EmulatedCommandExecutor::new(24, 80);
+        executor.add_field(0, 0, true, "USER:").unwrap();
+        executor.add_field(0, 6, false, "").unwrap();
+        executor.add_field(0, 16, true, "").unwrap();
+        executor
+    }
+
+    #[test]
+    fn type_into_unprotected_field_then_read_it() {
+        let mut executor = get_login_executor();
+
+        executor.execute(MoveCursorToFirstFieldCommand::new()).unwrap();
+        assert_eq!((0, 7), executor.execute(GetCursorCommand::new()).unwrap());
+        executor.execute(SetTextCommand::new(String::from("ALICE"))).unwrap();
+        assert_eq!(" USER: ALICE", executor.execute(GetTextCommand::new(0, 0, 12)).unwrap().trim_end());
+        executor.execute(MoveCursorToFieldEndCommand::new()).unwrap();
+        assert_eq!((0, 12), executor.execute(GetCursorCommand::new()).unwrap());
+        executor.execute(ClearTextFromFieldCommand::new()).unwrap();
+        assert_eq!(" USER:", executor.execute(GetTextCommand::new(0, 0, 12)).unwrap().trim_end());
+    }
+
+    #[test]
+    fn type_quotes_and_backslashes_as_text() {
+        let mut executor = get_login_executor();
+
+        let command = SetTextCommand::new(String::from("A\\\")Quit"));
+        assert_eq!("String(\"A\\\\\\\")Quit\")", command.get_client_message());
+        executor.execute(MoveCursorToFirstFieldCommand::new()).unwrap();
+        executor.execute(command).unwrap();
+        assert_eq!(" USER: A\\\")Quit", executor.execute(GetTextCommand::new(0, 0, 15)).unwrap().trim_end());
+        let error = executor.execute(SetTextCommand::new(String::from("A\nQuit"))).unwrap_err();
+        assert!(matches!(error, Rs3270Error::Rejected { .. }));
+    }
+
+    #[test]
+    fn reject_typing_into_protected_field() {
+        let mut executor = get_login_executor();
+
+        executor.execute(MoveCursorCommand::new(0, 2)).unwrap();
+        let error = executor.execute(SetTextCommand::new(String::from("X"))).unwrap_err();
+        assert!(matches!(error, Rs3270Error::Emulator { .. }));
+        assert!(executor.get_status().unwrap().is_field_protected);
+    }
+
+    #[test]
+    fn refuse_batch_without_running_any_of_it() {
+        let mut executor = get_login_executor();
+
+        let mut batch = CommandBatch::new();
+        batch.add(MoveCursorCommand::new(0, 7));
+        batch.add(SetTextCommand::new(String::from("costs 5€")));
+        assert!(matches!(executor.execute_batch(batch), Err(Rs3270Error::Rejected { .. })));
+        // the cursor move before the refused text did not run
+        assert_eq!((0, 0), executor.execute(GetCursorCommand::new()).unwrap());
+    }
+
+    #[test]
+    fn answer_enter_through_host_responder() {
+        let mut executor = get_login_executor();
+        executor.set_host_responder(|_| {
+            // leaves the keyboard locked until the host writes again
+            vec![vec![COMMAND_WRITE, 0]]
+        });
+
+        executor.execute(MoveCursorToFirstFieldCommand::new()).unwrap();
+        executor.execute(SendEnterKeyCommand::new()).unwrap();
+        assert_eq!(1, executor.get_inbound_records().len());
+        assert_eq!(AID_ENTER, executor.get_inbound_records()[0][0]);
+        assert!(executor.get_status().unwrap().is_keyboard_locked());
+        let error = executor.execute(SendEnterKeyCommand::new()).unwrap_err();
+        assert!(matches!(error, Rs3270Error::KeyboardLocked { .. }));
+        executor.apply_record(&[COMMAND_WRITE, WCC_KEYBOARD_RESTORE]);
+        executor.execute(SendEnterKeyCommand::new()).unwrap();
+    }
+
+    #[test]
+    fn read_buffer_with_extended_attributes() {
+        let mut executor = EmulatedCommandExecutor::new(2, 10);
+        let mut record = vec![COMMAND_ERASE_WRITE, WCC_KEYBOARD_RESTORE, ORDER_START_FIELD_EXTENDED, 2, EXTENDED_ATTRIBUTE_FIELD, 0x28, EXTENDED_ATTRIBUTE_FOREGROUND_COLOR, 0xF2];
+        record.extend(CodePage::Cp037.encode("ERROR").unwrap());
+        record.extend([ORDER_START_FIELD, 0x00]);
+        executor.apply_record(&record);
+
+        assert_eq!("SF(c0=e8,42=f2) 45 52 52 4f 52 SF(c0=c0) 00 00 00", executor.get_screen().get_read_buffer_lines()[0]);
+        let fields = executor.execute(ReadBufferCommand::new()).unwrap();
+        assert_eq!(2, fields.len());
+        assert_eq!(Color::Red, fields[0].color);
+        assert!(fields[0].is_protected && fields[0].is_intensified);
+        assert_eq!("ERROR", fields[0].content);
+        assert_eq!(13, fields[1].length);
+    }
+}
@@ -0,0 +1,346 @@
+#![allow(dead_code)]
+
+use std::time::Duration;
+use crate::{action::Action, client_interface::{CommandBuilder, WaitCondition}, client_status::*, error::Rs3270Error, logging::*, screen::*};
+
+#[derive(Debug)]
+pub(crate) enum ActionFailure {
+    IoError(std::io::Error),
+    CommandFailure(String),
+    KeyboardLocked
+}
+
+impl From<std::io::Error> for ActionFailure {
+    fn from(error: std::io::Error) -> Self {
+        ActionFailure::IoError(error)
+    }
+}
+
+fn get_usize_argument(action: &Action, index: usize) -> Result<usize, ActionFailure> {
+    action.get_argument_as::<usize>(index)
+        .ok_or_else(|| ActionFailure::CommandFailure(format!("{}: argument {} should be a non-negative number.", action.name, index + 1)))
+}
+
+fn format_ebcdic(bytes: &[u8]) -> String {
+    bytes.iter()
+        .map(|byte| format!("0x{byte:02x}"))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+// describes the screen the same way as the status line that x3270 reports after each action, where host is None once disconnected
+pub(crate) fn get_screen_status(screen: &Screen, host: Option<&str>, execution_time: Option<Duration>) -> ClientStatus {
+    let (cursor_row, cursor_column) = screen.get_cursor();
+    ClientStatus {
+        keyboard_state: if screen.is_keyboard_locked() { KeyboardState::Locked } else { KeyboardState::Unlocked },
+        is_formatted: screen.is_formatted(),
+        is_field_protected: screen.is_cursor_protected(),
+        connection_state: if let Some(host) = host { ConnectionState::Connected(String::from(host)) } else { ConnectionState::NotConnected },
+        emulator_mode: if host.is_some() { EmulatorMode::Tn3270 } else { EmulatorMode::NotConnected },
+        model_number: screen.get_model().get_number(),
+        rows: screen.get_rows(),
+        columns: screen.get_columns(),
+        cursor_row,
+        cursor_column,
+        window_id: 0,
+        execution_time
+    }
+}
+
+// a terminal that answers x3270 actions from its own Screen, leaving the AID keys and waits to the implementor
+pub(crate) trait ScreenEmulator {
+    fn get_screen(&self) -> &Screen;
+    fn get_screen_mut(&mut self) -> &mut Screen;
+    fn send_aid(&mut self, aid: u8) -> Result<(), ActionFailure>;
+    // waits until the condition holds, giving up with a TimedOut error after the timeout, or after the read timeout when None
+    fn wait(&mut self, condition: WaitCondition, timeout: Option<Duration>) -> Result<(), ActionFailure>;
+    // the Attn key, which is not an AID but a signal outside of the data stream
+    fn send_attention(&mut self) -> Result<(), ActionFailure> {
+        Err(ActionFailure::CommandFailure(String::from("Attn: there is no host to interrupt.")))
+    }
+    // the answer to the Query() items that depend on the connection rather than the screen: Host, LuName, Tls and ConnectionState
+    fn query_connection(&self, item: &str) -> Option<String>;
+    // applies whatever the host sent since the previous command
+    fn receive_pending(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+    // the actions that the command macros emit, answered from the screen
+    fn perform_action(&mut self, action: &Action) -> Result<Vec<String>, ActionFailure> {
+        let mut lines = Vec::new();
+        match (action.name.as_str(), action.arguments.len()) {
+            ("Ascii", 0) => {
+                lines = self.get_screen().get_text_range(0, 0, self.get_screen().get_rows(), self.get_screen().get_columns())
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("Ascii", 3) => {
+                let row = get_usize_argument(action, 0)?;
+                let column = get_usize_argument(action, 1)?;
+                let length = get_usize_argument(action, 2)?;
+                lines.push(self.get_screen().get_text(row, column, length)
+                    .map_err(ActionFailure::CommandFailure)?);
+            },
+            ("Ascii", 4) => {
+                let row = get_usize_argument(action, 0)?;
+                let column = get_usize_argument(action, 1)?;
+                let rows = get_usize_argument(action, 2)?;
+                let columns = get_usize_argument(action, 3)?;
+                lines = self.get_screen().get_text_range(row, column, rows, columns)
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("Ebcdic", 3) => {
+                let row = get_usize_argument(action, 0)?;
+                let column = get_usize_argument(action, 1)?;
+                let length = get_usize_argument(action, 2)?;
+                let bytes = self.get_screen().get_ebcdic(row, column, length)
+                    .map_err(ActionFailure::CommandFailure)?;
+                lines.push(format_ebcdic(&bytes));
+            },
+            ("Ebcdic", 4) => {
+                let row = get_usize_argument(action, 0)?;
+                let column = get_usize_argument(action, 1)?;
+                let rows = get_usize_argument(action, 2)?;
+                let columns = get_usize_argument(action, 3)?;
+                if row + rows > self.get_screen().get_rows() {
+                    return Err(ActionFailure::CommandFailure(format!("Ebcdic: {rows} rows at row {row} extend past the end of the screen.")));
+                }
+                for current_row in row..row + rows {
+                    let bytes = self.get_screen().get_ebcdic(current_row, column, columns)
+                        .map_err(ActionFailure::CommandFailure)?;
+                    lines.push(format_ebcdic(&bytes));
+                }
+            },
+            ("MoveCursor", 2) => {
+                let row = get_usize_argument(action, 0)?;
+                let column = get_usize_argument(action, 1)?;
+                self.get_screen_mut().move_cursor(row, column)
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("String", 1) => {
+                for character in action.arguments[0].chars() {
+                    match character {
+                        '\n' => {
+                            self.send_aid(AID_ENTER)?;
+                        },
+                        '\t' => {
+                            self.get_screen_mut().tab();
+                        },
+                        _ => {
+                            let byte = self.get_screen().get_code_page().get_byte(character)
+                                .ok_or_else(|| ActionFailure::CommandFailure(format!("String: '{character}' cannot be sent to the host.")))?;
+                            self.get_screen_mut().type_character(byte)
+                                .map_err(ActionFailure::CommandFailure)?;
+                        }
+                    }
+                }
+            },
+            ("Tab", 0) => {
+                self.get_screen_mut().tab();
+            },
+            ("BackTab", 0) => {
+                self.get_screen_mut().back_tab();
+            },
+            ("Home", 0) => {
+                self.get_screen_mut().home();
+            },
+            ("FieldEnd", 0) => {
+                self.get_screen_mut().field_end()
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("DeleteField", 0) => {
+                self.get_screen_mut().delete_field()
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("Enter", 0) => {
+                self.send_aid(AID_ENTER)?;
+            },
+            ("Clear", 0) => {
+                self.send_aid(AID_CLEAR)?;
+            },
+            ("PF", 1) => {
+                let number = get_usize_argument(action, 0)?;
+                let aid = u8::try_from(number).ok()
+                    .and_then(get_program_function_aid)
+                    .ok_or_else(|| ActionFailure::CommandFailure(format!("PF: there is no PF{number} key.")))?;
+                self.send_aid(aid)?;
+            },
+            ("PA", 1) => {
+                let aid = match get_usize_argument(action, 0)? {
+                    1 => AID_PA1,
+                    2 => AID_PA2,
+                    3 => AID_PA3,
+                    number => {
+                        return Err(ActionFailure::CommandFailure(format!("PA: there is no PA{number} key.")));
+                    }
+                };
+                self.send_aid(aid)?;
+            },
+            ("SysReq", 0) => {
+                self.send_aid(AID_SYSTEM_REQUEST)?;
+            },
+            ("Attn", 0) => {
+                self.send_attention()?;
+            },
+            ("Reset", 0) => {
+                self.get_screen_mut().reset();
+            },
+            ("Insert", 0) => {
+                self.get_screen_mut().set_insert_mode(true);
+            },
+            ("ToggleInsert", 0) => {
+                let is_insert_mode = self.get_screen().is_insert_mode();
+                self.get_screen_mut().set_insert_mode(!is_insert_mode);
+            },
+            ("Left", 0) => {
+                self.get_screen_mut().move_cursor_by(-1);
+            },
+            ("Right", 0) => {
+                self.get_screen_mut().move_cursor_by(1);
+            },
+            ("Left2", 0) => {
+                self.get_screen_mut().move_cursor_by(-2);
+            },
+            ("Right2", 0) => {
+                self.get_screen_mut().move_cursor_by(2);
+            },
+            ("Up", 0) => {
+                self.get_screen_mut().move_cursor_up();
+            },
+            ("Down", 0) => {
+                self.get_screen_mut().move_cursor_down();
+            },
+            ("Newline", 0) => {
+                self.get_screen_mut().new_line();
+            },
+            ("Delete", 0) => {
+                self.get_screen_mut().delete_character()
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("Erase", 0) => {
+                self.get_screen_mut().erase_previous_character()
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("EraseEOF", 0) => {
+                self.get_screen_mut().erase_end_of_field()
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("EraseInput", 0) => {
+                self.get_screen_mut().erase_input();
+            },
+            ("Dup", 0) => {
+                self.get_screen_mut().duplicate()
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("FieldMark", 0) => {
+                self.get_screen_mut().field_mark()
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("CursorSelect", 0) => {
+                self.get_screen_mut().cursor_select()
+                    .map_err(ActionFailure::CommandFailure)?;
+            },
+            ("Wait", 0) => {
+                self.wait(WaitCondition::InputField, None)?;
+            },
+            ("Wait", argument_count @ (1 | 2)) => {
+                let keyword = &action.arguments[argument_count - 1];
+                let condition = WaitCondition::from_keyword(keyword)
+                    .ok_or_else(|| ActionFailure::CommandFailure(format!("Wait: unknown condition \"{keyword}\".")))?;
+                let timeout = match argument_count {
+                    2 => {
+                        let seconds = action.get_argument_as::<f64>(0)
+                            .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+                            .ok_or_else(|| ActionFailure::CommandFailure(String::from("Wait: the timeout should be a non-negative number of seconds.")))?;
+                        Some(Duration::from_secs_f64(seconds))
+                    },
+                    _ => {
+                        None
+                    }
+                };
+                if condition == WaitCondition::Seconds && timeout.is_none() {
+                    return Err(ActionFailure::CommandFailure(String::from("Wait: Seconds needs a timeout.")));
+                }
+                self.wait(condition, timeout)?;
+            },
+            ("ReadBuffer", 1) if action.arguments[0] == "Ascii" => {
+                lines = self.get_screen().get_read_buffer_lines();
+            },
+            ("Query", 1) => {
+                let screen = self.get_screen();
+                let (row, column) = screen.get_cursor();
+                let answer = match action.arguments[0].as_str() {
+                    "Cursor" => {
+                        format!("{row} {column}")
+                    },
+                    "Cursor1" => {
+                        format!("{} {}", row + 1, column + 1)
+                    },
+                    "Formatted" => {
+                        String::from(if screen.is_formatted() { "formatted" } else { "unformatted" })
+                    },
+                    "ScreenCurSize" => {
+                        format!("rows {} columns {}", screen.get_rows(), screen.get_columns())
+                    },
+                    "ScreenMaxSize" => {
+                        let (rows, columns) = screen.get_maximum_size();
+                        format!("rows {rows} columns {columns}")
+                    },
+                    "Model" => {
+                        format!("IBM-3278-{}-E", screen.get_model().get_number())
+                    },
+                    "CodePage" => {
+                        String::from(screen.get_code_page().get_name())
+                    },
+                    item => {
+                        self.query_connection(item)
+                            .ok_or_else(|| ActionFailure::CommandFailure(format!("Query: unknown item \"{item}\".")))?
+                    }
+                };
+                lines.push(answer);
+            },
+            (name, argument_count) => {
+                return Err(ActionFailure::CommandFailure(format!("{name}: unsupported action with {argument_count} argument(s).")));
+            }
+        }
+        Ok(lines)
+    }
+    fn execute_actions<TOutput>(&mut self, command: impl CommandBuilder<TOutput>, client_message: &str) -> Result<TOutput, Rs3270Error> {
+        // errors only carry the redacted form of the command
+        let client_message = redact_command(client_message);
+        if let Err(message) = command.validate(&self.get_screen().get_code_page()) {
+            return Err(Rs3270Error::Rejected { command: client_message, message });
+        }
+        let actions = match Action::parse_line(&command.get_client_message()) {
+            Some(actions) => {
+                actions
+            },
+            None => {
+                return Err(Rs3270Error::Rejected { message: format!("Unable to parse \"{}\".", client_message), command: client_message });
+            }
+        };
+        if let Err(error) = self.receive_pending() {
+            return Err(Rs3270Error::from_io_error(&client_message, error));
+        }
+        for action in actions.iter() {
+            match self.perform_action(action) {
+                Ok(lines) => {
+                    for line in lines {
+                        if let Err(message) = command.append_client_data_response(line) {
+                            return Err(Rs3270Error::Parse { command: client_message, message });
+                        }
+                    }
+                },
+                Err(ActionFailure::IoError(error)) => {
+                    return Err(Rs3270Error::from_io_error(&client_message, error));
+                },
+                Err(ActionFailure::CommandFailure(message)) => {
+                    return Err(Rs3270Error::Emulator { command: client_message, lines: vec![message] });
+                },
+                Err(ActionFailure::KeyboardLocked) => {
+                    return Err(Rs3270Error::KeyboardLocked { command: client_message });
+                }
+            }
+        }
+        command.build()
+            .map_err(|message| Rs3270Error::Parse { command: client_message, message })
+    }
+}
@@ -0,0 +1,188 @@
+#![allow(dead_code)]
+
+use crate::{proxy::ProxyError, tls::TlsError};
+
+// every failure carries the command text that was sent, such as "Query(Cursor)", with typed text redacted per the RedactionPolicy
+#[derive(Debug)]
+pub enum Rs3270Error {
+    Io {
+        command: String,
+        error: std::io::Error
+    },
+    Timeout {
+        command: String
+    },
+    // the response did not follow the data, status and conclusion layout
+    Protocol {
+        command: String,
+        message: String
+    },
+    // the emulator concluded with "error", keeping every message line it reported
+    Emulator {
+        command: String,
+        lines: Vec<String>
+    },
+    // the emulator succeeded but its data could not be read as the command's output
+    Parse {
+        command: String,
+        message: String
+    },
+    Disconnected {
+        command: String
+    },
+    KeyboardLocked {
+        command: String
+    },
+    Certificate {
+        command: String,
+        message: String
+    },
+    // the proxy to the mainframe could not be reached, refused the credentials or would not connect
+    Proxy {
+        command: String,
+        message: String
+    },
+    // the command was refused before being sent, such as text the code page cannot represent
+    Rejected {
+        command: String,
+        message: String
+    }
+}
+
+impl Rs3270Error {
+    // sorts an I/O failure into the more specific variants where the kind allows
+    pub fn from_io_error(command: &str, error: std::io::Error) -> Self {
+        let command = String::from(command);
+        if let Some(TlsError::Certificate(message)) = TlsError::from_io_error(&error) {
+            return Rs3270Error::Certificate {
+                command,
+                message
+            };
+        }
+        if let Some(proxy_error) = ProxyError::from_io_error(&error) {
+            return Rs3270Error::Proxy {
+                command,
+                message: proxy_error.to_string()
+            };
+        }
+        match error.kind() {
+            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock => {
+                Rs3270Error::Timeout {
+                    command
+                }
+            },
+            std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::BrokenPipe | std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted | std::io::ErrorKind::NotConnected => {
+                Rs3270Error::Disconnected {
+                    command
+                }
+            },
+            _ => {
+                Rs3270Error::Io {
+                    command,
+                    error
+                }
+            }
+        }
+    }
+    // a short name for the variant, such as "timeout", used as the outcome of a command's span
+    pub fn get_kind(&self) -> &'static str {
+        match self {
+            Rs3270Error::Io { .. } => "io",
+            Rs3270Error::Timeout { .. } => "timeout",
+            Rs3270Error::Protocol { .. } => "protocol",
+            Rs3270Error::Emulator { .. } => "emulator",
+            Rs3270Error::Parse { .. } => "parse",
+            Rs3270Error::Disconnected { .. } => "disconnected",
+            Rs3270Error::KeyboardLocked { .. } => "keyboard_locked",
+            Rs3270Error::Certificate { .. } => "certificate",
+            Rs3270Error::Proxy { .. } => "proxy",
+            Rs3270Error::Rejected { .. } => "rejected"
+        }
+    }
+    pub fn get_command(&self) -> &str {
+        match self {
+            Rs3270Error::Io { command, .. } |
+            Rs3270Error::Timeout { command } |
+            Rs3270Error::Protocol { command, .. } |
+            Rs3270Error::Emulator { command, .. } |
+            Rs3270Error::Parse { command, .. } |
+            Rs3270Error::Disconnected { command } |
+            Rs3270Error::KeyboardLocked { command } |
+            Rs3270Error::Certificate { command, .. } |
+            Rs3270Error::Proxy { command, .. } |
+            Rs3270Error::Rejected { command, .. } => {
+                command
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Rs3270Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Rs3270Error::Io { command, error } => {
+                write!(f, "\"{}\" failed with an I/O error: {}", command, error)
+            },
+            Rs3270Error::Timeout { command } => {
+                write!(f, "\"{}\" timed out.", command)
+            },
+            Rs3270Error::Protocol { command, message } => {
+                write!(f, "\"{}\" received a malformed response: {}", command, message)
+            },
+            Rs3270Error::Emulator { command, lines } => {
+                write!(f, "\"{}\" failed: {}", command, lines.join(" "))
+            },
+            Rs3270Error::Parse { command, message } => {
+                write!(f, "\"{}\" returned data that could not be parsed: {}", command, message)
+            },
+            Rs3270Error::Disconnected { command } => {
+                write!(f, "\"{}\" failed because the session is disconnected.", command)
+            },
+            Rs3270Error::KeyboardLocked { command } => {
+                write!(f, "\"{}\" failed because the keyboard is locked.", command)
+            },
+            Rs3270Error::Certificate { command, message } => {
+                write!(f, "\"{}\" failed because the certificate was not accepted: {}", command, message)
+            },
+            Rs3270Error::Proxy { command, message } => {
+                write!(f, "\"{}\" failed at the proxy: {}", command, message)
+            },
+            Rs3270Error::Rejected { command, message } => {
+                write!(f, "\"{}\" was not sent: {}", command, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Rs3270Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Rs3270Error::Io { error, .. } => {
+                Some(error)
+            },
+            _ => {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_io_errors() {
+        let error = Rs3270Error::from_io_error("Enter", std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "closed"));
+        assert!(matches!(error, Rs3270Error::Disconnected { .. }));
+        let error = Rs3270Error::from_io_error("Enter", std::io::Error::new(std::io::ErrorKind::TimedOut, "slow"));
+        assert!(matches!(error, Rs3270Error::Timeout { .. }));
+        let error = Rs3270Error::from_io_error("Enter", TlsError::Certificate(String::from("expired")).into());
+        assert!(matches!(error, Rs3270Error::Certificate { .. }));
+        let error = Rs3270Error::from_io_error("Connect(mainframe:23)", ProxyError::Authentication(String::from("407")).into());
+        assert!(matches!(error, Rs3270Error::Proxy { .. }));
+        let error = Rs3270Error::from_io_error("Enter", std::io::Error::new(std::io::ErrorKind::PermissionDenied, "denied"));
+        assert_eq!("Enter", error.get_command());
+        assert!(std::error::Error::source(&error).is_some());
+    }
+}
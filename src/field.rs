@@ -0,0 +1,228 @@
+#![allow(dead_code)]
+
+use crate::{data_stream::*, screen::*};
+
+// the field attribute as x3270 reports it, with the two high bits that make the byte printable
+pub const READ_BUFFER_FIELD_ATTRIBUTE: u8 = 0xC0;
+
+const FIELD_ATTRIBUTE_INTENSIFIED: u8 = 0x08;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Default,
+    Blue,
+    Red,
+    Pink,
+    Green,
+    Turquoise,
+    Yellow,
+    White,
+    Other(u8)
+}
+
+impl Color {
+    pub fn from_code(code: u8) -> Color {
+        match code {
+            0x00 | 0xF0 => Color::Default,
+            0xF1 => Color::Blue,
+            0xF2 => Color::Red,
+            0xF3 => Color::Pink,
+            0xF4 => Color::Green,
+            0xF5 => Color::Turquoise,
+            0xF6 => Color::Yellow,
+            0xF7 => Color::White,
+            code => Color::Other(code)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Highlighting {
+    Default,
+    Blink,
+    Reverse,
+    Underscore,
+    Other(u8)
+}
+
+impl Highlighting {
+    pub fn from_code(code: u8) -> Highlighting {
+        match code {
+            0x00 | 0xF0 => Highlighting::Default,
+            0xF1 => Highlighting::Blink,
+            0xF2 => Highlighting::Reverse,
+            0xF4 => Highlighting::Underscore,
+            code => Highlighting::Other(code)
+        }
+    }
+}
+
+// a field as ReadBuffer reports it, starting at the position after its attribute and running up to the next one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Field {
+    pub start_address: usize,
+    pub row: usize,
+    pub column: usize,
+    pub length: usize,
+    pub is_protected: bool,
+    pub is_numeric: bool,
+    pub is_intensified: bool,
+    pub is_non_display: bool,
+    pub is_modified: bool,
+    // protected and numeric, so the cursor skips past it as typing fills the field before it
+    pub is_autoskip: bool,
+    pub color: Color,
+    pub highlighting: Highlighting,
+    // nulls read as spaces, and the text of non-display fields is included
+    pub content: String
+}
+
+impl Field {
+    fn new(attribute_pairs: &[(u8, u8)], start_address: usize, columns: usize) -> Self {
+        let get_value = |attribute_type: u8| attribute_pairs.iter()
+            .find(|(pair_type, _)| *pair_type == attribute_type)
+            .map(|(_, value)| *value);
+        let field_attribute = get_value(EXTENDED_ATTRIBUTE_FIELD).unwrap_or(0);
+        let is_protected = field_attribute & FIELD_ATTRIBUTE_PROTECTED != 0;
+        let is_numeric = field_attribute & FIELD_ATTRIBUTE_NUMERIC != 0;
+        Field {
+            start_address,
+            row: start_address / columns,
+            column: start_address % columns,
+            length: 0,
+            is_protected,
+            is_numeric,
+            is_intensified: field_attribute & FIELD_ATTRIBUTE_DISPLAY_MASK == FIELD_ATTRIBUTE_INTENSIFIED,
+            is_non_display: field_attribute & FIELD_ATTRIBUTE_DISPLAY_MASK == FIELD_ATTRIBUTE_NON_DISPLAY,
+            is_modified: field_attribute & FIELD_ATTRIBUTE_MODIFIED != 0,
+            is_autoskip: is_protected && is_numeric,
+            color: Color::from_code(get_value(EXTENDED_ATTRIBUTE_FOREGROUND_COLOR).unwrap_or(0)),
+            highlighting: Highlighting::from_code(get_value(EXTENDED_ATTRIBUTE_HIGHLIGHTING).unwrap_or(0)),
+            content: String::new()
+        }
+    }
+}
+
+// the "type=value" pairs of an SF(...) or SA(...) token, in hexadecimal
+fn parse_attribute_pairs(pairs: &str) -> Result<Vec<(u8, u8)>, String> {
+    pairs.split(',')
+        .map(|pair| {
+            let (attribute_type, value) = pair.split_once('=')
+                .ok_or_else(|| format!("\"{}\" is not a type=value pair.", pair))?;
+            let attribute_type = u8::from_str_radix(attribute_type, 16)
+                .map_err(|_| format!("\"{}\" is not a hexadecimal attribute type.", attribute_type))?;
+            let value = u8::from_str_radix(value, 16)
+                .map_err(|_| format!("\"{}\" is not a hexadecimal attribute value.", value))?;
+            Ok((attribute_type, value))
+        })
+        .collect()
+}
+
+// a character of ReadBuffer(Ascii), which is its UTF-8 bytes in hexadecimal
+fn parse_character(token: &str) -> Result<char, String> {
+    if !token.len().is_multiple_of(2) {
+        return Err(format!("\"{}\" is not a hexadecimal character.", token));
+    }
+    let bytes = (0..token.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&token[index..index + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| format!("\"{}\" is not a hexadecimal character.", token))?;
+    let text = String::from_utf8(bytes)
+        .map_err(|_| format!("\"{}\" is not a UTF-8 character.", token))?;
+    let mut characters = text.chars();
+    match (characters.next(), characters.next()) {
+        (Some('\0'), None) => {
+            Ok(' ')
+        },
+        (Some(character), None) => {
+            Ok(character)
+        },
+        _ => {
+            Err(format!("\"{}\" is not a single character.", token))
+        }
+    }
+}
+
+// reads the rows of ReadBuffer(Ascii) output into fields in buffer order, where an unformatted screen has none
+pub fn parse_read_buffer(lines: &[String]) -> Result<Vec<Field>, String> {
+    let columns = match lines.first() {
+        Some(line) => {
+            line.split_whitespace()
+                .filter(|token| !token.starts_with("SA("))
+                .count()
+        },
+        None => {
+            return Ok(Vec::new());
+        }
+    };
+    if columns == 0 {
+        return Err(String::from("The first row of the buffer is empty."));
+    }
+    let mut fields: Vec<Field> = Vec::new();
+    // the characters before the first field attribute, which belong to the last field once the buffer wraps
+    let mut leading_characters = String::new();
+    let mut address = 0;
+    for token in lines.iter().flat_map(|line| line.split_whitespace()) {
+        if token.starts_with("SA(") {
+            // character attributes do not change the field
+            continue;
+        }
+        if let Some(pairs) = token.strip_prefix("SF(").and_then(|token| token.strip_suffix(')')) {
+            let attribute_pairs = parse_attribute_pairs(pairs)?;
+            fields.push(Field::new(&attribute_pairs, (address + 1) % (columns * lines.len()), columns));
+        }
+        else {
+            let character = parse_character(token)?;
+            match fields.last_mut() {
+                Some(field) => {
+                    field.content.push(character);
+                    field.length += 1;
+                },
+                None => {
+                    leading_characters.push(character);
+                }
+            }
+        }
+        address += 1;
+    }
+    if address != columns * lines.len() {
+        return Err(format!("The buffer has {} positions, which is not {} rows of {} columns.", address, lines.len(), columns));
+    }
+    if let Some(last_field) = fields.last_mut() {
+        last_field.length += leading_characters.chars().count();
+        last_field.content.push_str(&leading_characters);
+    }
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_fields_with_attributes() {
+        let lines = vec![
+            String::from("41 SF(c0=e8,42=f2) 55 53 45 52 3a SF(c0=c1,41=f4) SA(42=f4) 42 4f"),
+            String::from("42 00 SF(c0=f0) 50 46 33 3d 45 58 20")
+        ];
+
+        let fields = parse_read_buffer(&lines).unwrap();
+        assert_eq!(3, fields.len());
+        assert_eq!((2, 0, 2, 5), (fields[0].start_address, fields[0].row, fields[0].column, fields[0].length));
+        assert_eq!("USER:", fields[0].content);
+        assert!(fields[0].is_protected && fields[0].is_intensified && !fields[0].is_autoskip);
+        assert_eq!(Color::Red, fields[0].color);
+        assert_eq!((8, 4), (fields[1].start_address, fields[1].length));
+        assert_eq!("BOB ", fields[1].content);
+        assert!(!fields[1].is_protected && fields[1].is_modified);
+        assert_eq!(Highlighting::Underscore, fields[1].highlighting);
+        // the last field wraps around to the first attribute
+        assert_eq!((13, 1, 3, 8), (fields[2].start_address, fields[2].row, fields[2].column, fields[2].length));
+        assert_eq!("PF3=EX A", fields[2].content);
+        assert!(fields[2].is_autoskip);
+
+        assert!(parse_read_buffer(&[String::from("41 42"), String::from("43")]).is_err());
+        assert!(parse_read_buffer(&[String::from("SF(c0) 41")]).is_err());
+    }
+}
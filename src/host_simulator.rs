@@ -0,0 +1,575 @@
+#![allow(dead_code)]
+
+use std::{io::{Read, Write}, net::{TcpListener, TcpStream}, sync::{atomic::{AtomicBool, Ordering}, Arc, Mutex}};
+use crate::{data_stream::*, screen::*, telnet::*, tn3270e::*};
+
+// the types a script is written with, for crates that enable the host-simulator feature
+pub use crate::{aid::Aid, code_page::CodePage};
+
+// a scripted TN3270 host on localhost, for integration tests of the spawners and executors without a mainframe
+
+const DEFAULT_LU_NAME: &str = "SIMLU001";
+const TERMINAL_TYPE_IS: u8 = 0;
+const TERMINAL_TYPE_SEND: u8 = 1;
+const TN3270E_HEADER_LENGTH: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulatedField {
+    // the position of the field attribute, with the text starting just after it
+    pub row: usize,
+    pub column: usize,
+    pub is_protected: bool,
+    pub text: String
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulatedTransition {
+    pub aid: Aid,
+    // the text the field at this position must have been sent with, such as a password
+    pub required_input: Option<(usize, usize, String)>,
+    pub next_screen_name: String
+}
+
+// a formatted screen and the AID keys that lead away from it, where any other key shows it again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimulatedScreen {
+    pub name: String,
+    pub fields: Vec<SimulatedField>,
+    pub cursor_row: usize,
+    pub cursor_column: usize,
+    pub transitions: Vec<SimulatedTransition>
+}
+
+impl SimulatedScreen {
+    pub fn new(name: &str) -> Self {
+        SimulatedScreen {
+            name: String::from(name),
+            fields: Vec::new(),
+            cursor_row: 0,
+            cursor_column: 0,
+            transitions: Vec::new()
+        }
+    }
+    pub fn add_field(&mut self, row: usize, column: usize, is_protected: bool, text: &str) {
+        self.fields.push(SimulatedField {
+            row,
+            column,
+            is_protected,
+            text: String::from(text)
+        });
+    }
+    pub fn set_cursor(&mut self, row: usize, column: usize) {
+        self.cursor_row = row;
+        self.cursor_column = column;
+    }
+    pub fn add_transition(&mut self, aid: Aid, next_screen_name: &str) {
+        self.transitions.push(SimulatedTransition {
+            aid,
+            required_input: None,
+            next_screen_name: String::from(next_screen_name)
+        });
+    }
+    pub fn add_transition_with_input(&mut self, aid: Aid, row: usize, column: usize, text: &str, next_screen_name: &str) {
+        self.transitions.push(SimulatedTransition {
+            aid,
+            required_input: Some((row, column, String::from(text))),
+            next_screen_name: String::from(next_screen_name)
+        });
+    }
+    // an erase/write of the whole screen that also unlocks the keyboard
+    fn get_record(&self, columns: usize, is_alternate_size: bool, code_page: CodePage) -> Vec<u8> {
+        let command = if is_alternate_size { COMMAND_ERASE_WRITE_ALTERNATE } else { COMMAND_ERASE_WRITE };
+        let mut record = vec![command, WCC_KEYBOARD_RESTORE | WCC_RESET_MDT];
+        for field in self.fields.iter() {
+            record.push(ORDER_SET_BUFFER_ADDRESS);
+            record.extend(encode_address(field.row * columns + field.column));
+            record.push(ORDER_START_FIELD);
+            record.push(if field.is_protected { FIELD_ATTRIBUTE_PROTECTED } else { 0 });
+            let text = code_page.encode(&field.text)
+                .expect("HostSimulator::start should have checked that every field can be encoded");
+            record.extend(text);
+        }
+        record.push(ORDER_SET_BUFFER_ADDRESS);
+        record.extend(encode_address(self.cursor_row * columns + self.cursor_column));
+        record.push(ORDER_INSERT_CURSOR);
+        record
+    }
+    fn get_next_screen_name(&self, received_aid: &ReceivedAid, columns: usize) -> Option<&str> {
+        self.transitions.iter()
+            .find(|transition| {
+                transition.aid.get_code() == received_aid.aid && match &transition.required_input {
+                    Some((row, column, text)) => {
+                        received_aid.get_field_text(row * columns + column + 1) == Some(text.as_str())
+                    },
+                    None => {
+                        true
+                    }
+                }
+            })
+            .map(|transition| transition.next_screen_name.as_str())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostScript {
+    // the first screen is shown once the session is negotiated
+    pub screens: Vec<SimulatedScreen>,
+    // the width of the terminal's default size, or of its alternate size when is_alternate_size is set
+    pub columns: usize,
+    // writes each screen with Erase/Write Alternate, so that the terminal switches to its alternate size
+    pub is_alternate_size: bool,
+    pub code_page: CodePage,
+    // plain TN3270 is negotiated when this is false or the terminal refuses TN3270E
+    pub is_tn3270e_offered: bool
+}
+
+impl HostScript {
+    pub fn new(screens: Vec<SimulatedScreen>) -> Self {
+        HostScript {
+            screens,
+            columns: 80,
+            is_alternate_size: false,
+            code_page: CodePage::Cp037,
+            is_tn3270e_offered: true
+        }
+    }
+    fn get_screen(&self, name: &str) -> Option<&SimulatedScreen> {
+        self.screens.iter()
+            .find(|screen| screen.name == name)
+    }
+    // the first problem that would otherwise only surface once a session reached it
+    fn validate(&self) -> Result<(), String> {
+        if self.screens.is_empty() {
+            return Err(String::from("The host script has no screens."));
+        }
+        for screen in self.screens.iter() {
+            for field in screen.fields.iter() {
+                if let Err(character) = self.code_page.encode(&field.text) {
+                    return Err(format!("'{}' on screen \"{}\" cannot be encoded in the code page.", character, screen.name));
+                }
+            }
+            for transition in screen.transitions.iter() {
+                if self.get_screen(&transition.next_screen_name).is_none() {
+                    return Err(format!("Screen \"{}\" leads to \"{}\", which the host script does not have.", screen.name, transition.next_screen_name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// an AID key as the host received it, with the text of each modified field
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReceivedAid {
+    pub screen_name: String,
+    pub aid: u8,
+    pub cursor_address: Option<usize>,
+    // the address of the first character of each field, which follows its attribute
+    pub fields: Vec<(usize, String)>
+}
+
+impl ReceivedAid {
+    fn parse(screen_name: &str, record: &[u8], code_page: CodePage) -> Option<Self> {
+        let aid = *record.first()?;
+        let cursor_address = record.get(1..3)
+            .map(|address| decode_address(address[0], address[1]));
+        let mut fields: Vec<(usize, Vec<u8>)> = Vec::new();
+        let mut index = 3;
+        while index < record.len() {
+            if record[index] == ORDER_SET_BUFFER_ADDRESS && index + 2 < record.len() {
+                fields.push((decode_address(record[index + 1], record[index + 2]), Vec::new()));
+                index += 3;
+            }
+            else {
+                if let Some((_, text)) = fields.last_mut() {
+                    text.push(record[index]);
+                }
+                index += 1;
+            }
+        }
+        Some(ReceivedAid {
+            screen_name: String::from(screen_name),
+            aid,
+            cursor_address,
+            fields: fields.into_iter()
+                .map(|(address, text)| (address, code_page.decode(&text)))
+                .collect()
+        })
+    }
+    pub fn get_field_text(&self, address: usize) -> Option<&str> {
+        self.fields.iter()
+            .find(|(field_address, _)| *field_address == address)
+            .map(|(_, text)| text.as_str())
+    }
+}
+
+enum HostEvent {
+    Negotiation(u8, u8),
+    Subnegotiation(Vec<u8>),
+    Record(Vec<u8>)
+}
+
+// the host side of a telnet session, which only needs to tell negotiation apart from records
+struct HostConnection {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    is_tn3270e_mode: bool,
+    sequence_number: u16
+}
+
+impl HostConnection {
+    fn read_byte(&mut self) -> std::io::Result<u8> {
+        let mut byte = [0u8; 1];
+        self.stream.read_exact(&mut byte)?;
+        Ok(byte[0])
+    }
+    fn read_event(&mut self) -> std::io::Result<HostEvent> {
+        loop {
+            let byte = self.read_byte()?;
+            if byte != IAC {
+                self.buffer.push(byte);
+                continue;
+            }
+            match self.read_byte()? {
+                IAC => {
+                    self.buffer.push(IAC);
+                },
+                EOR => {
+                    return Ok(HostEvent::Record(std::mem::take(&mut self.buffer)));
+                },
+                verb @ (DO | DONT | WILL | WONT) => {
+                    let option = self.read_byte()?;
+                    return Ok(HostEvent::Negotiation(verb, option));
+                },
+                SB => {
+                    let mut subnegotiation = Vec::new();
+                    loop {
+                        let byte = self.read_byte()?;
+                        if byte == IAC {
+                            let next_byte = self.read_byte()?;
+                            if next_byte == SE {
+                                break;
+                            }
+                            subnegotiation.push(next_byte);
+                        }
+                        else {
+                            subnegotiation.push(byte);
+                        }
+                    }
+                    return Ok(HostEvent::Subnegotiation(subnegotiation));
+                },
+                _ => {
+                    // NOP and the like
+                }
+            }
+        }
+    }
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.stream.write_all(bytes)?;
+        self.stream.flush()
+    }
+    fn write_subnegotiation(&mut self, body: &[u8]) -> std::io::Result<()> {
+        let mut bytes = vec![IAC, SB];
+        bytes.extend(body);
+        bytes.extend([IAC, SE]);
+        self.write_bytes(&bytes)
+    }
+    fn write_record(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let mut record = Vec::new();
+        if self.is_tn3270e_mode {
+            record.extend(Tn3270eHeader::new(DATA_TYPE_3270_DATA, RESPONSE_FLAG_NO_RESPONSE, self.sequence_number).to_bytes());
+            self.sequence_number = self.sequence_number.wrapping_add(1);
+        }
+        record.extend(data);
+        let mut bytes = Vec::with_capacity(record.len() + 2);
+        for byte in record {
+            if byte == IAC {
+                bytes.push(IAC);
+            }
+            bytes.push(byte);
+        }
+        bytes.extend([IAC, EOR]);
+        self.write_bytes(&bytes)
+    }
+    // the next 3270 data record, answering nothing else since negotiation is over
+    fn read_record(&mut self) -> std::io::Result<Vec<u8>> {
+        loop {
+            if let HostEvent::Record(record) = self.read_event()? {
+                if !self.is_tn3270e_mode {
+                    return Ok(record);
+                }
+                if record.len() > TN3270E_HEADER_LENGTH && record[0] == DATA_TYPE_3270_DATA {
+                    return Ok(record[TN3270E_HEADER_LENGTH..].to_vec());
+                }
+                // responses and other data types carry no AID
+            }
+        }
+    }
+    fn negotiate(&mut self, is_tn3270e_offered: bool) -> std::io::Result<()> {
+        if is_tn3270e_offered {
+            self.write_bytes(&[IAC, DO, OPTION_TN3270E])?;
+            loop {
+                match self.read_event()? {
+                    HostEvent::Negotiation(WILL, OPTION_TN3270E) => {
+                        return self.negotiate_tn3270e();
+                    },
+                    HostEvent::Negotiation(WONT, OPTION_TN3270E) => {
+                        break;
+                    },
+                    _ => {
+                        // nothing else is expected before the answer
+                    }
+                }
+            }
+        }
+        self.negotiate_tn3270()
+    }
+    fn negotiate_tn3270e(&mut self) -> std::io::Result<()> {
+        self.write_subnegotiation(&[OPTION_TN3270E, SEND, DEVICE_TYPE])?;
+        loop {
+            let subnegotiation = match self.read_event()? {
+                HostEvent::Subnegotiation(subnegotiation) if subnegotiation.first() == Some(&OPTION_TN3270E) => {
+                    subnegotiation
+                },
+                _ => {
+                    continue;
+                }
+            };
+            match subnegotiation.get(1..3) {
+                Some([DEVICE_TYPE, REQUEST]) => {
+                    let body = &subnegotiation[3..];
+                    let (device_type, lu_name) = match body.iter().position(|byte| *byte == CONNECT) {
+                        Some(index) => {
+                            (&body[..index], &body[index + 1..])
+                        },
+                        None => {
+                            (body, DEFAULT_LU_NAME.as_bytes())
+                        }
+                    };
+                    let mut reply = vec![OPTION_TN3270E, DEVICE_TYPE, IS];
+                    reply.extend(device_type);
+                    reply.push(CONNECT);
+                    reply.extend(lu_name);
+                    self.write_subnegotiation(&reply)?;
+                },
+                Some([FUNCTIONS, REQUEST]) => {
+                    // only responses are supported, and they are never asked for
+                    let responses = Function::Responses.get_code();
+                    let functions = subnegotiation[3..].iter()
+                        .filter(|code| **code == responses)
+                        .copied()
+                        .collect::<Vec<u8>>();
+                    if functions == subnegotiation[3..] {
+                        let mut reply = vec![OPTION_TN3270E, FUNCTIONS, IS];
+                        reply.extend(functions);
+                        self.write_subnegotiation(&reply)?;
+                        break;
+                    }
+                    let mut reply = vec![OPTION_TN3270E, FUNCTIONS, REQUEST];
+                    reply.extend(functions);
+                    self.write_subnegotiation(&reply)?;
+                },
+                Some([FUNCTIONS, IS]) => {
+                    break;
+                },
+                _ => {
+                    // nothing else is answered
+                }
+            }
+        }
+        self.is_tn3270e_mode = true;
+        Ok(())
+    }
+    fn negotiate_tn3270(&mut self) -> std::io::Result<()> {
+        self.write_bytes(&[IAC, DO, OPTION_TERMINAL_TYPE])?;
+        loop {
+            match self.read_event()? {
+                HostEvent::Negotiation(WILL, OPTION_TERMINAL_TYPE) => {
+                    self.write_subnegotiation(&[OPTION_TERMINAL_TYPE, TERMINAL_TYPE_SEND])?;
+                },
+                HostEvent::Subnegotiation(subnegotiation) if subnegotiation.starts_with(&[OPTION_TERMINAL_TYPE, TERMINAL_TYPE_IS]) => {
+                    break;
+                },
+                _ => {
+                    // the terminal type is all that is waited for
+                }
+            }
+        }
+        // the terminal's answers are not needed before the first screen
+        self.write_bytes(&[
+            IAC, DO, OPTION_EOR, IAC, WILL, OPTION_EOR,
+            IAC, DO, OPTION_BINARY, IAC, WILL, OPTION_BINARY
+        ])
+    }
+}
+
+pub struct HostSimulator {
+    address: String,
+    received_aids: Arc<Mutex<Vec<ReceivedAid>>>,
+    is_stopped: Arc<AtomicBool>
+}
+
+impl HostSimulator {
+    // listens on an unused localhost port, serving every connection the script from its first screen
+    pub fn start(host_script: HostScript) -> std::io::Result<Self> {
+        host_script.validate()
+            .map_err(|message| std::io::Error::new(std::io::ErrorKind::InvalidInput, message))?;
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?.to_string();
+        let received_aids = Arc::new(Mutex::new(Vec::new()));
+        let is_stopped = Arc::new(AtomicBool::new(false));
+        let host_script = Arc::new(host_script);
+        {
+            let received_aids = received_aids.clone();
+            let is_stopped = is_stopped.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if is_stopped.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    let stream = match stream {
+                        Ok(stream) => {
+                            stream
+                        },
+                        Err(error) => {
+                            tracing::warn!(%error, "the simulated host could not accept a connection");
+                            continue;
+                        }
+                    };
+                    let host_script = host_script.clone();
+                    let received_aids = received_aids.clone();
+                    std::thread::spawn(move || {
+                        if let Err(error) = Self::serve(stream, &host_script, &received_aids) {
+                            tracing::debug!(%error, "the simulated session ended");
+                        }
+                    });
+                }
+            });
+        }
+        Ok(HostSimulator {
+            address,
+            received_aids,
+            is_stopped
+        })
+    }
+    fn serve(stream: TcpStream, host_script: &HostScript, received_aids: &Mutex<Vec<ReceivedAid>>) -> std::io::Result<()> {
+        let mut connection = HostConnection {
+            stream,
+            buffer: Vec::new(),
+            is_tn3270e_mode: false,
+            sequence_number: 0
+        };
+        connection.negotiate(host_script.is_tn3270e_offered)?;
+        let mut screen = &host_script.screens[0];
+        loop {
+            connection.write_record(&screen.get_record(host_script.columns, host_script.is_alternate_size, host_script.code_page))?;
+            let record = connection.read_record()?;
+            let received_aid = match ReceivedAid::parse(&screen.name, &record, host_script.code_page) {
+                Some(received_aid) => {
+                    received_aid
+                },
+                None => {
+                    continue;
+                }
+            };
+            if let Some(next_screen_name) = screen.get_next_screen_name(&received_aid, host_script.columns) {
+                screen = host_script.get_screen(next_screen_name)
+                    .expect("HostSimulator::start should have checked that every transition leads to a screen");
+            }
+            received_aids.lock()
+                .expect("The received AIDs should not be poisoned.")
+                .push(received_aid);
+        }
+    }
+    // such as "127.0.0.1:41234", for the mainframe address of a TerminalConfiguration
+    pub fn get_address(&self) -> &str {
+        &self.address
+    }
+    // every AID key received over every session, oldest first
+    pub fn get_received_aids(&self) -> Vec<ReceivedAid> {
+        self.received_aids
+            .lock()
+            .expect("The received AIDs should not be poisoned.")
+            .clone()
+    }
+}
+
+impl Drop for HostSimulator {
+    fn drop(&mut self) {
+        self.is_stopped.store(true, Ordering::Relaxed);
+        // wakes the listener so that it sees the flag
+        let _ = TcpStream::connect(&self.address);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{client_interface::*, native_client::NativeCommandExecutor};
+
+    use super::*;
+
+    fn get_login_script() -> HostScript {
+        let mut login_screen = SimulatedScreen::new("login");
+        login_screen.add_field(0, 0, true, "USER:");
+        login_screen.add_field(0, 6, false, "");
+        login_screen.add_field(0, 16, true, "");
+        login_screen.set_cursor(0, 7);
+        login_screen.add_transition_with_input(Aid::Enter, 0, 6, "ALICE", "menu");
+        let mut menu_screen = SimulatedScreen::new("menu");
+        menu_screen.add_field(0, 0, true, "WELCOME ALICE");
+        menu_screen.add_transition(Aid::Clear, "login");
+        HostScript::new(vec![login_screen, menu_screen])
+    }
+
+    fn log_in(host_simulator: &HostSimulator) {
+        let terminal_configuration = TerminalConfiguration::new(host_simulator.get_address(), "");
+        let mut command_executor = NativeCommandExecutor::connect(&terminal_configuration).unwrap();
+
+        // the first screen follows negotiation
+        command_executor.execute(WaitForCurrentFieldCommand::new()).unwrap();
+        assert_eq!(" USER:", command_executor.execute(GetTextCommand::new(0, 0, 6)).unwrap());
+        assert_eq!((0, 7), command_executor.execute(GetCursorCommand::new()).unwrap());
+        command_executor.execute(SetTextCommand::new(String::from("BOB"))).unwrap();
+        command_executor.execute(SendEnterKeyCommand::new()).unwrap();
+        command_executor.execute(WaitForUnlockCommand::new()).unwrap();
+        // the wrong user is shown the login screen again
+        assert_eq!(" USER:", command_executor.execute(GetTextCommand::new(0, 0, 6)).unwrap());
+        command_executor.execute(SetTextCommand::new(String::from("ALICE"))).unwrap();
+        command_executor.execute(SendEnterKeyCommand::new()).unwrap();
+        command_executor.execute(WaitForUnlockCommand::new()).unwrap();
+        assert_eq!(" WELCOME ALICE", command_executor.execute(GetTextCommand::new(0, 0, 14)).unwrap());
+        command_executor.disconnect();
+    }
+
+    #[test]
+    fn serve_screens_over_tn3270e() {
+        let host_simulator = HostSimulator::start(get_login_script()).unwrap();
+
+        log_in(&host_simulator);
+        let received_aids = host_simulator.get_received_aids();
+        assert_eq!(2, received_aids.len());
+        assert_eq!(Some("BOB"), received_aids[0].get_field_text(7));
+        assert_eq!("login", received_aids[1].screen_name);
+    }
+
+    #[test]
+    fn serve_screens_over_tn3270() {
+        let mut host_script = get_login_script();
+        host_script.is_tn3270e_offered = false;
+        let host_simulator = HostSimulator::start(host_script).unwrap();
+
+        log_in(&host_simulator);
+        assert_eq!(AID_ENTER, host_simulator.get_received_aids()[1].aid);
+    }
+
+    #[test]
+    fn refuse_scripts_that_cannot_be_served() {
+        assert_eq!(std::io::ErrorKind::InvalidInput, HostSimulator::start(HostScript::new(Vec::new())).err().unwrap().kind());
+        let mut screen = SimulatedScreen::new("login");
+        screen.add_transition(Aid::Enter, "welcome");
+        assert_eq!(std::io::ErrorKind::InvalidInput, HostSimulator::start(HostScript::new(vec![screen])).err().unwrap().kind());
+        let mut screen = SimulatedScreen::new("price");
+        screen.add_field(0, 0, true, "costs 5€");
+        assert_eq!(std::io::ErrorKind::InvalidInput, HostSimulator::start(HostScript::new(vec![screen])).err().unwrap().kind());
+    }
+}
@@ -1,3 +1,25 @@
+mod action;
+mod aid;
 mod client_interface;
+mod client_status;
+mod code_page;
+mod command_batch;
+mod data_stream;
+mod emulated_client;
+mod emulator;
+mod error;
+mod field;
+#[cfg(any(test, feature = "host-simulator"))]
+pub mod host_simulator;
+mod logging;
 mod mainframe_provider;
-//mod processor_logic;
\ No newline at end of file
+mod native_client;
+//mod processor_logic;
+mod proxy;
+mod screen;
+mod script_codec;
+mod session_query;
+mod telnet;
+mod tls;
+mod tn3270e;
+mod transcript;
@@ -0,0 +1,118 @@
+#![allow(dead_code)]
+
+use std::{sync::atomic::{AtomicU8, Ordering}, time::Instant};
+use tracing::Span;
+use crate::{action::Action, error::Rs3270Error};
+
+const REDACTED: &str = "<redacted>";
+
+// the actions whose arguments are text typed into the screen, such as passwords
+const TYPED_TEXT_ACTIONS: [&str; 1] = ["String"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedactionPolicy {
+    // typed text never reaches logs or error messages, which is the default
+    TypedText,
+    // commands are logged as they were sent, which is only fit for test systems
+    Nothing
+}
+
+static REDACTION_POLICY: AtomicU8 = AtomicU8::new(0);
+
+pub fn set_redaction_policy(redaction_policy: RedactionPolicy) {
+    let value = match redaction_policy {
+        RedactionPolicy::TypedText => 0,
+        RedactionPolicy::Nothing => 1
+    };
+    REDACTION_POLICY.store(value, Ordering::Relaxed);
+}
+
+pub fn get_redaction_policy() -> RedactionPolicy {
+    match REDACTION_POLICY.load(Ordering::Relaxed) {
+        1 => RedactionPolicy::Nothing,
+        _ => RedactionPolicy::TypedText
+    }
+}
+
+// the command as it may appear in logs and in the errors returned to callers
+pub fn redact_command(command: &str) -> String {
+    redact_command_with_policy(command, get_redaction_policy())
+}
+
+fn redact_command_with_policy(command: &str, redaction_policy: RedactionPolicy) -> String {
+    if redaction_policy == RedactionPolicy::Nothing {
+        return String::from(command);
+    }
+    match Action::parse_line(command) {
+        Some(actions) => {
+            actions.iter()
+                .map(|action| {
+                    if action.arguments.is_empty() {
+                        action.name.clone()
+                    }
+                    else if TYPED_TEXT_ACTIONS.contains(&action.name.as_str()) {
+                        format!("{}({})", action.name, REDACTED)
+                    }
+                    else {
+                        format!("{}({})", action.name, action.arguments.join(","))
+                    }
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        },
+        None => {
+            // a command that cannot be parsed cannot be shown to be free of typed text
+            String::from(REDACTED)
+        }
+    }
+}
+
+// a span for one command, named by its actions, which records the outcome and duration once it completes
+pub(crate) fn start_command_span(command: &str) -> Span {
+    let action = Action::parse_line(command)
+        .map(|actions| {
+            actions.iter()
+                .map(|action| action.name.as_str())
+                .collect::<Vec<&str>>()
+                .join(" ")
+        })
+        .unwrap_or_default();
+    tracing::debug_span!("command",
+        action = %action,
+        command = %redact_command(command),
+        outcome = tracing::field::Empty,
+        duration_ms = tracing::field::Empty
+    )
+}
+
+pub(crate) fn record_command_outcome<T>(span: &Span, start_time: Instant, execution_result: &Result<T, Rs3270Error>) {
+    let duration_ms = start_time.elapsed().as_secs_f64() * 1000.0;
+    span.record("duration_ms", duration_ms);
+    match execution_result {
+        Ok(_) => {
+            span.record("outcome", "ok");
+            tracing::debug!(parent: span, duration_ms, "command succeeded");
+        },
+        Err(error) => {
+            span.record("outcome", error.get_kind());
+            tracing::debug!(parent: span, duration_ms, %error, "command failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact_typed_text_only() {
+        assert_eq!("MoveCursor(1,2) String(<redacted>) Enter", redact_command_with_policy("MoveCursor(1,2) String(\"hunter2\") Enter", RedactionPolicy::TypedText));
+        assert_eq!("Ascii(0,0,80)", redact_command_with_policy("Ascii(0,0,80)", RedactionPolicy::TypedText));
+        assert_eq!("<redacted>", redact_command_with_policy("String(\"hunter2", RedactionPolicy::TypedText));
+    }
+
+    #[test]
+    fn keep_everything_without_redaction() {
+        assert_eq!("String(\"hunter2\")", redact_command_with_policy("String(\"hunter2\")", RedactionPolicy::Nothing));
+    }
+}
@@ -1,37 +1,196 @@
 #![allow(dead_code)]
 
-use std::cell::RefCell;
-use crate::client_interface::*;
+use std::{cell::RefCell, time::{Duration, Instant}};
+use crate::{aid::Aid, client_interface::*, client_status::ClientStatus, code_page::CodePage, command_batch::*, error::Rs3270Error, field::Field, session_query::*};
 
 pub trait ImmutableMainframeProvider {
     fn get_screen_text(&self) -> Vec<String>;
-    fn get_text_at_location(&self, x: u8, y: u8, length: u8) -> String;
-    fn get_fields_count(&self) -> u8;
-    fn get_field_vector(&self) -> Option<(u8, u8, u8)>;
+    fn get_text_at_location(&self, x: usize, y: usize, length: usize) -> String;
+    fn get_fields_count(&self) -> usize;
+    fn get_field_vector(&self) -> Option<(usize, usize, usize)>;
+    // every field on the screen in buffer order, read without moving the cursor
+    fn get_fields(&self) -> Result<Vec<Field>, Rs3270Error>;
+    // the status reported with the most recent command, such as whether the keyboard is locked or the session has dropped
+    fn get_status(&self) -> Option<ClientStatus>;
+    // waits for the condition, giving up after the timeout when there is one or after the executor's read timeout otherwise
+    fn wait(&self, condition: WaitCondition, timeout: Option<Duration>) -> Result<WaitOutcome, Rs3270Error>;
+    // the session, as the emulator reports it
+    fn get_host(&self) -> Result<Option<HostInfo>, Rs3270Error>;
+    fn get_lu_name(&self) -> Result<Option<String>, Rs3270Error>;
+    fn get_model(&self) -> Result<TerminalModel, Rs3270Error>;
+    fn get_screen_size(&self) -> Result<ScreenSize, Rs3270Error>;
+    fn get_maximum_screen_size(&self) -> Result<ScreenSize, Rs3270Error>;
+    fn is_formatted(&self) -> Result<bool, Rs3270Error>;
+    fn get_code_page(&self) -> Result<CodePage, Rs3270Error>;
+    fn get_tls_state(&self) -> Result<TlsState, Rs3270Error>;
+    fn get_session_state(&self) -> Result<SessionState, Rs3270Error>;
+    // the cursor as (row, column), counting from 1
+    fn get_cursor_from_one(&self) -> Result<(usize, usize), Rs3270Error>;
 }
 
 pub trait MutableMainframeProvider: ImmutableMainframeProvider {
-    fn set_text_at_location(&self, x: u8, y: u8, text: &str) -> ();
-    fn move_to_field_index(&self, index: u8) -> ();
+    fn set_text_at_location(&self, x: usize, y: usize, text: &str);
+    fn move_to_field_index(&self, index: usize);
+    // presses an AID key and waits for the host to answer by unlocking the keyboard, failing with Rs3270Error::Timeout when it does not
+    // with a settle time, it then also waits until the host has written nothing for that long
+    fn send_aid(&self, aid: Aid) -> Result<Option<ClientStatus>, Rs3270Error>;
+    // the keyboard, where the terminal refuses keys that do not apply, such as typing keys in a protected field
+    fn press_program_function_key(&self, number: u8) -> Result<(), Rs3270Error>;
+    fn press_program_attention_key(&self, number: u8) -> Result<(), Rs3270Error>;
+    fn press_clear_key(&self) -> Result<(), Rs3270Error>;
+    fn press_attention_key(&self) -> Result<(), Rs3270Error>;
+    fn press_system_request_key(&self) -> Result<(), Rs3270Error>;
+    fn reset_keyboard(&self) -> Result<(), Rs3270Error>;
+    fn erase_to_end_of_field(&self) -> Result<(), Rs3270Error>;
+    fn erase_all_input(&self) -> Result<(), Rs3270Error>;
+    fn erase_previous_character(&self) -> Result<(), Rs3270Error>;
+    fn delete_character(&self) -> Result<(), Rs3270Error>;
+    fn enable_insert_mode(&self) -> Result<(), Rs3270Error>;
+    fn toggle_insert_mode(&self) -> Result<(), Rs3270Error>;
+    fn move_cursor_left(&self) -> Result<(), Rs3270Error>;
+    fn move_cursor_right(&self) -> Result<(), Rs3270Error>;
+    fn move_cursor_up(&self) -> Result<(), Rs3270Error>;
+    fn move_cursor_down(&self) -> Result<(), Rs3270Error>;
+    fn move_cursor_left_twice(&self) -> Result<(), Rs3270Error>;
+    fn move_cursor_right_twice(&self) -> Result<(), Rs3270Error>;
+    fn move_cursor_to_next_line(&self) -> Result<(), Rs3270Error>;
+    fn send_duplicate(&self) -> Result<(), Rs3270Error>;
+    fn send_field_mark(&self) -> Result<(), Rs3270Error>;
+    fn select_at_cursor(&self) -> Result<(), Rs3270Error>;
 }
 
+// the margin a timed wait's response is given past its own timeout before the executor's read timeout would end it
+const WAIT_RESPONSE_MARGIN: Duration = Duration::from_secs(5);
+// how often a wait for the connection queries the connection state
+const CONNECTION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 pub struct MainframeProvider<T: CommandExecutor> {
-    client_interface: RefCell<T>
+    client_interface: RefCell<T>,
+    settle_time: Option<Duration>
 }
 
 impl<T: CommandExecutor> MainframeProvider<T> {
     pub fn new(command_executor: T) -> Self {
         MainframeProvider {
-            client_interface: RefCell::new(command_executor)
+            client_interface: RefCell::new(command_executor),
+            settle_time: None
+        }
+    }
+    // how long the host must stay quiet after unlocking the keyboard before send_aid returns, where None returns on the unlock
+    pub fn set_settle_time(&mut self, settle_time: Option<Duration>) {
+        self.settle_time = settle_time;
+    }
+    // x3270 has no Wait keyword for a connection in any mode, so the connection state is polled until it is connected, up to the timeout or else the read timeout
+    fn wait_for_connection(&self, timeout: Option<Duration>) -> Result<WaitOutcome, Rs3270Error> {
+        let timeout = timeout.or(self.client_interface.borrow().get_timeouts().read);
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            let session_state = match self.get_session_state() {
+                Ok(session_state) => {
+                    session_state
+                },
+                Err(error) => {
+                    return WaitOutcome::from_result(Err(error));
+                }
+            };
+            if session_state.is_connected() {
+                return Ok(WaitOutcome::ConditionMet);
+            }
+            let remaining_time = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+            if remaining_time.is_some_and(|remaining_time| remaining_time.is_zero()) {
+                return Ok(WaitOutcome::TimedOut);
+            }
+            std::thread::sleep(remaining_time.map_or(CONNECTION_POLL_INTERVAL, |remaining_time| remaining_time.min(CONNECTION_POLL_INTERVAL)));
         }
     }
 }
 
 impl<T: CommandExecutor> ImmutableMainframeProvider for MainframeProvider<T> {
+    fn get_status(&self) -> Option<ClientStatus> {
+        self.client_interface
+            .borrow()
+            .get_status()
+            .cloned()
+    }
+    fn wait(&self, condition: WaitCondition, timeout: Option<Duration>) -> Result<WaitOutcome, Rs3270Error> {
+        if condition == WaitCondition::Connected {
+            return self.wait_for_connection(timeout);
+        }
+        let mut client_interface = self.client_interface.borrow_mut();
+        let wait_result = match timeout {
+            Some(timeout) => {
+                let mut timeouts = client_interface.get_timeouts().clone();
+                timeouts.read = timeouts.read.map(|read| read.max(timeout + WAIT_RESPONSE_MARGIN));
+                client_interface.execute_with_timeouts(WaitWithTimeoutCommand::new(condition, timeout), &timeouts)
+            },
+            None => {
+                client_interface.execute(WaitCommand::new(condition))
+            }
+        };
+        WaitOutcome::from_result(wait_result)
+    }
+    fn get_fields(&self) -> Result<Vec<Field>, Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(ReadBufferCommand::new())
+    }
+    fn get_host(&self) -> Result<Option<HostInfo>, Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(QueryHostCommand::new())
+    }
+    fn get_lu_name(&self) -> Result<Option<String>, Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(QueryLuNameCommand::new())
+    }
+    fn get_model(&self) -> Result<TerminalModel, Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(QueryModelCommand::new())
+    }
+    fn get_screen_size(&self) -> Result<ScreenSize, Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(QueryScreenSizeCommand::new())
+    }
+    fn get_maximum_screen_size(&self) -> Result<ScreenSize, Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(QueryMaximumScreenSizeCommand::new())
+    }
+    fn is_formatted(&self) -> Result<bool, Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(QueryFormattedCommand::new())
+    }
+    fn get_code_page(&self) -> Result<CodePage, Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(QueryCodePageCommand::new())
+    }
+    fn get_tls_state(&self) -> Result<TlsState, Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(QueryTlsCommand::new())
+    }
+    fn get_session_state(&self) -> Result<SessionState, Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(QueryConnectionStateCommand::new())
+    }
+    fn get_cursor_from_one(&self) -> Result<(usize, usize), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(GetCursorFromOneCommand::new())
+    }
     fn get_screen_text(&self) -> Vec<String> {
+        // the host can switch between the default and alternate sizes, so the size is read before each screen
+        let screen_size = self.get_screen_size()
+            .expect("The screen size should be returned from the client interface.");
         let lines = self.client_interface
             .borrow_mut()
-            .execute(GetTextRangeCommand::new(0, 0, 80, 24))
+            .execute(GetTextRangeCommand::new(0, 0, screen_size.columns, screen_size.rows))
             .expect("The lines should be returned from the client interface");
         lines
             .into_iter()
@@ -41,24 +200,23 @@ impl<T: CommandExecutor> ImmutableMainframeProvider for MainframeProvider<T> {
             })
             .collect()
     }
-    fn get_text_at_location(&self, x: u8, y: u8, length: u8) -> String {
+    fn get_text_at_location(&self, x: usize, y: usize, length: usize) -> String {
         let line = self.client_interface
             .borrow_mut()
             .execute(GetTextCommand::new(y, x, length))
             .expect("The line should have been returned from the client interface.");
         line
     }
-    fn get_fields_count(&self) -> u8 {
-        // get the current cursor position so that it can be restored at the end
-        let current_cursor_position = self.client_interface
-            .borrow_mut()
-            .execute(GetCursorCommand::new())
+    fn get_fields_count(&self) -> usize {
+        // get the current cursor position so that it can be restored at the end, then move to the first field
+        let mut batch = CommandBatch::new();
+        let current_cursor_position = batch.add(GetCursorCommand::new());
+        let first_field = batch.add(MoveCursorToFirstFieldCommand::new());
+        let first_field_cursor_position = batch.add(GetCursorCommand::new());
+        let mut results = self.execute_batch(batch);
+        let current_cursor_position = results.take(current_cursor_position)
             .expect("The client interface should have returned the cursor position.");
-
-        // move to the first field
-        self.client_interface
-            .borrow_mut()
-            .execute(MoveCursorToFirstFieldCommand::new())
+        results.take(first_field)
             .expect("The client interface should be able to find the first field.");
 
         let mut fields_count = 0;
@@ -66,26 +224,22 @@ impl<T: CommandExecutor> ImmutableMainframeProvider for MainframeProvider<T> {
         // TODO determine what should be done if there are no fields on the screen
 
         // get the first field cursor position so that we can determine when we've cycled back
-        let first_field_cursor_position = self.client_interface
-            .borrow_mut()
-            .execute(GetCursorCommand::new())
+        let first_field_cursor_position = results.take(first_field_cursor_position)
             .expect("The client interface should be able to get the current cursor position.");
 
-        let mut current_field_cursor_position: Option<(u8, u8)> = None;
+        let mut current_field_cursor_position: Option<(usize, usize)> = None;
         while current_field_cursor_position.is_none() || current_field_cursor_position.unwrap() != first_field_cursor_position {
 
             fields_count += 1;
 
-            // move to the next field
-            self.client_interface
-                .borrow_mut()
-                .execute(MoveCursorToNextFieldCommand::new())
+            // move to the next field and get its cursor position
+            let mut batch = CommandBatch::new();
+            let next_field = batch.add(MoveCursorToNextFieldCommand::new());
+            let next_field_cursor_position = batch.add(GetCursorCommand::new());
+            let mut results = self.execute_batch(batch);
+            results.take(next_field)
                 .expect("The client interface should be able to move the cursor to the next field.");
-
-            // get the current field cursor position
-            current_field_cursor_position = Some(self.client_interface
-                .borrow_mut()
-                .execute(GetCursorCommand::new())
+            current_field_cursor_position = Some(results.take(next_field_cursor_position)
                 .expect("The client interface should be able to get the current cursor position."));
         }
 
@@ -93,61 +247,46 @@ impl<T: CommandExecutor> ImmutableMainframeProvider for MainframeProvider<T> {
         self.client_interface
             .borrow_mut()
             .execute(MoveCursorCommand::new(current_cursor_position.0, current_cursor_position.1))
-            .expect("The client nterface should be able to set the cursor position back to the original position.");
+            .expect("The client interface should be able to set the cursor position back to the original position.");
 
         fields_count
     }
-    fn get_field_vector(&self) -> Option<(u8, u8, u8)> {
+    fn get_field_vector(&self) -> Option<(usize, usize, usize)> {
         // get the current cursor position
         let original_cursor_position = self.client_interface
             .borrow_mut()
             .execute(GetCursorCommand::new())
             .expect("The client interface should have returned the cursor position.");
 
+        // find both ends of the field and restore the cursor position in a single batch
+        let mut batch = CommandBatch::new();
         // move the cursor to the front of the field by going forward and backward
-        self.client_interface
-            .borrow_mut()
-            .execute(MoveCursorToNextFieldCommand::new())
+        let next_field = batch.add(MoveCursorToNextFieldCommand::new());
+        let previous_field = batch.add(MoveCursorToPreviousFieldCommand::new());
+        let starting_cursor_position = batch.add(GetCursorCommand::new());
+        // move the cursor to the end of the field
+        let field_end = batch.add(MoveCursorToFieldEndCommand::new());
+        let ending_cursor_position = batch.add(GetCursorCommand::new());
+        let restore = batch.add(MoveCursorCommand::new(original_cursor_position.0, original_cursor_position.1));
+        let mut results = self.execute_batch(batch);
+
+        results.take(next_field)
             .expect("The client interface should be permitted to move to the next field.");
-        self.client_interface
-            .borrow_mut()
-            .execute(MoveCursorToPreviousFieldCommand::new())
+        results.take(previous_field)
             .expect("The client interface should be permitted to move back to the original field.");
-
-        // get the cursor position as the beginning of the field
-        let starting_cursor_position = self.client_interface
-            .borrow_mut()
-            .execute(GetCursorCommand::new())
+        let starting_cursor_position = results.take(starting_cursor_position)
             .expect("The client interface should return the starting position of the field.");
+        results.take(field_end)
+            .expect("The client interface should be able to move to the ending field position.");
+        let ending_cursor_position = results.take(ending_cursor_position)
+            .expect("The client interface should return the ending position of the field.");
+        results.take(restore)
+            .expect("The client interface should permit restoring the cursor to the original position.");
 
-        if original_cursor_position.0 != starting_cursor_position.1 {
+        if original_cursor_position.0 != starting_cursor_position.0 {
             // the original cursor position and the field are not on the same row
-
-            // restore the cursor position
-            self.client_interface
-                .borrow_mut()
-                .execute(MoveCursorCommand::new(original_cursor_position.0, original_cursor_position.1))
-                .expect("The client interface should permit restoring the cursor to the original position.");
-
             return None;
         }
-        // move the cursor to the end of the field
-        self.client_interface
-            .borrow_mut()
-            .execute(MoveCursorToFieldEndCommand::new())
-            .expect("The client interface should be able to move to the ending field position.");
-
-        // get the cursor position at the end of the field
-        let ending_cursor_position = self.client_interface
-            .borrow_mut()
-            .execute(GetCursorCommand::new())
-            .expect("The client interface should return the ending position of the field.");
-
-        // restore the cursor position
-        self.client_interface
-            .borrow_mut()
-            .execute(MoveCursorCommand::new(original_cursor_position.0, original_cursor_position.1))
-            .expect("The client interface should permit restoring the cursor to the original position.");
 
         // if the original cursor position is contained within the bounds, return the vector
         if starting_cursor_position.1 <= original_cursor_position.1 && original_cursor_position.1 <= ending_cursor_position.1 {
@@ -159,48 +298,175 @@ impl<T: CommandExecutor> ImmutableMainframeProvider for MainframeProvider<T> {
     }
 }
 
+impl<T: CommandExecutor> MainframeProvider<T> {
+    fn execute_batch(&self, batch: CommandBatch) -> BatchResults {
+        self.client_interface
+            .borrow_mut()
+            .execute_batch(batch)
+            .expect("The client interface should accept the batch of commands.")
+    }
+}
+
 impl<T: CommandExecutor> MutableMainframeProvider for MainframeProvider<T> {
-    fn set_text_at_location(&self, x: u8, y: u8, text: &str) -> () {
+    fn set_text_at_location(&self, x: usize, y: usize, text: &str) {
         // get the current cursor position so that it can be restored at the end
         let current_cursor_position = self.client_interface
             .borrow_mut()
             .execute(GetCursorCommand::new())
             .expect("The client interface should have returned the cursor position.");
-        
+
+        let mut batch = CommandBatch::new();
         // move the cursor to the appropriate location
+        let move_cursor = batch.add(MoveCursorCommand::new(y, x));
+        // set the text to the screen
+        let set_text = batch.add(SetTextCommand::new(String::from(text)));
+        // restore the cursor to its original location
+        let restore = batch.add(MoveCursorCommand::new(current_cursor_position.0, current_cursor_position.1));
+        let mut results = self.execute_batch(batch);
+
+        results.take(move_cursor)
+            .expect("The client interface should have moved the cursor to where the text needs to go.");
+        results.take(set_text)
+            .expect("The client interface should have set the text.");
+        results.take(restore)
+            .expect("The client interface should move the cursor back to where it started.");
+    }
+    fn move_to_field_index(&self, index: usize) {
+        // move to the 0th field, then iterate as needed
+        let mut batch = CommandBatch::new();
+        let first_field = batch.add(MoveCursorToFirstFieldCommand::new());
+        let next_fields = (0..index)
+            .map(|_| batch.add(MoveCursorToNextFieldCommand::new()))
+            .collect::<Vec<BatchHandle<()>>>();
+        let mut results = self.execute_batch(batch);
+
+        results.take(first_field)
+            .expect("The client interface should move the cursor back to the first field initially.");
+        for next_field in next_fields {
+            results.take(next_field)
+                .expect("The client interface should permit moving the cursor to the next field.");
+        }
+    }
+    fn send_aid(&self, aid: Aid) -> Result<Option<ClientStatus>, Rs3270Error> {
+        let mut client_interface = self.client_interface.borrow_mut();
+        client_interface.execute(SendAidCommand::new(aid))?;
+        client_interface.execute(WaitForUnlockCommand::new())?;
+        drop(client_interface);
+        if let Some(settle_time) = self.settle_time {
+            // each write the host makes restarts the quiet period
+            while self.wait(WaitCondition::Output, Some(settle_time))? == WaitOutcome::ConditionMet {}
+        }
+        Ok(self.get_status())
+    }
+    fn press_program_function_key(&self, number: u8) -> Result<(), Rs3270Error> {
         self.client_interface
             .borrow_mut()
-            .execute(MoveCursorCommand::new(y, x))
-            .expect("The client interface should have moved the cursor to where the text needs to go.");
-
-        // set the text to the screen
+            .execute(SendProgramFunctionKeyCommand::new(number))
+    }
+    fn press_program_attention_key(&self, number: u8) -> Result<(), Rs3270Error> {
         self.client_interface
             .borrow_mut()
-            .execute(SetTextCommand::new(String::from(text)))
-            .expect("The client interface should have set the text.");
-
-        // restore the cursor to its original location
+            .execute(SendProgramAttentionKeyCommand::new(number))
+    }
+    fn press_clear_key(&self) -> Result<(), Rs3270Error> {
         self.client_interface
             .borrow_mut()
-            .execute(MoveCursorCommand::new(current_cursor_position.0, current_cursor_position.1))
-            .expect("The client interface should move the cursor back to where it started.");
+            .execute(SendClearKeyCommand::new())
     }
-    fn move_to_field_index(&self, index: u8) -> () {
-        // move to the 0th field
+    fn press_attention_key(&self) -> Result<(), Rs3270Error> {
         self.client_interface
             .borrow_mut()
-            .execute(MoveCursorToFirstFieldCommand::new())
-            .expect("The client interface should move the cursor back to the first field initially.");
-
-        if index > 0 {
-            // iterate as needed
-            for _ in 0..index {
-                self.client_interface
-                    .borrow_mut()
-                    .execute(MoveCursorToNextFieldCommand::new())
-                    .expect("The client interface should permit moving the cursor to the next field.");
-            }
-        }
+            .execute(SendAttentionKeyCommand::new())
+    }
+    fn press_system_request_key(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(SendSystemRequestKeyCommand::new())
+    }
+    fn reset_keyboard(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(ResetKeyboardCommand::new())
+    }
+    fn erase_to_end_of_field(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(EraseToEndOfFieldCommand::new())
+    }
+    fn erase_all_input(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(EraseAllInputCommand::new())
+    }
+    fn erase_previous_character(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(ErasePreviousCharacterCommand::new())
+    }
+    fn delete_character(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(DeleteCharacterCommand::new())
+    }
+    fn enable_insert_mode(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(EnableInsertModeCommand::new())
+    }
+    fn toggle_insert_mode(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(ToggleInsertModeCommand::new())
+    }
+    fn move_cursor_left(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(MoveCursorLeftCommand::new())
+    }
+    fn move_cursor_right(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(MoveCursorRightCommand::new())
+    }
+    fn move_cursor_up(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(MoveCursorUpCommand::new())
+    }
+    fn move_cursor_down(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(MoveCursorDownCommand::new())
+    }
+    fn move_cursor_left_twice(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(MoveCursorLeftTwiceCommand::new())
+    }
+    fn move_cursor_right_twice(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(MoveCursorRightTwiceCommand::new())
+    }
+    fn move_cursor_to_next_line(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(MoveCursorToNextLineCommand::new())
+    }
+    fn send_duplicate(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(SendDuplicateCommand::new())
+    }
+    fn send_field_mark(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(SendFieldMarkCommand::new())
+    }
+    fn select_at_cursor(&self) -> Result<(), Rs3270Error> {
+        self.client_interface
+            .borrow_mut()
+            .execute(SelectAtCursorCommand::new())
     }
 }
 
@@ -209,41 +475,35 @@ mod tests {
 
     use std::{sync::Mutex, time::Duration};
 
-    use crate::client_interface;
+    use crate::{emulated_client::EmulatedCommandExecutor, host_simulator::*, native_client::NativeCommandExecutor, screen::*};
 
     use super::*;
 
-    static cached_client: Mutex<Option<Client>> = Mutex::new(None);
-    static is_previous_still_running: Mutex<bool> = Mutex::new(false);
+    static CACHED_CLIENT: Mutex<Option<Client>> = Mutex::new(None);
+    static IS_PREVIOUS_STILL_RUNNING: Mutex<bool> = Mutex::new(false);
 
     fn init() {
         std::env::set_var("RUST_BACKTRACE", "1");
-        assert!(!*is_previous_still_running.lock().unwrap());
-        let _ = std::mem::replace(&mut *is_previous_still_running.lock().unwrap(), true);
+        assert!(!*IS_PREVIOUS_STILL_RUNNING.lock().unwrap());
+        let _ = std::mem::replace(&mut *IS_PREVIOUS_STILL_RUNNING.lock().unwrap(), true);
     }
 
     fn cleanup() {
-        let _ = std::mem::replace(&mut *is_previous_still_running.lock().unwrap(), false);
-        cached_client
+        let _ = std::mem::replace(&mut *IS_PREVIOUS_STILL_RUNNING.lock().unwrap(), false);
+        if let Some(client) = CACHED_CLIENT
             .lock()
             .expect("The mutex should provide the client Option.")
-            .as_mut()
-            .map(|client| {
-                client
-                    .kill()
-                    .expect("The client should be killable.");
-            });
+            .as_mut() {
+            client
+                .kill()
+                .expect("The client should be killable.");
+        }
     }
 
     fn get_provider() -> MainframeProvider<StreamCommandExecutor> {
         let terminal_configuration = TerminalConfiguration::new("localhost:3270", "localhost:3271");
-        let temp_client = X3270ClientSpawner::spawn(&terminal_configuration).unwrap();
-
-        // wait a second
-        std::thread::sleep(Duration::from_secs(1));
-
-        let _ = std::mem::replace(&mut *cached_client.lock().unwrap(), Some(temp_client));
-        let command_executor = StreamCommandExecutor::connect_to_client_process(&terminal_configuration.client_address).unwrap();
+        let (temp_client, command_executor) = X3270ClientSpawner::spawn_and_wait(&terminal_configuration).unwrap();
+        let _ = CACHED_CLIENT.lock().unwrap().replace(temp_client);
         MainframeProvider::new(command_executor)
     }
 
@@ -263,8 +523,189 @@ mod tests {
         let provider = get_provider();
 
         let screen_text = provider.get_screen_text();
-        assert_eq!(24, screen_text.len());
+        // x3270 is launched as a model 4, so the screen is 43 rows rather than 24
+        assert_eq!(provider.get_screen_size().unwrap().rows, screen_text.len());
+        assert_eq!(43, screen_text.len());
 
         cleanup();
     }
-}
\ No newline at end of file
+
+    fn get_emulated_provider() -> MainframeProvider<EmulatedCommandExecutor> {
+        let mut command_executor = EmulatedCommandExecutor::new(24, 80);
+        command_executor.add_field(0, 0, true, "USER:").unwrap();
+        command_executor.add_field(0, 6, false, "ALICE").unwrap();
+        command_executor.add_field(0, 16, true, "PASSWORD:").unwrap();
+        command_executor.add_field(0, 26, false, "").unwrap();
+        command_executor.add_field(0, 36, true, "").unwrap();
+        MainframeProvider::new(command_executor)
+    }
+
+    #[test]
+    fn count_fields_on_emulated_screen() {
+        let provider = get_emulated_provider();
+
+        assert_eq!(2, provider.get_fields_count());
+        assert_eq!(Some((0, 0)), provider.get_status().map(|status| (status.cursor_row, status.cursor_column)));
+    }
+
+    #[test]
+    fn get_field_vector_on_emulated_screen() {
+        let provider = get_emulated_provider();
+
+        provider.move_to_field_index(0);
+        // FieldEnd stops just past "ALICE", which the length includes
+        assert_eq!(Some((0, 7, 6)), provider.get_field_vector());
+        assert_eq!(Some((0, 7)), provider.get_status().map(|status| (status.cursor_row, status.cursor_column)));
+    }
+
+    #[test]
+    fn set_text_on_emulated_screen() {
+        let provider = get_emulated_provider();
+
+        provider.set_text_at_location(27, 0, "SECRET");
+        assert_eq!("SECRET", provider.get_text_at_location(27, 0, 6));
+        provider.move_to_field_index(1);
+        assert_eq!(Some((0, 27)), provider.get_status().map(|status| (status.cursor_row, status.cursor_column)));
+    }
+
+    #[test]
+    fn edit_with_keyboard_on_emulated_screen() {
+        let provider = get_emulated_provider();
+
+        provider.move_to_field_index(0);
+        provider.move_cursor_right().unwrap();
+        provider.delete_character().unwrap();
+        assert_eq!("AICE", provider.get_text_at_location(7, 0, 4));
+        provider.enable_insert_mode().unwrap();
+        provider.set_text_at_location(8, 0, "L");
+        assert_eq!("ALICE", provider.get_text_at_location(7, 0, 5));
+        // the cursor is back after the "A"
+        provider.erase_to_end_of_field().unwrap();
+        assert_eq!("A    ", provider.get_text_at_location(7, 0, 5));
+        // the protected field at the end of the first row wraps around the screen, so the next input position is back on it
+        provider.move_cursor_to_next_line().unwrap();
+        assert_eq!(Some((0, 7)), provider.get_status().map(|status| (status.cursor_row, status.cursor_column)));
+        // the label cannot be edited
+        provider.move_cursor_left_twice().unwrap();
+        assert!(matches!(provider.delete_character(), Err(Rs3270Error::Emulator { .. })));
+        assert!(matches!(provider.press_program_function_key(25), Err(Rs3270Error::Rejected { .. })));
+        provider.press_program_function_key(3).unwrap();
+    }
+
+    #[test]
+    fn send_aid_then_wait_for_host() {
+        let mut login_screen = SimulatedScreen::new("login");
+        login_screen.add_field(0, 0, true, "USER:");
+        login_screen.add_field(0, 6, false, "");
+        login_screen.add_field(0, 16, true, "");
+        login_screen.set_cursor(0, 7);
+        login_screen.add_transition_with_input(Aid::Enter, 0, 6, "ALICE", "menu");
+        let mut menu_screen = SimulatedScreen::new("menu");
+        menu_screen.add_field(0, 0, true, "MENU");
+        menu_screen.add_transition(Aid::Pf3, "login");
+        let host_simulator = HostSimulator::start(HostScript::new(vec![login_screen, menu_screen])).unwrap();
+        let terminal_configuration = TerminalConfiguration::new(host_simulator.get_address(), "");
+        let mut command_executor = NativeCommandExecutor::connect(&terminal_configuration).unwrap();
+        command_executor.execute(WaitForCurrentFieldCommand::new()).unwrap();
+        let mut provider = MainframeProvider::new(command_executor);
+        provider.set_settle_time(Some(Duration::from_millis(100)));
+
+        provider.set_text_at_location(7, 0, "ALICE");
+        let status = provider.send_aid(Aid::Enter).unwrap().unwrap();
+        assert!(!status.is_keyboard_locked());
+        assert_eq!("MENU", provider.get_text_at_location(1, 0, 4));
+        provider.send_aid(Aid::Pf3).unwrap();
+        assert_eq!("USER:", provider.get_text_at_location(1, 0, 5));
+        assert_eq!(vec![AID_ENTER, Aid::Pf3.get_code()], host_simulator.get_received_aids().iter().map(|received_aid| received_aid.aid).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn wait_on_emulated_screen() {
+        let provider = get_emulated_provider();
+
+        assert_eq!(WaitOutcome::ConditionMet, provider.wait(WaitCondition::Mode3270, None).unwrap());
+        assert_eq!(WaitOutcome::TimedOut, provider.wait(WaitCondition::Disconnected, Some(Duration::from_secs(1))).unwrap());
+        assert!(matches!(provider.wait(WaitCondition::NvtMode, None), Err(Rs3270Error::Emulator { .. })));
+        assert!(matches!(provider.wait(WaitCondition::Seconds, None), Err(Rs3270Error::Rejected { .. })));
+        // polled through the connection state rather than sent as a Wait
+        assert_eq!(WaitOutcome::ConditionMet, provider.wait(WaitCondition::Connected, Some(Duration::from_secs(1))).unwrap());
+        assert!(matches!(provider.client_interface.borrow_mut().execute(WaitCommand::new(WaitCondition::Connected)), Err(Rs3270Error::Rejected { .. })));
+    }
+
+    #[test]
+    fn get_fields_on_emulated_screen() {
+        let provider = get_emulated_provider();
+        provider.set_text_at_location(27, 0, "SECRET");
+
+        let fields = provider.get_fields().unwrap();
+        assert_eq!(5, fields.len());
+        assert_eq!((0, 7, 9), (fields[1].row, fields[1].column, fields[1].length));
+        assert_eq!("ALICE    ", fields[1].content);
+        assert!(!fields[1].is_protected && !fields[1].is_modified);
+        assert!(fields[3].is_modified);
+        assert_eq!("SECRET", fields[3].content.trim_end());
+        // the last field wraps around to the first attribute
+        assert_eq!(80 * 24 - 37, fields[4].length);
+        assert_eq!(Some((0, 0)), provider.get_status().map(|status| (status.cursor_row, status.cursor_column)));
+    }
+
+    #[test]
+    fn query_session_on_emulated_screen() {
+        let provider = get_emulated_provider();
+        provider.move_to_field_index(1);
+
+        assert_eq!((1, 28), provider.get_cursor_from_one().unwrap());
+        assert!(provider.is_formatted().unwrap());
+        assert_eq!(ScreenSize { rows: 24, columns: 80 }, provider.get_screen_size().unwrap());
+        assert_eq!(2, provider.get_model().unwrap().model_number);
+        assert_eq!(CodePage::Cp037, provider.get_code_page().unwrap());
+        assert_eq!(None, provider.get_lu_name().unwrap());
+        assert!(!provider.get_tls_state().unwrap().is_secure);
+        assert_eq!(SessionState::Connected3270, provider.get_session_state().unwrap());
+    }
+
+    #[test]
+    fn query_session_over_tn3270e() {
+        let mut screen = SimulatedScreen::new("banner");
+        screen.add_field(0, 0, true, "WELCOME");
+        let host_simulator = HostSimulator::start(HostScript::new(vec![screen])).unwrap();
+        let terminal_configuration = TerminalConfiguration::new(host_simulator.get_address(), "");
+        let mut command_executor = NativeCommandExecutor::connect(&terminal_configuration).unwrap();
+        command_executor.execute(WaitCommand::new(WaitCondition::Mode3270)).unwrap();
+        let provider = MainframeProvider::new(command_executor);
+
+        let host = provider.get_host().unwrap().unwrap();
+        assert_eq!(host_simulator.get_address(), format!("{}:{}", host.name, host.port));
+        assert_eq!(Some(String::from("SIMLU001")), provider.get_lu_name().unwrap());
+        assert_eq!(SessionState::ConnectedTn3270e, provider.get_session_state().unwrap());
+        assert_eq!(WaitOutcome::ConditionMet, provider.wait(WaitCondition::Connected, None).unwrap());
+        assert!(!provider.get_tls_state().unwrap().is_secure);
+        assert!(provider.get_model().unwrap().is_extended);
+    }
+
+    #[test]
+    fn read_alternate_size_screen_over_tn3270e() {
+        let mut screen = SimulatedScreen::new("wide");
+        screen.add_field(0, 0, true, "WIDE");
+        screen.add_field(26, 120, true, "BOTTOM");
+        let mut host_script = HostScript::new(vec![screen]);
+        host_script.columns = 132;
+        host_script.is_alternate_size = true;
+        let host_simulator = HostSimulator::start(host_script).unwrap();
+        let mut terminal_configuration = TerminalConfiguration::new(host_simulator.get_address(), "");
+        terminal_configuration.screen_model = ScreenModel::Model5;
+        let mut command_executor = NativeCommandExecutor::connect(&terminal_configuration).unwrap();
+        command_executor.execute(WaitCommand::new(WaitCondition::Output)).unwrap();
+        let provider = MainframeProvider::new(command_executor);
+
+        assert_eq!(ScreenSize { rows: 27, columns: 132 }, provider.get_screen_size().unwrap());
+        assert_eq!(5, provider.get_model().unwrap().model_number);
+        let screen_text = provider.get_screen_text();
+        assert_eq!(27, screen_text.len());
+        assert!(screen_text[26].contains("BOTTOM"));
+        assert_eq!("BOTTOM", provider.get_text_at_location(121, 26, 6));
+
+        terminal_configuration.screen_model = ScreenModel::Oversize { rows: 200, columns: 200 };
+        assert!(NativeCommandExecutor::connect(&terminal_configuration).is_err());
+    }
+}
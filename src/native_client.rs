@@ -0,0 +1,689 @@
+#![allow(dead_code)]
+
+use std::{net::TcpStream, time::{Duration, Instant}};
+use crate::{client_interface::*, client_status::*, code_page::*, emulator::*, error::Rs3270Error, logging::*, screen::*, telnet::TelnetConnection, tls::*, tn3270e::Tn3270eConfiguration};
+
+// the port of a mainframe address that names none
+pub const DEFAULT_TELNET_PORT: u16 = 23;
+
+// the host and port of an address such as "mainframe.example.com:23" or "[::1]:23", where a bare IPv6 address has no port
+pub fn split_mainframe_address(address: &str) -> (&str, Option<u16>) {
+    if let Some(bracketed) = address.strip_prefix('[') {
+        if let Some((host, rest)) = bracketed.split_once(']') {
+            return (host, rest.strip_prefix(':').and_then(|port| port.parse::<u16>().ok()));
+        }
+    }
+    match address.split_once(':') {
+        Some((host, port)) if !port.contains(':') => {
+            match port.parse::<u16>() {
+                Ok(port) => (host, Some(port)),
+                Err(_) => (address, None)
+            }
+        },
+        _ => {
+            (address, None)
+        }
+    }
+}
+
+// the address with its port, taken from the configured port or else the telnet port when the address names none
+fn get_effective_mainframe_address(terminal_configuration: &TerminalConfiguration) -> String {
+    let (host, port) = split_mainframe_address(&terminal_configuration.mainframe_address);
+    let port = port.or(terminal_configuration.port).unwrap_or(DEFAULT_TELNET_PORT);
+    if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    }
+    else {
+        format!("{}:{}", host, port)
+    }
+}
+
+// the options that only mean something to an emulator process, which the native client cannot honor
+fn get_emulator_only_option(terminal_configuration: &TerminalConfiguration) -> Option<&'static str> {
+    if terminal_configuration.keymap.is_some() {
+        Some("keymap")
+    }
+    else if terminal_configuration.trace_file_path.is_some() {
+        Some("trace file")
+    }
+    else if terminal_configuration.executable_path.is_some() {
+        Some("executable path")
+    }
+    else if !terminal_configuration.extra_arguments.is_empty() {
+        Some("extra arguments")
+    }
+    else if terminal_configuration.emulator_connect_timeout.is_some() {
+        Some("emulator connect timeout")
+    }
+    else {
+        None
+    }
+}
+
+// a CommandExecutor that speaks TN3270 to the mainframe directly instead of driving an x3270 process
+pub struct NativeCommandExecutor {
+    connection: TelnetConnection<MainframeStream>,
+    screen: Screen,
+    is_output_pending: bool,
+    mainframe_address: String,
+    is_connected: bool,
+    is_certificate_verified: bool,
+    status: Option<ClientStatus>,
+    timeouts: Timeouts,
+    // the read timeout of the command in progress, which bounds each Wait as a whole rather than each read
+    read_timeout: Option<Duration>
+}
+
+impl NativeCommandExecutor {
+    // negotiation failures such as a rejected LU name or certificate can be inspected with Tn3270eError::from_io_error and TlsError::from_io_error
+    pub fn connect(terminal_configuration: &TerminalConfiguration) -> std::io::Result<Self> {
+        terminal_configuration.screen_model.validate()
+            .map_err(|message| std::io::Error::new(std::io::ErrorKind::InvalidInput, message))?;
+        if let Some(option) = get_emulator_only_option(terminal_configuration) {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("The native client has no emulator process, so it cannot honor the {}.", option)));
+        }
+        let mainframe_address = &get_effective_mainframe_address(terminal_configuration);
+        let timeouts = &terminal_configuration.timeouts;
+        let stream = match &terminal_configuration.proxy {
+            Some(proxy_configuration) => {
+                proxy_configuration.connect(mainframe_address, timeouts)?
+            },
+            None => {
+                connect_with_timeout(mainframe_address, timeouts.connect)?
+            }
+        };
+        // the handshake and negotiation are bounded by the same timeouts as commands
+        Self::set_socket_timeouts(&stream, timeouts)?;
+        let stream = match &terminal_configuration.tls {
+            Some(tls_configuration) if tls_configuration.mode == TlsMode::Implicit => {
+                tls_configuration.connect(mainframe_address, stream)?
+            },
+            _ => {
+                MainframeStream::Plain(stream)
+            }
+        };
+        let mut connection = Self::open_connection(stream, terminal_configuration);
+        connection.negotiate()?;
+        if let Some(tls_configuration) = &terminal_configuration.tls {
+            if connection.is_start_tls_pending() {
+                let stream = connection.into_inner()
+                    .into_tcp_stream()
+                    .expect("STARTTLS should only be accepted over a plain stream");
+                connection = Self::open_connection(tls_configuration.connect(mainframe_address, stream)?, terminal_configuration);
+                connection.negotiate()?;
+            }
+            else if !connection.get_ref().is_tls() {
+                return Err(TlsError::StartTlsRefused.into());
+            }
+        }
+        let mut screen = Screen::for_model(terminal_configuration.screen_model);
+        screen.set_code_page(terminal_configuration.get_code_page());
+        let mut native_command_executor = NativeCommandExecutor {
+            connection,
+            screen,
+            is_output_pending: false,
+            mainframe_address: mainframe_address.clone(),
+            is_connected: true,
+            is_certificate_verified: terminal_configuration.tls.as_ref().is_some_and(|tls_configuration| tls_configuration.is_certificate_verified),
+            status: None,
+            timeouts: timeouts.clone(),
+            read_timeout: timeouts.read
+        };
+        native_command_executor.apply_records()?;
+        native_command_executor.status = Some(native_command_executor.get_current_status(None));
+        Ok(native_command_executor)
+    }
+    // TLS reads and writes go through the same socket, so its timeouts bound both kinds of stream
+    fn set_socket_timeouts(stream: &TcpStream, timeouts: &Timeouts) -> std::io::Result<()> {
+        stream.set_read_timeout(timeouts.read)?;
+        stream.set_write_timeout(timeouts.write)
+    }
+    fn open_connection(stream: MainframeStream, terminal_configuration: &TerminalConfiguration) -> TelnetConnection<MainframeStream> {
+        let is_start_tls_offered = !stream.is_tls() && terminal_configuration.tls.is_some();
+        // an oversize screen is a model 2 that reports its alternate size in the query reply
+        let terminal_type = format!("IBM-3278-{}", terminal_configuration.screen_model.get_number());
+        let mut connection = TelnetConnection::new(stream, &terminal_type);
+        connection.set_tn3270e_configuration(Tn3270eConfiguration::new_terminal(&format!("{terminal_type}-E"), terminal_configuration.lu_name.as_deref()));
+        connection.set_start_tls_offered(is_start_tls_offered);
+        connection
+    }
+    // the LU name the host assigned to this session when TN3270E is in use
+    pub fn get_lu_name(&self) -> Option<&str> {
+        self.connection
+            .get_tn3270e_negotiation()
+            .and_then(|negotiation| negotiation.get_device_name())
+    }
+    fn get_current_status(&self, execution_time: Option<Duration>) -> ClientStatus {
+        let host = if self.is_connected { Some(self.mainframe_address.as_str()) } else { None };
+        get_screen_status(&self.screen, host, execution_time)
+    }
+    fn apply_records(&mut self) -> std::io::Result<()> {
+        while let Some(record) = self.connection.take_record() {
+            self.is_output_pending = true;
+            if let Some(inbound) = self.screen.process_record(&record) {
+                self.connection.write_record(&inbound)?;
+            }
+        }
+        Ok(())
+    }
+    fn receive_until(&mut self, condition: fn(&NativeCommandExecutor) -> bool, timeout: Option<Duration>) -> std::io::Result<()> {
+        self.receive_pending()?;
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        while !condition(self) {
+            if let Some(deadline) = deadline {
+                let remaining_time = deadline.saturating_duration_since(Instant::now());
+                if remaining_time.is_zero() {
+                    return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "The host did not respond in time."));
+                }
+                self.connection.get_ref().get_tcp_stream().set_read_timeout(Some(remaining_time))?;
+            }
+            self.connection.receive()?;
+            self.apply_records()?;
+        }
+        Ok(())
+    }
+}
+
+impl ScreenEmulator for NativeCommandExecutor {
+    fn get_screen(&self) -> &Screen {
+        &self.screen
+    }
+    fn get_screen_mut(&mut self) -> &mut Screen {
+        &mut self.screen
+    }
+    fn send_aid(&mut self, aid: u8) -> Result<(), ActionFailure> {
+        if self.screen.is_keyboard_locked() {
+            return Err(ActionFailure::KeyboardLocked);
+        }
+        let inbound = self.screen.read_modified(aid);
+        if aid == AID_CLEAR {
+            self.screen.erase();
+        }
+        self.connection.write_record(&inbound)?;
+        self.screen.set_keyboard_locked(true);
+        self.is_output_pending = false;
+        Ok(())
+    }
+    fn query_connection(&self, item: &str) -> Option<String> {
+        match item {
+            "Host" => {
+                if !self.is_connected {
+                    return Some(String::new());
+                }
+                let (host, port) = split_mainframe_address(&self.mainframe_address);
+                Some(format!("host {} {}", host, port.unwrap_or(DEFAULT_TELNET_PORT)))
+            },
+            "LuName" => {
+                Some(String::from(self.get_lu_name().unwrap_or("none")))
+            },
+            "Tls" => {
+                if !self.connection.get_ref().is_tls() {
+                    return Some(String::from("not secure"));
+                }
+                Some(String::from(if self.is_certificate_verified { "secure verified" } else { "secure unverified" }))
+            },
+            "ConnectionState" => {
+                let state = if !self.is_connected {
+                    "not-connected"
+                }
+                else if self.connection.is_tn3270e_mode() {
+                    "connected-tn3270e"
+                }
+                else {
+                    "connected-3270"
+                };
+                Some(String::from(state))
+            },
+            _ => {
+                None
+            }
+        }
+    }
+    fn send_attention(&mut self) -> Result<(), ActionFailure> {
+        self.connection.write_attention()?;
+        Ok(())
+    }
+    fn wait(&mut self, condition: WaitCondition, timeout: Option<Duration>) -> Result<(), ActionFailure> {
+        let timeout = timeout.or(self.read_timeout);
+        match condition {
+            WaitCondition::InputField => {
+                self.receive_until(|executor| executor.screen.is_formatted() && !executor.screen.is_keyboard_locked(), timeout)?;
+            },
+            WaitCondition::Unlock => {
+                self.receive_until(|executor| !executor.screen.is_keyboard_locked(), timeout)?;
+            },
+            WaitCondition::Output => {
+                self.receive_until(|executor| executor.is_output_pending, timeout)?;
+                self.is_output_pending = false;
+            },
+            WaitCondition::Mode3270 => {
+                self.receive_until(|executor| executor.is_connected && executor.connection.is_3270_mode(), timeout)?;
+            },
+            WaitCondition::Connected => {
+                self.receive_until(|executor| executor.is_connected, timeout)?;
+            },
+            WaitCondition::NvtMode => {
+                return Err(ActionFailure::CommandFailure(String::from("Wait: the native client has no NVT mode.")));
+            },
+            WaitCondition::Disconnected => {
+                match self.receive_until(|executor| !executor.is_connected, timeout) {
+                    Err(error) if matches!(error.kind(), std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::ConnectionReset | std::io::ErrorKind::ConnectionAborted) => {
+                        self.is_connected = false;
+                    },
+                    receive_result => {
+                        receive_result?;
+                    }
+                }
+            },
+            WaitCondition::Seconds => {
+                // keeps the screen current while the time passes
+                match self.receive_until(|_| false, timeout) {
+                    Err(error) if matches!(error.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock) => {
+                        // the time is up
+                    },
+                    receive_result => {
+                        receive_result?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    // applies everything the host has already sent without blocking
+    fn receive_pending(&mut self) -> std::io::Result<()> {
+        self.connection.get_ref().get_tcp_stream().set_nonblocking(true)?;
+        let receive_result = loop {
+            match self.connection.receive() {
+                Ok(()) => {
+                    // keep draining
+                },
+                Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {
+                    break Ok(());
+                },
+                Err(error) => {
+                    break Err(error);
+                }
+            }
+        };
+        self.connection.get_ref().get_tcp_stream().set_nonblocking(false)?;
+        self.apply_records()?;
+        receive_result
+    }
+}
+
+impl CommandExecutor for NativeCommandExecutor {
+    fn connect_to_client_process(mainframe_address: &str) -> Option<Self> {
+        let terminal_configuration = TerminalConfiguration::new(mainframe_address, "");
+        match NativeCommandExecutor::connect(&terminal_configuration) {
+            Ok(native_command_executor) => {
+                Some(native_command_executor)
+            },
+            Err(error) => {
+                tracing::error!(mainframe_address, %error, "unable to connect to the mainframe");
+                None
+            }
+        }
+    }
+    fn execute_with_timeouts<TOutput>(&mut self, command: impl CommandBuilder<TOutput>, timeouts: &Timeouts) -> Result<TOutput, Rs3270Error> {
+        let client_message = command.get_client_message();
+        let span = start_command_span(&client_message);
+        let _entered = span.enter();
+        let start_time = Instant::now();
+        self.read_timeout = timeouts.read;
+        let execution_result = match Self::set_socket_timeouts(self.connection.get_ref().get_tcp_stream(), timeouts) {
+            Ok(()) => {
+                self.execute_actions(command, &client_message)
+            },
+            Err(error) => {
+                Err(Rs3270Error::from_io_error(&redact_command(&client_message), error))
+            }
+        };
+        record_command_outcome(&span, start_time, &execution_result);
+        if let Err(Rs3270Error::Io { .. } | Rs3270Error::Disconnected { .. } | Rs3270Error::Certificate { .. }) = &execution_result {
+            self.is_connected = false;
+        }
+        self.status = Some(self.get_current_status(Some(start_time.elapsed())));
+        execution_result
+    }
+    fn get_timeouts(&self) -> &Timeouts {
+        &self.timeouts
+    }
+    fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+    // the screen is only updated from complete records, so catching up on what the host sent since is enough
+    fn resynchronize(&mut self) -> Result<(), Rs3270Error> {
+        self.receive_pending()
+            .map_err(|error| Rs3270Error::from_io_error("(resynchronize)", error))
+    }
+    fn get_status(&self) -> Option<&ClientStatus> {
+        self.status.as_ref()
+    }
+    fn get_code_page(&self) -> CodePage {
+        self.screen.get_code_page()
+    }
+    fn set_code_page(&mut self, code_page: CodePage) {
+        self.screen.set_code_page(code_page);
+    }
+    fn disconnect(&mut self) {
+        let shutdown_result = self.connection.get_ref().get_tcp_stream().shutdown(std::net::Shutdown::Both);
+        if let Err(shutdown_error) = shutdown_result {
+            tracing::warn!(%shutdown_error, "unable to disconnect via shutdown");
+        }
+        self.is_connected = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::{Read, Write}, net::TcpListener, path::PathBuf, sync::{mpsc::{Receiver, Sender}, Arc}};
+    use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+
+    use crate::{action::Action, data_stream::*, host_simulator::*, telnet::*};
+
+    use super::*;
+
+    fn get_ebcdic(text: &str) -> Vec<u8> {
+        text.chars()
+            .map(|character| CodePage::Cp037.get_byte(character).unwrap())
+            .collect()
+    }
+
+    // shows a login screen, reports the first inbound record, then acknowledges it
+    fn serve_host<S: Read + Write>(mut stream: S, sender: Sender<Vec<u8>>) {
+        let mut host_bytes = vec![
+            IAC, DO, OPTION_TERMINAL_TYPE,
+            IAC, SB, OPTION_TERMINAL_TYPE, 1, IAC, SE,
+            IAC, DO, OPTION_EOR, IAC, WILL, OPTION_EOR,
+            IAC, DO, OPTION_BINARY, IAC, WILL, OPTION_BINARY
+        ];
+        host_bytes.extend([COMMAND_ERASE_WRITE, WCC_KEYBOARD_RESTORE, ORDER_START_FIELD, 0x60]);
+        host_bytes.extend(get_ebcdic("NAME:"));
+        host_bytes.extend([ORDER_START_FIELD, 0x40, ORDER_INSERT_CURSOR, ORDER_SET_BUFFER_ADDRESS]);
+        host_bytes.extend(encode_address(20));
+        host_bytes.extend([ORDER_START_FIELD, 0x60, IAC, EOR]);
+        if stream.write_all(&host_bytes).is_err() {
+            return;
+        }
+
+        let mut terminal_bytes = Vec::new();
+        while !terminal_bytes.ends_with(&[IAC, EOR]) {
+            let mut buffer = [0u8; 1024];
+            let read_length = match stream.read(&mut buffer) {
+                Ok(read_length) if read_length > 0 => read_length,
+                _ => return
+            };
+            terminal_bytes.extend(&buffer[..read_length]);
+        }
+        sender.send(terminal_bytes).unwrap();
+
+        let mut host_bytes = vec![COMMAND_WRITE, WCC_KEYBOARD_RESTORE, ORDER_SET_BUFFER_ADDRESS];
+        host_bytes.extend(encode_address(80));
+        host_bytes.extend(get_ebcdic("WELCOME"));
+        host_bytes.extend([IAC, EOR]);
+        stream.write_all(&host_bytes).unwrap();
+        stream.flush().unwrap();
+
+        // hold the connection open until the terminal disconnects
+        let _ = stream.read(&mut [0u8; 1]);
+    }
+
+    // a single-connection host in the clear
+    fn start_host() -> (String, Receiver<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_host(stream, sender);
+        });
+        (address, receiver)
+    }
+
+    // a certificate authority written to a PEM file along with a "localhost" certificate it issued
+    fn create_certificates(name: &str) -> (PathBuf, Vec<CertificateDer<'static>>, PrivateKeyDer<'static>) {
+        let mut ca_parameters = rcgen::CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_parameters.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+        let ca_key = rcgen::KeyPair::generate().unwrap();
+        let ca_certificate = ca_parameters.self_signed(&ca_key).unwrap();
+        let host_key = rcgen::KeyPair::generate().unwrap();
+        let host_certificate = rcgen::CertificateParams::new(vec![String::from("localhost")]).unwrap()
+            .signed_by(&host_key, &ca_certificate, &ca_key)
+            .unwrap();
+        let ca_bundle_path = std::env::temp_dir().join(format!("rs3270-{}-{}.pem", name, std::process::id()));
+        std::fs::write(&ca_bundle_path, ca_certificate.pem()).unwrap();
+        (ca_bundle_path, vec![host_certificate.der().clone()], PrivateKeyDer::Pkcs8(host_key.serialize_der().into()))
+    }
+
+    // a single-connection host that only speaks TN3270 once TLS is up, either immediately or after START_TLS
+    fn start_tls_host(mode: TlsMode, certificates: Vec<CertificateDer<'static>>, key: PrivateKeyDer<'static>) -> (u16, Receiver<Vec<u8>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            if mode == TlsMode::StartTls {
+                stream.write_all(&[IAC, DO, OPTION_START_TLS]).unwrap();
+                let mut reply = [0u8; 3];
+                stream.read_exact(&mut reply).unwrap();
+                assert_eq!([IAC, WILL, OPTION_START_TLS], reply);
+                stream.write_all(&[IAC, SB, OPTION_START_TLS, 1, IAC, SE]).unwrap();
+                let mut reply = [0u8; 6];
+                stream.read_exact(&mut reply).unwrap();
+                assert_eq!([IAC, SB, OPTION_START_TLS, 1, IAC, SE], reply);
+            }
+            let server_configuration = rustls::ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+                .with_safe_default_protocol_versions()
+                .unwrap()
+                .with_no_client_auth()
+                .with_single_cert(certificates, key)
+                .unwrap();
+            let connection = rustls::ServerConnection::new(Arc::new(server_configuration)).unwrap();
+            serve_host(rustls::StreamOwned::new(connection, stream), sender);
+        });
+        (port, receiver)
+    }
+
+    #[test]
+    fn connect_on_configured_port_and_refuse_emulator_options() {
+        let mut screen = SimulatedScreen::new("banner");
+        screen.add_field(0, 0, true, "WELCOME");
+        let host_simulator = HostSimulator::start(HostScript::new(vec![screen])).unwrap();
+        let (host, port) = split_mainframe_address(host_simulator.get_address());
+
+        let terminal_configuration = TerminalConfiguration::builder(host)
+            .with_port(port.unwrap())
+            .build()
+            .unwrap();
+        let mut executor = NativeCommandExecutor::connect(&terminal_configuration).unwrap();
+        let host_info = executor.execute(QueryHostCommand::new()).unwrap().unwrap();
+        assert_eq!((host, port.unwrap()), (host_info.name.as_str(), host_info.port));
+        executor.disconnect();
+
+        let terminal_configuration = TerminalConfiguration::builder(host_simulator.get_address())
+            .with_keymap("rctrl")
+            .build()
+            .unwrap();
+        assert_eq!(std::io::ErrorKind::InvalidInput, NativeCommandExecutor::connect(&terminal_configuration).err().unwrap().kind());
+        let terminal_configuration = TerminalConfiguration::builder(host_simulator.get_address())
+            .with_extra_argument("-nvt")
+            .build()
+            .unwrap();
+        assert_eq!(std::io::ErrorKind::InvalidInput, NativeCommandExecutor::connect(&terminal_configuration).err().unwrap().kind());
+    }
+
+    #[test]
+    fn split_addresses_with_and_without_ports() {
+        assert_eq!(("mainframe.example.com", Some(992)), split_mainframe_address("mainframe.example.com:992"));
+        assert_eq!(("mainframe.example.com", None), split_mainframe_address("mainframe.example.com"));
+        assert_eq!(("::1", Some(23)), split_mainframe_address("[::1]:23"));
+        assert_eq!(("::1", None), split_mainframe_address("::1"));
+    }
+
+    #[test]
+    fn connect_then_type_then_enter() {
+        let (address, receiver) = start_host();
+        let mut executor = NativeCommandExecutor::connect_to_client_process(&address).unwrap();
+        executor.perform_action(&Action::new("Wait", vec![String::from("InputField")])).unwrap();
+
+        let text = executor.execute(GetTextCommand::new(0, 0, 6)).unwrap();
+        assert_eq!(String::from(" NAME:"), text);
+        let position = executor.execute(GetCursorCommand::new()).unwrap();
+        assert_eq!((0, 7), position);
+
+        executor.execute(SetTextCommand::new(String::from("BOB"))).unwrap();
+        executor.execute(SendEnterKeyCommand::new()).unwrap();
+
+        // the keyboard stays locked until the host answers
+        assert!(executor.screen.is_keyboard_locked());
+        assert!(executor.get_status().unwrap().is_keyboard_locked());
+
+        let terminal_bytes = receiver.recv().unwrap();
+        let mut expected_record = vec![AID_ENTER];
+        expected_record.extend(encode_address(10));
+        expected_record.push(ORDER_SET_BUFFER_ADDRESS);
+        expected_record.extend(encode_address(7));
+        expected_record.extend(get_ebcdic("BOB"));
+        expected_record.extend([IAC, EOR]);
+        assert!(terminal_bytes.ends_with(&expected_record));
+
+        executor.perform_action(&Action::new("Wait", vec![String::from("Unlock")])).unwrap();
+        let text = executor.execute(GetTextCommand::new(1, 0, 7)).unwrap();
+        assert_eq!(String::from("WELCOME"), text);
+        let status = executor.get_status().unwrap();
+        assert_eq!(KeyboardState::Unlocked, status.keyboard_state);
+        assert_eq!(ConnectionState::Connected(address), status.connection_state);
+        assert!(status.is_formatted);
+    }
+
+    #[test]
+    fn read_ebcdic_and_reject_unrepresentable_text() {
+        let (address, _) = start_host();
+        let mut executor = NativeCommandExecutor::connect_to_client_process(&address).unwrap();
+        executor.perform_action(&Action::new("Wait", vec![String::from("InputField")])).unwrap();
+
+        let text = executor.execute(GetEbcdicTextCommand::new(0, 1, 5, CodePage::Cp037)).unwrap();
+        assert_eq!(String::from("NAME:"), text);
+        let lines = executor.execute(GetEbcdicTextRangeCommand::new(0, 1, 4, 1, CodePage::Cp037)).unwrap();
+        assert_eq!(vec![String::from("NAME")], lines);
+
+        executor.set_code_page(CodePage::Cp273);
+        assert!(executor.execute(SetTextCommand::new(String::from("Müller"))).is_ok());
+        let failure = executor.execute(SetTextCommand::new(String::from("5€"))).err().unwrap();
+        assert!(matches!(&failure, Rs3270Error::Rejected { command, message } if command == "String(<redacted>)" && message.contains("cp273")));
+        assert_eq!((0, 13), executor.execute(GetCursorCommand::new()).unwrap());
+    }
+
+    #[test]
+    fn reject_unknown_action() {
+        let (address, _) = start_host();
+        let mut executor = NativeCommandExecutor::connect_to_client_process(&address).unwrap();
+        match executor.perform_action(&Action::new("Frobnicate", vec![])) {
+            Err(ActionFailure::CommandFailure(message)) => {
+                assert!(message.starts_with("Frobnicate"));
+            },
+            _ => {
+                panic!("An unknown action should be a command failure.");
+            }
+        }
+    }
+
+    #[test]
+    fn connect_with_implicit_tls() {
+        let (ca_bundle_path, certificates, key) = create_certificates("implicit");
+        let (port, _) = start_tls_host(TlsMode::Implicit, certificates, key);
+        let mut tls_configuration = TlsConfiguration::new(TlsMode::Implicit);
+        tls_configuration.ca_bundle_path = Some(ca_bundle_path);
+        tls_configuration.hostname_verification = HostnameVerification::Name(String::from("localhost"));
+        let mut terminal_configuration = TerminalConfiguration::new(&format!("127.0.0.1:{}", port), "");
+        terminal_configuration.tls = Some(tls_configuration);
+
+        let mut executor = NativeCommandExecutor::connect(&terminal_configuration).unwrap();
+        assert!(executor.connection.get_ref().is_tls());
+        executor.perform_action(&Action::new("Wait", vec![String::from("InputField")])).unwrap();
+        assert_eq!(String::from(" NAME:"), executor.execute(GetTextCommand::new(0, 0, 6)).unwrap());
+    }
+
+    #[test]
+    fn connect_with_start_tls() {
+        let (ca_bundle_path, certificates, key) = create_certificates("starttls");
+        let (port, receiver) = start_tls_host(TlsMode::StartTls, certificates, key);
+        let mut tls_configuration = TlsConfiguration::new(TlsMode::StartTls);
+        tls_configuration.ca_bundle_path = Some(ca_bundle_path);
+        let mut terminal_configuration = TerminalConfiguration::new(&format!("localhost:{}", port), "");
+        terminal_configuration.tls = Some(tls_configuration);
+
+        let mut executor = NativeCommandExecutor::connect(&terminal_configuration).unwrap();
+        assert!(executor.connection.get_ref().is_tls());
+        executor.perform_action(&Action::new("Wait", vec![String::from("InputField")])).unwrap();
+        executor.execute(SetTextCommand::new(String::from("BOB"))).unwrap();
+        executor.execute(SendEnterKeyCommand::new()).unwrap();
+        assert!(receiver.recv().unwrap().ends_with(&[IAC, EOR]));
+        executor.perform_action(&Action::new("Wait", vec![String::from("Unlock")])).unwrap();
+        assert_eq!(String::from("WELCOME"), executor.execute(GetTextCommand::new(1, 0, 7)).unwrap());
+    }
+
+    #[test]
+    fn report_untrusted_certificate() {
+        let (_, certificates, key) = create_certificates("untrusted");
+        let (port, _) = start_tls_host(TlsMode::Implicit, certificates, key);
+        let mut terminal_configuration = TerminalConfiguration::new(&format!("localhost:{}", port), "");
+        terminal_configuration.tls = Some(TlsConfiguration::new(TlsMode::Implicit));
+
+        let error = NativeCommandExecutor::connect(&terminal_configuration).err().unwrap();
+        assert!(TlsError::is_certificate_error(&error));
+    }
+
+    #[test]
+    fn refuse_plain_session_when_start_tls_is_required() {
+        let (address, _) = start_host();
+        let mut terminal_configuration = TerminalConfiguration::new(&address, "");
+        terminal_configuration.tls = Some(TlsConfiguration::new(TlsMode::StartTls));
+
+        let error = NativeCommandExecutor::connect(&terminal_configuration).err().unwrap();
+        assert!(matches!(TlsError::from_io_error(&error), Some(TlsError::StartTlsRefused)));
+    }
+
+    #[test]
+    fn time_out_negotiating_with_silent_host() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mainframe_address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            // accepts but never negotiates
+            let (_stream, _) = listener.accept().unwrap();
+            std::thread::sleep(std::time::Duration::from_secs(2));
+        });
+        let mut terminal_configuration = TerminalConfiguration::new(&mainframe_address, "");
+        terminal_configuration.timeouts.read = Some(std::time::Duration::from_millis(200));
+
+        let error = NativeCommandExecutor::connect(&terminal_configuration).err().unwrap();
+        assert!(matches!(error.kind(), std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock));
+    }
+
+    #[test]
+    fn wait_for_output_then_disconnect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mainframe_address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            // negotiates and writes one screen, then hangs up once the terminal has seen it
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut host_bytes = vec![
+                IAC, DO, OPTION_TERMINAL_TYPE,
+                IAC, SB, OPTION_TERMINAL_TYPE, 1, IAC, SE,
+                IAC, DO, OPTION_EOR, IAC, WILL, OPTION_EOR,
+                IAC, DO, OPTION_BINARY, IAC, WILL, OPTION_BINARY
+            ];
+            host_bytes.extend([COMMAND_ERASE_WRITE, WCC_KEYBOARD_RESTORE, IAC, EOR]);
+            stream.write_all(&host_bytes).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(300));
+        });
+        let mut executor = NativeCommandExecutor::connect_to_client_process(&mainframe_address).unwrap();
+
+        executor.execute(WaitCommand::new(WaitCondition::Output)).unwrap();
+        executor.execute(WaitWithTimeoutCommand::new(WaitCondition::Mode3270, std::time::Duration::from_secs(1))).unwrap();
+        let outcome = WaitOutcome::from_result(executor.execute(WaitWithTimeoutCommand::new(WaitCondition::Output, std::time::Duration::from_millis(100))));
+        assert_eq!(WaitOutcome::TimedOut, outcome.unwrap());
+        executor.execute(WaitWithTimeoutCommand::new(WaitCondition::Disconnected, std::time::Duration::from_secs(5))).unwrap();
+        assert_eq!(ConnectionState::NotConnected, executor.get_status().unwrap().connection_state);
+    }
+}
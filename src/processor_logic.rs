@@ -9,9 +9,9 @@ struct NavigateOperation<'a, T: MutableMainframeProvider> {
 }
 
 struct StoreOperation {
-    x: u8,
-    y: u8,
-    length: u8,
+    x: usize,
+    y: usize,
+    length: usize,
     variable_name: String
 }
 
@@ -21,8 +21,8 @@ enum SetOperationSource {
 }
 
 struct SetOperation {
-    x: u8,
-    y: u8,
+    x: usize,
+    y: usize,
     source: SetOperationSource
 }
 
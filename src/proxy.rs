@@ -0,0 +1,543 @@
+#![allow(dead_code)]
+
+use std::{io::{Read, Write}, net::{IpAddr, TcpStream}};
+use crate::client_interface::{connect_with_timeout, Timeouts};
+
+const SOCKS_VERSION: u8 = 0x05;
+const SOCKS_METHOD_NO_AUTHENTICATION: u8 = 0x00;
+const SOCKS_METHOD_USERNAME_PASSWORD: u8 = 0x02;
+const SOCKS_METHOD_NONE_ACCEPTABLE: u8 = 0xFF;
+const SOCKS_USERNAME_PASSWORD_VERSION: u8 = 0x01;
+const SOCKS_COMMAND_CONNECT: u8 = 0x01;
+const SOCKS_ADDRESS_IPV4: u8 = 0x01;
+const SOCKS_ADDRESS_DOMAIN_NAME: u8 = 0x03;
+const SOCKS_ADDRESS_IPV6: u8 = 0x04;
+const SOCKS_REPLY_SUCCEEDED: u8 = 0x00;
+
+// the most an HTTP proxy may send before the blank line that ends its response
+const MAXIMUM_HTTP_RESPONSE_LENGTH: usize = 8192;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyKind {
+    Socks5,
+    // an HTTP proxy that tunnels with CONNECT
+    Http
+}
+
+impl ProxyKind {
+    // the type prefix of x3270's -proxy option
+    pub fn get_x3270_name(&self) -> &'static str {
+        match self {
+            ProxyKind::Socks5 => "socks5",
+            ProxyKind::Http => "http"
+        }
+    }
+    pub fn from_x3270_name(name: &str) -> Option<ProxyKind> {
+        match name {
+            "socks5" => Some(ProxyKind::Socks5),
+            "http" => Some(ProxyKind::Http),
+            _ => None
+        }
+    }
+    pub fn get_default_port(&self) -> u16 {
+        match self {
+            ProxyKind::Socks5 => 1080,
+            ProxyKind::Http => 3128
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum ProxyError {
+    // the settings cannot describe a proxy, such as an unknown type
+    Configuration(String),
+    // the proxy itself could not be reached
+    Unreachable(String),
+    // the proxy refused the credentials, or wanted some when none were given
+    Authentication(String),
+    // the proxy was reached but would not connect to the mainframe
+    ConnectRefused(String),
+    // the proxy answered with something other than its protocol
+    Protocol(String)
+}
+
+impl std::fmt::Display for ProxyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProxyError::Configuration(message) => {
+                write!(f, "The proxy settings could not be used: {}", message)
+            },
+            ProxyError::Unreachable(message) => {
+                write!(f, "The proxy could not be reached: {}", message)
+            },
+            ProxyError::Authentication(message) => {
+                write!(f, "The proxy did not accept the credentials: {}", message)
+            },
+            ProxyError::ConnectRefused(message) => {
+                write!(f, "The proxy did not connect to the host: {}", message)
+            },
+            ProxyError::Protocol(message) => {
+                write!(f, "The proxy sent an unexpected response: {}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ProxyError {}
+
+impl ProxyError {
+    // finds the proxy failure carried by an io::Error
+    pub fn from_io_error(error: &std::io::Error) -> Option<ProxyError> {
+        error.get_ref()?
+            .downcast_ref::<ProxyError>()
+            .cloned()
+    }
+}
+
+impl From<ProxyError> for std::io::Error {
+    fn from(error: ProxyError) -> Self {
+        let kind = match error {
+            ProxyError::Configuration(_) => std::io::ErrorKind::InvalidInput,
+            ProxyError::Unreachable(_) => std::io::ErrorKind::NotConnected,
+            ProxyError::Authentication(_) => std::io::ErrorKind::PermissionDenied,
+            ProxyError::ConnectRefused(_) => std::io::ErrorKind::ConnectionRefused,
+            ProxyError::Protocol(_) => std::io::ErrorKind::InvalidData
+        };
+        std::io::Error::new(kind, error)
+    }
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub struct ProxyConfiguration {
+    pub kind: ProxyKind,
+    // the proxy's "host:port"
+    pub address: String,
+    pub username: Option<String>,
+    pub password: Option<String>
+}
+
+// the spawners log their configuration, so the password is left out
+impl std::fmt::Debug for ProxyConfiguration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProxyConfiguration")
+            .field("kind", &self.kind)
+            .field("address", &self.address)
+            .field("username", &self.username)
+            .field("password", &self.password.as_ref().map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+impl ProxyConfiguration {
+    pub fn new(kind: ProxyKind, address: &str) -> Self {
+        ProxyConfiguration {
+            kind,
+            address: String::from(address),
+            username: None,
+            password: None
+        }
+    }
+    // reads x3270's "type:[username:password@]host[:port]", such as "socks5:proxy.example.com:1080"
+    pub fn parse(proxy: &str) -> Result<ProxyConfiguration, ProxyError> {
+        let (kind, rest) = proxy.split_once(':')
+            .ok_or_else(|| ProxyError::Configuration(format!("\"{}\" has no proxy type.", proxy)))?;
+        let kind = ProxyKind::from_x3270_name(kind)
+            .ok_or_else(|| ProxyError::Configuration(format!("\"{}\" is not a supported proxy type.", kind)))?;
+        let (credentials, address) = match rest.rsplit_once('@') {
+            Some((credentials, address)) => (Some(credentials), address),
+            None => (None, rest)
+        };
+        if address.is_empty() {
+            return Err(ProxyError::Configuration(format!("\"{}\" has no proxy host.", proxy)));
+        }
+        let address = if address.ends_with(']') || !address.contains(':') {
+            format!("{}:{}", address, kind.get_default_port())
+        }
+        else {
+            String::from(address)
+        };
+        let mut proxy_configuration = ProxyConfiguration::new(kind, &address);
+        if let Some(credentials) = credentials {
+            let (username, password) = credentials.split_once(':')
+                .unwrap_or((credentials, ""));
+            proxy_configuration.username = Some(String::from(username));
+            proxy_configuration.password = Some(String::from(password));
+        }
+        Ok(proxy_configuration)
+    }
+    // the value of x3270's -proxy option, which carries any credentials on the emulator's command line
+    pub fn get_x3270_argument(&self) -> String {
+        match &self.username {
+            Some(username) => {
+                format!("{}:{}:{}@{}", self.kind.get_x3270_name(), username, self.password.as_deref().unwrap_or(""), self.address)
+            },
+            None => {
+                format!("{}:{}", self.kind.get_x3270_name(), self.address)
+            }
+        }
+    }
+    // opens a tunnel to the target "host:port", where the handshake is bounded by the connect timeout
+    pub fn connect(&self, target_address: &str, timeouts: &Timeouts) -> std::io::Result<TcpStream> {
+        let mut stream = connect_with_timeout(&self.address, timeouts.connect)
+            .map_err(|error| ProxyError::Unreachable(format!("{}: {}", self.address, error)))?;
+        stream.set_read_timeout(timeouts.connect)?;
+        stream.set_write_timeout(timeouts.connect)?;
+        match self.kind {
+            ProxyKind::Socks5 => {
+                self.negotiate_socks5(&mut stream, target_address)?;
+            },
+            ProxyKind::Http => {
+                self.negotiate_http(&mut stream, target_address)?;
+            }
+        }
+        tracing::debug!(proxy = %self.address, target = %target_address, "opened proxy tunnel");
+        Ok(stream)
+    }
+    fn negotiate_socks5(&self, stream: &mut TcpStream, target_address: &str) -> std::io::Result<()> {
+        let methods = if self.username.is_some() {
+            vec![SOCKS_METHOD_NO_AUTHENTICATION, SOCKS_METHOD_USERNAME_PASSWORD]
+        }
+        else {
+            vec![SOCKS_METHOD_NO_AUTHENTICATION]
+        };
+        let mut greeting = vec![SOCKS_VERSION, methods.len() as u8];
+        greeting.extend(methods);
+        stream.write_all(&greeting)?;
+        let mut method_selection = [0u8; 2];
+        stream.read_exact(&mut method_selection)?;
+        if method_selection[0] != SOCKS_VERSION {
+            return Err(ProxyError::Protocol(format!("SOCKS version {} is not 5.", method_selection[0])).into());
+        }
+        match method_selection[1] {
+            SOCKS_METHOD_NO_AUTHENTICATION => {
+                // nothing to send
+            },
+            SOCKS_METHOD_USERNAME_PASSWORD => {
+                let username = self.username.as_deref()
+                    .ok_or_else(|| ProxyError::Authentication(String::from("the proxy asked for a username and password, but none were configured")))?;
+                let password = self.password.as_deref().unwrap_or("");
+                if username.len() > 255 || password.len() > 255 {
+                    return Err(ProxyError::Configuration(String::from("SOCKS5 usernames and passwords are limited to 255 bytes")).into());
+                }
+                let mut request = vec![SOCKS_USERNAME_PASSWORD_VERSION, username.len() as u8];
+                request.extend(username.as_bytes());
+                request.push(password.len() as u8);
+                request.extend(password.as_bytes());
+                stream.write_all(&request)?;
+                let mut reply = [0u8; 2];
+                stream.read_exact(&mut reply)?;
+                if reply[1] != 0 {
+                    return Err(ProxyError::Authentication(format!("the username and password were rejected with status {}", reply[1])).into());
+                }
+            },
+            SOCKS_METHOD_NONE_ACCEPTABLE => {
+                return Err(ProxyError::Authentication(String::from("none of the offered authentication methods were acceptable")).into());
+            },
+            method => {
+                return Err(ProxyError::Protocol(format!("the proxy chose authentication method {}, which was not offered", method)).into());
+            }
+        }
+        let (host, port) = split_target_address(target_address)?;
+        let mut request = vec![SOCKS_VERSION, SOCKS_COMMAND_CONNECT, 0x00];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(address)) => {
+                request.push(SOCKS_ADDRESS_IPV4);
+                request.extend(address.octets());
+            },
+            Ok(IpAddr::V6(address)) => {
+                request.push(SOCKS_ADDRESS_IPV6);
+                request.extend(address.octets());
+            },
+            Err(_) => {
+                if host.len() > 255 {
+                    return Err(ProxyError::Configuration(format!("the host name {} is longer than 255 bytes", host)).into());
+                }
+                request.push(SOCKS_ADDRESS_DOMAIN_NAME);
+                request.push(host.len() as u8);
+                request.extend(host.as_bytes());
+            }
+        }
+        request.extend(port.to_be_bytes());
+        stream.write_all(&request)?;
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply)?;
+        if reply[0] != SOCKS_VERSION {
+            return Err(ProxyError::Protocol(format!("SOCKS version {} is not 5.", reply[0])).into());
+        }
+        if reply[1] != SOCKS_REPLY_SUCCEEDED {
+            return Err(ProxyError::ConnectRefused(get_socks5_reply_message(reply[1])).into());
+        }
+        // the address the proxy bound for the tunnel is not needed
+        let bound_address_length = match reply[3] {
+            SOCKS_ADDRESS_IPV4 => 4,
+            SOCKS_ADDRESS_IPV6 => 16,
+            SOCKS_ADDRESS_DOMAIN_NAME => {
+                let mut length = [0u8; 1];
+                stream.read_exact(&mut length)?;
+                length[0] as usize
+            },
+            address_type => {
+                return Err(ProxyError::Protocol(format!("address type {} is not a SOCKS5 address type", address_type)).into());
+            }
+        };
+        let mut bound_address = vec![0u8; bound_address_length + 2];
+        stream.read_exact(&mut bound_address)?;
+        Ok(())
+    }
+    fn negotiate_http(&self, stream: &mut TcpStream, target_address: &str) -> std::io::Result<()> {
+        let mut request = format!("CONNECT {target_address} HTTP/1.1\r\nHost: {target_address}\r\n");
+        if let Some(username) = &self.username {
+            let credentials = format!("{}:{}", username, self.password.as_deref().unwrap_or(""));
+            request.push_str(&format!("Proxy-Authorization: Basic {}\r\n", encode_base64(credentials.as_bytes())));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        // read a byte at a time so that nothing the mainframe sends after the response is consumed here
+        let mut response = Vec::new();
+        while !response.ends_with(b"\r\n\r\n") {
+            if response.len() >= MAXIMUM_HTTP_RESPONSE_LENGTH {
+                return Err(ProxyError::Protocol(format!("the response was longer than {} bytes", MAXIMUM_HTTP_RESPONSE_LENGTH)).into());
+            }
+            let mut byte = [0u8; 1];
+            stream.read_exact(&mut byte)?;
+            response.push(byte[0]);
+        }
+        let response = String::from_utf8_lossy(&response);
+        let status_line = response.lines()
+            .next()
+            .unwrap_or("");
+        let status_code = match status_line.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [version, status_code, ..] if version.starts_with("HTTP/") => {
+                status_code.parse::<u16>()
+                    .map_err(|_| ProxyError::Protocol(format!("\"{}\" has no status code", status_line)))?
+            },
+            _ => {
+                return Err(ProxyError::Protocol(format!("\"{}\" is not an HTTP status line", status_line)).into());
+            }
+        };
+        match status_code {
+            200..=299 => {
+                Ok(())
+            },
+            407 => {
+                Err(ProxyError::Authentication(String::from(status_line)).into())
+            },
+            _ => {
+                Err(ProxyError::ConnectRefused(String::from(status_line)).into())
+            }
+        }
+    }
+}
+
+// the host and port of "host:port" or "[ipv6]:port"
+fn split_target_address(target_address: &str) -> Result<(&str, u16), ProxyError> {
+    let (host, port) = target_address.rsplit_once(':')
+        .ok_or_else(|| ProxyError::Configuration(format!("\"{}\" has no port.", target_address)))?;
+    let port = port.parse::<u16>()
+        .map_err(|_| ProxyError::Configuration(format!("\"{}\" is not a port number.", port)))?;
+    Ok((host.trim_start_matches('[').trim_end_matches(']'), port))
+}
+
+fn get_socks5_reply_message(reply: u8) -> String {
+    let message = match reply {
+        0x01 => "general failure",
+        0x02 => "the connection is not allowed by the ruleset",
+        0x03 => "the network is unreachable",
+        0x04 => "the host is unreachable",
+        0x05 => "the connection was refused",
+        0x06 => "the TTL expired",
+        0x07 => "the command is not supported",
+        0x08 => "the address type is not supported",
+        _ => "unknown failure"
+    };
+    format!("{} (reply {})", message, reply)
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = (u32::from(chunk[0]) << 16) | (u32::from(*chunk.get(1).unwrap_or(&0)) << 8) | u32::from(*chunk.get(2).unwrap_or(&0));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(group >> (18 - index * 6)) as usize & 0x3F] as char);
+            }
+            else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Shutdown, TcpListener};
+
+    use crate::{client_interface::*, host_simulator::*, native_client::NativeCommandExecutor};
+
+    use super::*;
+
+    const USERNAME: &str = "robot";
+    const PASSWORD: &str = "secret";
+
+    // a proxy that tunnels a single connection, requiring USERNAME and PASSWORD when is_authenticated is set
+    fn start_proxy_stand_in(kind: ProxyKind, is_authenticated: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            let target_address = match kind {
+                ProxyKind::Socks5 => accept_socks5(&mut client, is_authenticated),
+                ProxyKind::Http => accept_http(&mut client, is_authenticated)
+            };
+            if let Some(target_address) = target_address {
+                relay(client, TcpStream::connect(target_address).unwrap());
+            }
+        });
+        address
+    }
+
+    fn read_bytes(stream: &mut TcpStream, length: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; length];
+        stream.read_exact(&mut bytes).unwrap();
+        bytes
+    }
+
+    fn accept_socks5(client: &mut TcpStream, is_authenticated: bool) -> Option<String> {
+        let greeting = read_bytes(client, 2);
+        let methods = read_bytes(client, greeting[1] as usize);
+        if is_authenticated {
+            if !methods.contains(&SOCKS_METHOD_USERNAME_PASSWORD) {
+                client.write_all(&[SOCKS_VERSION, SOCKS_METHOD_NONE_ACCEPTABLE]).unwrap();
+                return None;
+            }
+            client.write_all(&[SOCKS_VERSION, SOCKS_METHOD_USERNAME_PASSWORD]).unwrap();
+            let username_length = read_bytes(client, 2)[1] as usize;
+            let username = read_bytes(client, username_length);
+            let password_length = read_bytes(client, 1)[0] as usize;
+            let password = read_bytes(client, password_length);
+            if username != USERNAME.as_bytes() || password != PASSWORD.as_bytes() {
+                client.write_all(&[SOCKS_USERNAME_PASSWORD_VERSION, 0x01]).unwrap();
+                return None;
+            }
+            client.write_all(&[SOCKS_USERNAME_PASSWORD_VERSION, 0x00]).unwrap();
+        }
+        else {
+            client.write_all(&[SOCKS_VERSION, SOCKS_METHOD_NO_AUTHENTICATION]).unwrap();
+        }
+        let request = read_bytes(client, 4);
+        let host = match request[3] {
+            SOCKS_ADDRESS_IPV4 => {
+                read_bytes(client, 4).iter().map(|octet| octet.to_string()).collect::<Vec<String>>().join(".")
+            },
+            SOCKS_ADDRESS_DOMAIN_NAME => {
+                let length = read_bytes(client, 1)[0] as usize;
+                String::from_utf8(read_bytes(client, length)).unwrap()
+            },
+            address_type => {
+                panic!("unexpected address type {}", address_type);
+            }
+        };
+        let port = read_bytes(client, 2);
+        client.write_all(&[SOCKS_VERSION, SOCKS_REPLY_SUCCEEDED, 0x00, SOCKS_ADDRESS_IPV4, 0, 0, 0, 0, 0, 0]).unwrap();
+        Some(format!("{}:{}", host, u16::from_be_bytes([port[0], port[1]])))
+    }
+
+    fn accept_http(client: &mut TcpStream, is_authenticated: bool) -> Option<String> {
+        let mut request = Vec::new();
+        while !request.ends_with(b"\r\n\r\n") {
+            request.extend(read_bytes(client, 1));
+        }
+        let request = String::from_utf8(request).unwrap();
+        let authorization = format!("Proxy-Authorization: Basic {}", encode_base64(format!("{USERNAME}:{PASSWORD}").as_bytes()));
+        if is_authenticated && !request.lines().any(|line| line == authorization) {
+            client.write_all(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n").unwrap();
+            return None;
+        }
+        client.write_all(b"HTTP/1.1 200 Connection established\r\n\r\n").unwrap();
+        let target_address = request.split_whitespace().nth(1).unwrap();
+        Some(String::from(target_address))
+    }
+
+    fn relay(client: TcpStream, target: TcpStream) {
+        let (mut client_reader, mut target_writer) = (client.try_clone().unwrap(), target.try_clone().unwrap());
+        std::thread::spawn(move || {
+            let _ = std::io::copy(&mut client_reader, &mut target_writer);
+            let _ = target_writer.shutdown(Shutdown::Both);
+        });
+        let (mut target_reader, mut client_writer) = (target, client);
+        let _ = std::io::copy(&mut target_reader, &mut client_writer);
+        let _ = client_writer.shutdown(Shutdown::Both);
+    }
+
+    fn start_host_simulator() -> HostSimulator {
+        let mut screen = SimulatedScreen::new("banner");
+        screen.add_field(0, 0, true, "WELCOME");
+        HostSimulator::start(HostScript::new(vec![screen])).unwrap()
+    }
+
+    fn connect_through(host_simulator: &HostSimulator, proxy_configuration: ProxyConfiguration) -> std::io::Result<NativeCommandExecutor> {
+        let terminal_configuration = TerminalConfiguration::builder(host_simulator.get_address())
+            .with_proxy(proxy_configuration)
+            .build()
+            .unwrap();
+        NativeCommandExecutor::connect(&terminal_configuration)
+    }
+
+    #[test]
+    fn tunnel_through_socks5_with_credentials() {
+        let host_simulator = start_host_simulator();
+        let mut proxy_configuration = ProxyConfiguration::new(ProxyKind::Socks5, &start_proxy_stand_in(ProxyKind::Socks5, true));
+        proxy_configuration.username = Some(String::from(USERNAME));
+        proxy_configuration.password = Some(String::from(PASSWORD));
+
+        let mut command_executor = connect_through(&host_simulator, proxy_configuration).unwrap();
+        command_executor.execute(WaitCommand::new(WaitCondition::Output)).unwrap();
+        assert_eq!("WELCOME", command_executor.execute(GetTextCommand::new(0, 1, 7)).unwrap());
+    }
+
+    #[test]
+    fn tunnel_through_http_connect() {
+        let host_simulator = start_host_simulator();
+        let proxy_configuration = ProxyConfiguration::new(ProxyKind::Http, &start_proxy_stand_in(ProxyKind::Http, false));
+
+        let mut command_executor = connect_through(&host_simulator, proxy_configuration).unwrap();
+        command_executor.execute(WaitCommand::new(WaitCondition::Output)).unwrap();
+        assert_eq!("WELCOME", command_executor.execute(GetTextCommand::new(0, 1, 7)).unwrap());
+    }
+
+    #[test]
+    fn report_proxy_failures() {
+        let host_simulator = start_host_simulator();
+        let mut proxy_configuration = ProxyConfiguration::new(ProxyKind::Socks5, &start_proxy_stand_in(ProxyKind::Socks5, true));
+        proxy_configuration.username = Some(String::from(USERNAME));
+        proxy_configuration.password = Some(String::from("wrong"));
+        let error = connect_through(&host_simulator, proxy_configuration).err().unwrap();
+        assert!(matches!(ProxyError::from_io_error(&error), Some(ProxyError::Authentication(_))));
+
+        let proxy_configuration = ProxyConfiguration::new(ProxyKind::Http, &start_proxy_stand_in(ProxyKind::Http, true));
+        let error = connect_through(&host_simulator, proxy_configuration).err().unwrap();
+        assert!(matches!(ProxyError::from_io_error(&error), Some(ProxyError::Authentication(_))));
+
+        // nothing listens on a port once its listener is dropped
+        let closed_address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let error = connect_through(&host_simulator, ProxyConfiguration::new(ProxyKind::Http, &closed_address)).err().unwrap();
+        assert!(matches!(ProxyError::from_io_error(&error), Some(ProxyError::Unreachable(_))));
+    }
+
+    #[test]
+    fn parse_x3270_proxy() {
+        let proxy_configuration = ProxyConfiguration::parse("http:robot:secret@proxy.example.com").unwrap();
+        assert_eq!(ProxyKind::Http, proxy_configuration.kind);
+        assert_eq!("proxy.example.com:3128", proxy_configuration.address);
+        assert_eq!(Some("secret"), proxy_configuration.password.as_deref());
+        assert_eq!("http:robot:secret@proxy.example.com:3128", proxy_configuration.get_x3270_argument());
+        assert_eq!("socks5:[::1]:1080", ProxyConfiguration::parse("socks5:[::1]").unwrap().get_x3270_argument());
+        assert!(matches!(ProxyConfiguration::parse("telnet:proxy.example.com"), Err(ProxyError::Configuration(_))));
+        assert_eq!("cm9ib3Q6c2VjcmV0", encode_base64(b"robot:secret"));
+        assert_eq!("YWI=", encode_base64(b"ab"));
+        assert!(!format!("{:?}", proxy_configuration).contains("secret"));
+    }
+}
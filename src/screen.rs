@@ -0,0 +1,981 @@
+#![allow(dead_code)]
+
+use crate::{code_page::*, data_stream::*, field::READ_BUFFER_FIELD_ATTRIBUTE};
+
+pub const FIELD_ATTRIBUTE_PROTECTED: u8 = 0x20;
+pub const FIELD_ATTRIBUTE_NUMERIC: u8 = 0x10;
+pub const FIELD_ATTRIBUTE_DISPLAY_MASK: u8 = 0x0C;
+pub const FIELD_ATTRIBUTE_NON_DISPLAY: u8 = 0x0C;
+pub const FIELD_ATTRIBUTE_MODIFIED: u8 = 0x01;
+
+pub const AID_NONE: u8 = 0x60;
+pub const AID_ENTER: u8 = 0x7D;
+pub const AID_CLEAR: u8 = 0x6D;
+pub const AID_PA1: u8 = 0x6C;
+pub const AID_PA2: u8 = 0x6E;
+pub const AID_PA3: u8 = 0x6B;
+pub const AID_STRUCTURED_FIELD: u8 = 0x88;
+pub const AID_SYSTEM_REQUEST: u8 = 0xF0;
+const AID_PROGRAM_FUNCTION: [u8; 24] = [
+    0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8, 0xF9, 0x7A, 0x7B, 0x7C,
+    0xC1, 0xC2, 0xC3, 0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0x4A, 0x4B, 0x4C
+];
+
+const CHARACTER_DUP: u8 = 0x1C;
+const CHARACTER_FIELD_MARK: u8 = 0x1E;
+// the designator characters of a selector pen field, before and after it is selected
+const DESIGNATOR_UNSELECTED: u8 = 0x6F;
+const DESIGNATOR_SELECTED: u8 = 0x6E;
+
+// every model starts at this size and keeps it for Erase/Write
+pub const DEFAULT_SCREEN_ROWS: usize = 24;
+pub const DEFAULT_SCREEN_COLUMNS: usize = 80;
+// the largest buffer that 14-bit addresses reach
+pub const MAXIMUM_BUFFER_SIZE: usize = 0x4000;
+
+// the 3278 and 3279 models, which differ in the alternate size the host switches to with Erase/Write Alternate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenModel {
+    // 24x80
+    Model2,
+    // 32x80
+    Model3,
+    // 43x80
+    Model4,
+    // 27x132
+    Model5,
+    // a model 2 with a larger alternate size, as x3270's -oversize gives it
+    Oversize {
+        rows: usize,
+        columns: usize
+    }
+}
+
+impl ScreenModel {
+    pub fn from_alternate_size(rows: usize, columns: usize) -> ScreenModel {
+        match (rows, columns) {
+            (DEFAULT_SCREEN_ROWS, DEFAULT_SCREEN_COLUMNS) => ScreenModel::Model2,
+            (32, 80) => ScreenModel::Model3,
+            (43, 80) => ScreenModel::Model4,
+            (27, 132) => ScreenModel::Model5,
+            (rows, columns) => ScreenModel::Oversize { rows, columns }
+        }
+    }
+    pub fn get_number(&self) -> u8 {
+        match self {
+            ScreenModel::Model2 | ScreenModel::Oversize { .. } => 2,
+            ScreenModel::Model3 => 3,
+            ScreenModel::Model4 => 4,
+            ScreenModel::Model5 => 5
+        }
+    }
+    // (rows, columns)
+    pub fn get_alternate_size(&self) -> (usize, usize) {
+        match self {
+            ScreenModel::Model2 => (DEFAULT_SCREEN_ROWS, DEFAULT_SCREEN_COLUMNS),
+            ScreenModel::Model3 => (32, 80),
+            ScreenModel::Model4 => (43, 80),
+            ScreenModel::Model5 => (27, 132),
+            ScreenModel::Oversize { rows, columns } => (*rows, *columns)
+        }
+    }
+    // an oversize screen must cover the default size and fit in 14-bit addresses
+    pub fn validate(&self) -> Result<(), String> {
+        let (rows, columns) = self.get_alternate_size();
+        if rows < DEFAULT_SCREEN_ROWS || columns < DEFAULT_SCREEN_COLUMNS {
+            return Err(format!("The {rows}x{columns} screen is smaller than {DEFAULT_SCREEN_ROWS}x{DEFAULT_SCREEN_COLUMNS}."));
+        }
+        if rows * columns > MAXIMUM_BUFFER_SIZE {
+            return Err(format!("The {rows}x{columns} screen has more than {MAXIMUM_BUFFER_SIZE} positions."));
+        }
+        Ok(())
+    }
+}
+
+// the AID of PF1 through PF24
+pub fn get_program_function_aid(number: u8) -> Option<u8> {
+    AID_PROGRAM_FUNCTION.get((number as usize).checked_sub(1)?)
+        .cloned()
+}
+
+// the terminal-side presentation space: one EBCDIC character or field attribute per buffer position
+pub struct Screen {
+    // the current size, which is either the default or the alternate one
+    rows: usize,
+    columns: usize,
+    default_size: (usize, usize),
+    alternate_size: (usize, usize),
+    characters: Vec<u8>,
+    field_attributes: Vec<Option<u8>>,
+    // the foreground color and highlighting of the field whose attribute is at each position, where 0 is the default
+    field_extended_attributes: Vec<(u8, u8)>,
+    cursor_address: usize,
+    is_keyboard_locked: bool,
+    is_insert_mode: bool,
+    decoder: DataStreamDecoder,
+    code_page: CodePage
+}
+
+impl Screen {
+    // a screen that keeps one size, whichever erase the host uses
+    pub fn new(rows: usize, columns: usize) -> Self {
+        Screen {
+            rows,
+            columns,
+            default_size: (rows, columns),
+            alternate_size: (rows, columns),
+            characters: vec![0; rows * columns],
+            field_attributes: vec![None; rows * columns],
+            field_extended_attributes: vec![(0, 0); rows * columns],
+            cursor_address: 0,
+            is_keyboard_locked: false,
+            is_insert_mode: false,
+            decoder: DataStreamDecoder::new(),
+            code_page: CodePage::Cp037
+        }
+    }
+    // starts at the default size until the host erases to the alternate one
+    pub fn for_model(screen_model: ScreenModel) -> Self {
+        let mut screen = Screen::new(DEFAULT_SCREEN_ROWS, DEFAULT_SCREEN_COLUMNS);
+        screen.alternate_size = screen_model.get_alternate_size();
+        screen
+    }
+    pub fn get_model(&self) -> ScreenModel {
+        ScreenModel::from_alternate_size(self.alternate_size.0, self.alternate_size.1)
+    }
+    // the alternate size as (rows, columns), which is never smaller than the default one
+    pub fn get_maximum_size(&self) -> (usize, usize) {
+        self.alternate_size
+    }
+    pub fn is_alternate_size(&self) -> bool {
+        (self.rows, self.columns) == self.alternate_size && self.alternate_size != self.default_size
+    }
+    // erases the screen, switching to the alternate size or back to the default one
+    pub fn erase_to_size(&mut self, is_alternate: bool) {
+        let (rows, columns) = if is_alternate { self.alternate_size } else { self.default_size };
+        self.rows = rows;
+        self.columns = columns;
+        self.characters = vec![0; rows * columns];
+        self.field_attributes = vec![None; rows * columns];
+        self.field_extended_attributes = vec![(0, 0); rows * columns];
+        self.cursor_address = 0;
+    }
+    pub fn get_rows(&self) -> usize {
+        self.rows
+    }
+    pub fn get_columns(&self) -> usize {
+        self.columns
+    }
+    fn get_size(&self) -> usize {
+        self.rows * self.columns
+    }
+    pub fn get_code_page(&self) -> CodePage {
+        self.code_page
+    }
+    pub fn set_code_page(&mut self, code_page: CodePage) {
+        self.code_page = code_page;
+    }
+    pub fn is_keyboard_locked(&self) -> bool {
+        self.is_keyboard_locked
+    }
+    pub fn set_keyboard_locked(&mut self, is_keyboard_locked: bool) {
+        self.is_keyboard_locked = is_keyboard_locked;
+    }
+    pub fn is_insert_mode(&self) -> bool {
+        self.is_insert_mode
+    }
+    pub fn set_insert_mode(&mut self, is_insert_mode: bool) {
+        self.is_insert_mode = is_insert_mode;
+    }
+    pub fn is_formatted(&self) -> bool {
+        self.field_attributes.iter().any(|field_attribute| field_attribute.is_some())
+    }
+    pub fn is_cursor_protected(&self) -> bool {
+        self.is_protected_address(self.cursor_address)
+    }
+    pub fn get_cursor(&self) -> (usize, usize) {
+        (self.cursor_address / self.columns, self.cursor_address % self.columns)
+    }
+    pub fn move_cursor(&mut self, row: usize, column: usize) -> Result<(), String> {
+        if row >= self.rows || column >= self.columns {
+            return Err(format!("Position ({row},{column}) is outside of the {}x{} screen.", self.rows, self.columns));
+        }
+        self.cursor_address = row * self.columns + column;
+        Ok(())
+    }
+    // as the Clear key does, which also returns the screen to its default size
+    pub fn erase(&mut self) {
+        self.erase_to_size(false);
+    }
+
+    // host to terminal
+
+    // applies an outbound record, returning the inbound record when the host requested a read
+    pub fn process_record(&mut self, record: &[u8]) -> Option<Vec<u8>> {
+        match self.decoder.decode(record) {
+            Ok(events) => {
+                self.apply_events(&events)
+            },
+            Err(error) => {
+                tracing::warn!(?error, "unable to decode record");
+                None
+            }
+        }
+    }
+    pub fn apply_events(&mut self, events: &[DataStreamEvent]) -> Option<Vec<u8>> {
+        let mut size = self.get_size();
+        let mut address = self.cursor_address;
+        let mut inbound = None;
+        let mut write_control_character: Option<WriteControlCharacter> = None;
+        for event in events.iter() {
+            match event {
+                DataStreamEvent::Write(wcc) => {
+                    if wcc.is_reset_modified() {
+                        self.reset_modified_flags();
+                    }
+                    address = self.cursor_address;
+                    write_control_character = Some(*wcc);
+                },
+                DataStreamEvent::EraseWrite(wcc) => {
+                    self.erase_to_size(false);
+                    size = self.get_size();
+                    address = 0;
+                    write_control_character = Some(*wcc);
+                },
+                DataStreamEvent::EraseWriteAlternate(wcc) => {
+                    self.erase_to_size(true);
+                    size = self.get_size();
+                    address = 0;
+                    write_control_character = Some(*wcc);
+                },
+                DataStreamEvent::EraseAllUnprotected => {
+                    self.erase_all_unprotected();
+                },
+                DataStreamEvent::ReadBuffer => {
+                    inbound = Some(self.read_buffer(AID_NONE));
+                },
+                DataStreamEvent::ReadModified => {
+                    inbound = Some(self.read_modified(AID_NONE));
+                },
+                DataStreamEvent::ReadModifiedAll => {
+                    inbound = Some(self.read_modified_all(AID_NONE));
+                },
+                DataStreamEvent::StructuredField(structured_field) => {
+                    match structured_field {
+                        StructuredField::EraseReset { is_alternate } => {
+                            self.erase_to_size(*is_alternate);
+                            size = self.get_size();
+                            address = 0;
+                        },
+                        StructuredField::Outbound3270Ds { events, .. } => {
+                            if let Some(nested_inbound) = self.apply_events(events) {
+                                inbound = Some(nested_inbound);
+                            }
+                            size = self.get_size();
+                            address = self.cursor_address;
+                        },
+                        StructuredField::ReadPartition { read_type: READ_PARTITION_QUERY | READ_PARTITION_QUERY_LIST, .. } => {
+                            inbound = Some(self.get_query_reply());
+                        },
+                        _ => {
+                            // other reads and reply modes are not interpreted
+                        }
+                    }
+                },
+                DataStreamEvent::SetBufferAddress(buffer_address) => {
+                    address = buffer_address % size;
+                },
+                DataStreamEvent::StartField(field_attribute) => {
+                    self.set_field_attribute(address, *field_attribute);
+                    address = (address + 1) % size;
+                },
+                DataStreamEvent::StartFieldExtended(pairs) => {
+                    let field_attribute = pairs
+                        .iter()
+                        .find(|(attribute_type, _)| *attribute_type == EXTENDED_ATTRIBUTE_FIELD)
+                        .map(|(_, value)| *value)
+                        .unwrap_or(0);
+                    self.set_field_attribute(address, field_attribute);
+                    self.set_field_extended_attributes(address, pairs);
+                    address = (address + 1) % size;
+                },
+                DataStreamEvent::SetAttribute(_, _) => {
+                    // character attributes are not tracked
+                },
+                DataStreamEvent::ModifyField(pairs) => {
+                    if self.field_attributes[address].is_some() {
+                        if let Some((_, value)) = pairs.iter().find(|(attribute_type, _)| *attribute_type == EXTENDED_ATTRIBUTE_FIELD) {
+                            self.field_attributes[address] = Some(value & 0x3F);
+                        }
+                        self.set_field_extended_attributes(address, pairs);
+                        address = (address + 1) % size;
+                    }
+                },
+                DataStreamEvent::InsertCursor => {
+                    self.cursor_address = address;
+                },
+                DataStreamEvent::ProgramTab => {
+                    address = self.get_next_unprotected_field_start(address).unwrap_or(0);
+                },
+                DataStreamEvent::RepeatToAddress { address: stop_address, character, .. } => {
+                    let stop_address = stop_address % size;
+                    loop {
+                        self.set_character(address, *character);
+                        address = (address + 1) % size;
+                        if address == stop_address {
+                            break;
+                        }
+                    }
+                },
+                DataStreamEvent::EraseUnprotectedToAddress(stop_address) => {
+                    let stop_address = stop_address % size;
+                    loop {
+                        if !self.is_protected_address(address) {
+                            self.characters[address] = 0;
+                        }
+                        address = (address + 1) % size;
+                        if address == stop_address {
+                            break;
+                        }
+                    }
+                },
+                DataStreamEvent::GraphicEscape(character) => {
+                    self.set_character(address, *character);
+                    address = (address + 1) % size;
+                },
+                DataStreamEvent::Text(characters) => {
+                    for character in characters.iter() {
+                        self.set_character(address, *character);
+                        address = (address + 1) % size;
+                    }
+                }
+            }
+        }
+        if let Some(write_control_character) = write_control_character {
+            if write_control_character.is_keyboard_restore() {
+                self.is_keyboard_locked = false;
+            }
+        }
+        inbound
+    }
+    fn erase_all_unprotected(&mut self) {
+        for address in 0..self.get_size() {
+            if !self.is_protected_address(address) {
+                self.characters[address] = 0;
+            }
+        }
+        self.reset_modified_flags();
+        self.cursor_address = self.get_next_unprotected_field_start(self.get_size() - 1).unwrap_or(0);
+        self.is_keyboard_locked = false;
+    }
+    fn set_field_attribute(&mut self, address: usize, field_attribute: u8) {
+        self.field_attributes[address] = Some(field_attribute & 0x3F);
+        self.field_extended_attributes[address] = (0, 0);
+        self.characters[address] = 0;
+    }
+    fn set_field_extended_attributes(&mut self, address: usize, pairs: &[(u8, u8)]) {
+        for (attribute_type, value) in pairs.iter() {
+            match *attribute_type {
+                EXTENDED_ATTRIBUTE_FOREGROUND_COLOR => {
+                    self.field_extended_attributes[address].0 = *value;
+                },
+                EXTENDED_ATTRIBUTE_HIGHLIGHTING => {
+                    self.field_extended_attributes[address].1 = *value;
+                },
+                _ => {
+                    // other extended attributes are not tracked
+                }
+            }
+        }
+    }
+    fn set_character(&mut self, address: usize, character: u8) {
+        self.field_attributes[address] = None;
+        self.characters[address] = character;
+    }
+    fn reset_modified_flags(&mut self) {
+        for field_attribute in self.field_attributes.iter_mut().flatten() {
+            *field_attribute &= !FIELD_ATTRIBUTE_MODIFIED;
+        }
+    }
+
+    // fields
+
+    fn get_field_attribute_address(&self, address: usize) -> Option<usize> {
+        let size = self.get_size();
+        (0..size)
+            .map(|offset| (address + size - offset) % size)
+            .find(|field_attribute_address| self.field_attributes[*field_attribute_address].is_some())
+    }
+    fn is_protected_address(&self, address: usize) -> bool {
+        if self.field_attributes[address].is_some() {
+            return true;
+        }
+        match self.get_field_attribute_address(address) {
+            Some(field_attribute_address) => {
+                self.field_attributes[field_attribute_address].unwrap() & FIELD_ATTRIBUTE_PROTECTED != 0
+            },
+            None => {
+                false
+            }
+        }
+    }
+    fn is_non_display_address(&self, address: usize) -> bool {
+        match self.get_field_attribute_address(address) {
+            Some(field_attribute_address) => {
+                self.field_attributes[field_attribute_address].unwrap() & FIELD_ATTRIBUTE_DISPLAY_MASK == FIELD_ATTRIBUTE_NON_DISPLAY
+            },
+            None => {
+                false
+            }
+        }
+    }
+    // the first data position of every unprotected field, in buffer order
+    fn get_unprotected_field_starts(&self) -> Vec<usize> {
+        let size = self.get_size();
+        (0..size)
+            .filter(|address| {
+                self.field_attributes[*address]
+                    .map(|field_attribute| field_attribute & FIELD_ATTRIBUTE_PROTECTED == 0)
+                    .unwrap_or(false)
+            })
+            .map(|address| (address + 1) % size)
+            .filter(|address| self.field_attributes[*address].is_none())
+            .collect()
+    }
+    fn get_next_unprotected_field_start(&self, address: usize) -> Option<usize> {
+        let field_starts = self.get_unprotected_field_starts();
+        field_starts
+            .iter()
+            .find(|field_start| **field_start > address)
+            .or(field_starts.first())
+            .cloned()
+    }
+    // the bounds of the field containing the address as (first data position, length)
+    fn get_field_bounds(&self, address: usize) -> Option<(usize, usize)> {
+        let size = self.get_size();
+        let field_attribute_address = self.get_field_attribute_address(address)?;
+        let start = (field_attribute_address + 1) % size;
+        let mut length = 0;
+        while self.field_attributes[(start + length) % size].is_none() {
+            length += 1;
+        }
+        Some((start, length))
+    }
+    // the bounds of the input field at the address, where an unformatted screen is one field covering the buffer
+    fn get_input_field_bounds(&self, address: usize) -> Result<(usize, usize), String> {
+        if self.is_protected_address(address) {
+            return Err(String::from("The cursor is in a protected field."));
+        }
+        Ok(self.get_field_bounds(address).unwrap_or((0, self.get_size())))
+    }
+    fn set_modified_flag(&mut self, address: usize) {
+        if let Some(field_attribute_address) = self.get_field_attribute_address(address) {
+            if let Some(field_attribute) = self.field_attributes[field_attribute_address].as_mut() {
+                *field_attribute |= FIELD_ATTRIBUTE_MODIFIED;
+            }
+        }
+    }
+
+    // keyboard actions
+
+    pub fn tab(&mut self) {
+        self.cursor_address = self.get_next_unprotected_field_start(self.cursor_address).unwrap_or(0);
+    }
+    pub fn back_tab(&mut self) {
+        let field_starts = self.get_unprotected_field_starts();
+        self.cursor_address = field_starts
+            .iter()
+            .rev()
+            .find(|field_start| **field_start < self.cursor_address)
+            .or(field_starts.last())
+            .cloned()
+            .unwrap_or(0);
+    }
+    pub fn home(&mut self) {
+        self.cursor_address = self.get_unprotected_field_starts()
+            .first()
+            .cloned()
+            .unwrap_or(0);
+    }
+    pub fn field_end(&mut self) -> Result<(), String> {
+        if self.is_protected_address(self.cursor_address) {
+            return Err(String::from("The cursor is not in an unprotected field."));
+        }
+        if let Some((start, length)) = self.get_field_bounds(self.cursor_address) {
+            let size = self.get_size();
+            let data_length = (0..length)
+                .rev()
+                .find(|offset| {
+                    let character = self.characters[(start + offset) % size];
+                    character != 0 && character != 0x40
+                })
+                .map(|offset| (offset + 1).min(length - 1))
+                .unwrap_or(0);
+            self.cursor_address = (start + data_length) % size;
+        }
+        Ok(())
+    }
+    pub fn delete_field(&mut self) -> Result<(), String> {
+        if self.is_protected_address(self.cursor_address) {
+            return Err(String::from("The cursor is not in an unprotected field."));
+        }
+        if let Some((start, length)) = self.get_field_bounds(self.cursor_address) {
+            let size = self.get_size();
+            for offset in 0..length {
+                self.characters[(start + offset) % size] = 0;
+            }
+            self.set_modified_flag(start);
+            self.cursor_address = start;
+        }
+        Ok(())
+    }
+    pub fn type_character(&mut self, character: u8) -> Result<(), String> {
+        if self.is_keyboard_locked {
+            return Err(String::from("Keyboard locked"));
+        }
+        if self.is_insert_mode {
+            // the characters after the cursor move right into a trailing null
+            let (start, length) = self.get_input_field_bounds(self.cursor_address)?;
+            let size = self.get_size();
+            let offset = (self.cursor_address + size - start) % size;
+            if self.characters[(start + length - 1) % size] != 0 {
+                return Err(String::from("There is no room to insert into the field."));
+            }
+            for current_offset in (offset + 1..length).rev() {
+                self.characters[(start + current_offset) % size] = self.characters[(start + current_offset - 1) % size];
+            }
+        }
+        else if self.is_protected_address(self.cursor_address) {
+            return Err(String::from("The cursor is in a protected field."));
+        }
+        self.characters[self.cursor_address] = character;
+        self.set_modified_flag(self.cursor_address);
+        self.cursor_address = (self.cursor_address + 1) % self.get_size();
+        if self.field_attributes[self.cursor_address].is_some() {
+            // the end of the field skips ahead to the next input field
+            self.tab();
+        }
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.is_keyboard_locked = false;
+        self.is_insert_mode = false;
+    }
+    // moves the cursor by a number of positions, wrapping around the buffer
+    pub fn move_cursor_by(&mut self, offset: isize) {
+        let size = self.get_size() as isize;
+        self.cursor_address = (self.cursor_address as isize + offset).rem_euclid(size) as usize;
+    }
+    pub fn move_cursor_up(&mut self) {
+        self.move_cursor_by(-(self.columns as isize));
+    }
+    pub fn move_cursor_down(&mut self) {
+        self.move_cursor_by(self.columns as isize);
+    }
+    // the first input position from the start of the next row
+    pub fn new_line(&mut self) {
+        let size = self.get_size();
+        let (row, _) = self.get_cursor();
+        let line_start = (row + 1) % self.rows * self.columns;
+        self.cursor_address = (0..size)
+            .map(|offset| (line_start + offset) % size)
+            .find(|address| !self.is_protected_address(*address))
+            .unwrap_or(0);
+    }
+    // removes the character at the cursor, moving the rest of the field left
+    pub fn delete_character(&mut self) -> Result<(), String> {
+        let (start, length) = self.get_input_field_bounds(self.cursor_address)?;
+        let size = self.get_size();
+        let offset = (self.cursor_address + size - start) % size;
+        for current_offset in offset..length - 1 {
+            self.characters[(start + current_offset) % size] = self.characters[(start + current_offset + 1) % size];
+        }
+        self.characters[(start + length - 1) % size] = 0;
+        self.set_modified_flag(self.cursor_address);
+        Ok(())
+    }
+    // moves back one position and deletes the character there, doing nothing at the start of a field
+    pub fn erase_previous_character(&mut self) -> Result<(), String> {
+        let (start, _) = self.get_input_field_bounds(self.cursor_address)?;
+        if self.cursor_address == start {
+            return Ok(());
+        }
+        self.move_cursor_by(-1);
+        self.delete_character()
+    }
+    pub fn erase_end_of_field(&mut self) -> Result<(), String> {
+        let (start, length) = self.get_input_field_bounds(self.cursor_address)?;
+        let size = self.get_size();
+        let offset = (self.cursor_address + size - start) % size;
+        for current_offset in offset..length {
+            self.characters[(start + current_offset) % size] = 0;
+        }
+        self.set_modified_flag(self.cursor_address);
+        Ok(())
+    }
+    // clears every input field and moves the cursor to the first of them
+    pub fn erase_input(&mut self) {
+        let is_keyboard_locked = self.is_keyboard_locked;
+        self.erase_all_unprotected();
+        self.is_keyboard_locked = is_keyboard_locked;
+    }
+    // types the DUP character, then moves to the next input field
+    pub fn duplicate(&mut self) -> Result<(), String> {
+        self.type_character(CHARACTER_DUP)?;
+        self.tab();
+        Ok(())
+    }
+    pub fn field_mark(&mut self) -> Result<(), String> {
+        self.type_character(CHARACTER_FIELD_MARK)
+    }
+    // toggles a selector pen field between its "?" and ">" designators, marking it modified once selected
+    pub fn cursor_select(&mut self) -> Result<(), String> {
+        let size = self.get_size();
+        let field_attribute_address = self.get_field_attribute_address(self.cursor_address)
+            .ok_or_else(|| String::from("The screen has no fields to select."))?;
+        let designator_address = (field_attribute_address + 1) % size;
+        let designator = match self.characters[designator_address] {
+            DESIGNATOR_UNSELECTED => {
+                DESIGNATOR_SELECTED
+            },
+            DESIGNATOR_SELECTED => {
+                DESIGNATOR_UNSELECTED
+            },
+            _ => {
+                return Err(String::from("The cursor is not in a selectable field."));
+            }
+        };
+        self.characters[designator_address] = designator;
+        if let Some(field_attribute) = self.field_attributes[field_attribute_address].as_mut() {
+            if designator == DESIGNATOR_SELECTED {
+                *field_attribute |= FIELD_ATTRIBUTE_MODIFIED;
+            }
+            else {
+                *field_attribute &= !FIELD_ATTRIBUTE_MODIFIED;
+            }
+        }
+        Ok(())
+    }
+
+    // terminal to host
+
+    pub fn read_modified(&self, aid: u8) -> Vec<u8> {
+        let mut inbound = vec![aid];
+        if aid == AID_CLEAR || aid == AID_PA1 || aid == AID_PA2 || aid == AID_PA3 || aid == AID_SYSTEM_REQUEST {
+            // short read
+            return inbound;
+        }
+        inbound.extend(encode_address(self.cursor_address));
+        self.append_modified_fields(&mut inbound);
+        inbound
+    }
+    fn read_modified_all(&self, aid: u8) -> Vec<u8> {
+        let mut inbound = vec![aid];
+        inbound.extend(encode_address(self.cursor_address));
+        self.append_modified_fields(&mut inbound);
+        inbound
+    }
+    fn append_modified_fields(&self, inbound: &mut Vec<u8>) {
+        let size = self.get_size();
+        if !self.is_formatted() {
+            inbound.extend(self.characters.iter().filter(|character| **character != 0));
+            return;
+        }
+        for address in 0..size {
+            if let Some(field_attribute) = self.field_attributes[address] {
+                if field_attribute & FIELD_ATTRIBUTE_MODIFIED != 0 {
+                    let start = (address + 1) % size;
+                    inbound.push(ORDER_SET_BUFFER_ADDRESS);
+                    inbound.extend(encode_address(start));
+                    let mut field_address = start;
+                    while self.field_attributes[field_address].is_none() {
+                        if self.characters[field_address] != 0 {
+                            inbound.push(self.characters[field_address]);
+                        }
+                        field_address = (field_address + 1) % size;
+                    }
+                }
+            }
+        }
+    }
+    // the query replies that extended (-E) terminal types owe the host when it asks what the terminal supports
+    fn get_query_reply(&self) -> Vec<u8> {
+        let columns = (self.alternate_size.1 as u16).to_be_bytes();
+        let rows = (self.alternate_size.0 as u16).to_be_bytes();
+        let buffer_size = ((self.alternate_size.0 * self.alternate_size.1) as u16).to_be_bytes();
+        let default_columns = (self.default_size.1 as u16).to_be_bytes();
+        let default_rows = (self.default_size.0 as u16).to_be_bytes();
+        let query_replies: [Vec<u8>; 3] = [
+            vec![QUERY_CODE_SUMMARY, QUERY_CODE_USABLE_AREA, QUERY_CODE_IMPLICIT_PARTITION],
+            vec![
+                0x01, 0x00,
+                columns[0], columns[1], rows[0], rows[1],
+                0x01, 0x00, 0x0A, 0x02, 0xE5, 0x00, 0x02, 0x00, 0x6F, 0x09, 0x0C,
+                buffer_size[0], buffer_size[1]
+            ],
+            vec![
+                0x00, 0x00, 0x0B, 0x01, 0x00,
+                default_columns[0], default_columns[1], default_rows[0], default_rows[1],
+                columns[0], columns[1], rows[0], rows[1]
+            ]
+        ];
+        let query_codes = [QUERY_CODE_SUMMARY, QUERY_CODE_USABLE_AREA, QUERY_CODE_IMPLICIT_PARTITION];
+        let mut inbound = vec![AID_STRUCTURED_FIELD];
+        for (query_code, query_reply) in query_codes.iter().zip(query_replies.iter()) {
+            let length = ((query_reply.len() + 4) as u16).to_be_bytes();
+            inbound.extend([length[0], length[1], STRUCTURED_FIELD_QUERY_REPLY, *query_code]);
+            inbound.extend(query_reply);
+        }
+        inbound
+    }
+    pub fn read_buffer(&self, aid: u8) -> Vec<u8> {
+        let mut inbound = vec![aid];
+        inbound.extend(encode_address(self.cursor_address));
+        for address in 0..self.get_size() {
+            match self.field_attributes[address] {
+                Some(field_attribute) => {
+                    inbound.push(ORDER_START_FIELD);
+                    inbound.push(ADDRESS_CODES[field_attribute as usize & 0x3F]);
+                },
+                None => {
+                    inbound.push(self.characters[address]);
+                }
+            }
+        }
+        inbound
+    }
+
+    // text
+
+    fn get_display_byte(&self, address: usize) -> u8 {
+        if self.field_attributes[address].is_some() || self.is_non_display_address(address) {
+            0x40
+        }
+        else {
+            self.characters[address]
+        }
+    }
+    fn get_display_character(&self, address: usize) -> char {
+        let character = self.code_page.get_char(self.get_display_byte(address));
+        if character.is_control() {
+            ' '
+        }
+        else {
+            character
+        }
+    }
+    fn get_region_start(&self, row: usize, column: usize, length: usize) -> Result<usize, String> {
+        if row >= self.rows || column >= self.columns {
+            return Err(format!("Position ({row},{column}) is outside of the {}x{} screen.", self.rows, self.columns));
+        }
+        let start = row * self.columns + column;
+        if start + length > self.get_size() {
+            return Err(format!("Length {length} extends past the end of the screen."));
+        }
+        Ok(start)
+    }
+    pub fn get_text(&self, row: usize, column: usize, length: usize) -> Result<String, String> {
+        let start = self.get_region_start(row, column, length)?;
+        Ok((start..start + length)
+            .map(|address| self.get_display_character(address))
+            .collect())
+    }
+    pub fn get_ebcdic(&self, row: usize, column: usize, length: usize) -> Result<Vec<u8>, String> {
+        let start = self.get_region_start(row, column, length)?;
+        Ok((start..start + length)
+            .map(|address| self.get_display_byte(address))
+            .collect())
+    }
+    // every row of the buffer as x3270's ReadBuffer(Ascii) reports it, with SF(...) for each field attribute and each character's UTF-8 bytes in hexadecimal
+    pub fn get_read_buffer_lines(&self) -> Vec<String> {
+        (0..self.rows)
+            .map(|row| {
+                (row * self.columns..(row + 1) * self.columns)
+                    .map(|address| match self.field_attributes[address] {
+                        Some(field_attribute) => {
+                            let mut token = format!("SF({:02x}={:02x}", EXTENDED_ATTRIBUTE_FIELD, READ_BUFFER_FIELD_ATTRIBUTE | field_attribute);
+                            let (color, highlighting) = self.field_extended_attributes[address];
+                            if color != 0 {
+                                token.push_str(&format!(",{:02x}={:02x}", EXTENDED_ATTRIBUTE_FOREGROUND_COLOR, color));
+                            }
+                            if highlighting != 0 {
+                                token.push_str(&format!(",{:02x}={:02x}", EXTENDED_ATTRIBUTE_HIGHLIGHTING, highlighting));
+                            }
+                            token.push(')');
+                            token
+                        },
+                        None if self.characters[address] == 0 => {
+                            String::from("00")
+                        },
+                        None => {
+                            let character = self.code_page.get_char(self.characters[address]);
+                            let mut bytes = [0u8; 4];
+                            character.encode_utf8(&mut bytes)
+                                .bytes()
+                                .map(|byte| format!("{byte:02x}"))
+                                .collect()
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .collect()
+    }
+    pub fn get_text_range(&self, row: usize, column: usize, rows: usize, columns: usize) -> Result<Vec<String>, String> {
+        if row + rows > self.rows || column + columns > self.columns {
+            return Err(format!("Region of {rows}x{columns} at ({row},{column}) is outside of the {}x{} screen.", self.rows, self.columns));
+        }
+        (row..row + rows)
+            .map(|current_row| self.get_text(current_row, column, columns))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_ebcdic(text: &str) -> Vec<u8> {
+        text.chars()
+            .map(|character| CodePage::Cp037.get_byte(character).unwrap())
+            .collect()
+    }
+
+    // a protected "NAME:" label followed by an unprotected six-character input field
+    fn get_login_screen() -> Screen {
+        let mut record = vec![COMMAND_ERASE_WRITE, WCC_KEYBOARD_RESTORE | WCC_RESET_MDT];
+        record.push(ORDER_SET_BUFFER_ADDRESS);
+        record.extend(encode_address(80));
+        record.extend([ORDER_START_FIELD, 0x60]);
+        record.extend(get_ebcdic("NAME:"));
+        record.extend([ORDER_START_FIELD, 0x40, ORDER_INSERT_CURSOR]);
+        record.push(ORDER_SET_BUFFER_ADDRESS);
+        record.extend(encode_address(93));
+        record.extend([ORDER_START_FIELD, 0x60]);
+        let mut screen = Screen::new(24, 80);
+        assert!(screen.process_record(&record).is_none());
+        screen
+    }
+
+    #[test]
+    fn erase_write_builds_fields() {
+        let screen = get_login_screen();
+        assert!(screen.is_formatted());
+        assert!(!screen.is_keyboard_locked());
+        assert_eq!((1, 7), screen.get_cursor());
+        assert_eq!(String::from(" NAME:       "), screen.get_text(1, 0, 13).unwrap());
+    }
+
+    #[test]
+    fn type_into_field_then_read_modified() {
+        let mut screen = get_login_screen();
+        for character in get_ebcdic("BOB") {
+            screen.type_character(character).unwrap();
+        }
+        assert_eq!(String::from("BOB   "), screen.get_text(1, 7, 6).unwrap());
+
+        let mut expected = vec![AID_ENTER];
+        expected.extend(encode_address(90));
+        expected.push(ORDER_SET_BUFFER_ADDRESS);
+        expected.extend(encode_address(87));
+        expected.extend(get_ebcdic("BOB"));
+        assert_eq!(expected, screen.read_modified(AID_ENTER));
+
+        // the label is protected
+        screen.move_cursor(1, 2).unwrap();
+        assert!(screen.type_character(0xC1).is_err());
+    }
+
+    #[test]
+    fn answer_read_partition_query() {
+        let mut screen = Screen::new(27, 132);
+        let inbound = screen.process_record(&[COMMAND_WRITE_STRUCTURED_FIELD, 0x00, 0x05, STRUCTURED_FIELD_READ_PARTITION, 0xFF, READ_PARTITION_QUERY]).unwrap();
+        assert_eq!(&[AID_STRUCTURED_FIELD, 0x00, 0x07, STRUCTURED_FIELD_QUERY_REPLY, QUERY_CODE_SUMMARY], &inbound[..5]);
+        // the usable area reports 132 columns by 27 rows
+        assert_eq!(&[0x00, 0x17, STRUCTURED_FIELD_QUERY_REPLY, QUERY_CODE_USABLE_AREA, 0x01, 0x00, 0x00, 0x84, 0x00, 0x1B], &inbound[8..18]);
+    }
+
+    #[test]
+    fn switch_between_default_and_alternate_sizes() {
+        let mut screen = Screen::for_model(ScreenModel::Model5);
+        assert_eq!((24, 80), (screen.get_rows(), screen.get_columns()));
+        let mut record = vec![COMMAND_ERASE_WRITE_ALTERNATE, WCC_KEYBOARD_RESTORE, ORDER_SET_BUFFER_ADDRESS];
+        // the last position of a 27x132 buffer
+        record.extend(encode_address(3563));
+        record.extend([ORDER_START_FIELD, 0x60]);
+        assert!(screen.process_record(&record).is_none());
+        assert_eq!((27, 132), (screen.get_rows(), screen.get_columns()));
+        assert!(screen.is_alternate_size());
+        assert!(screen.is_formatted());
+        // the implicit partition reports the default 80x24 and the alternate 132x27
+        let inbound = screen.process_record(&[COMMAND_WRITE_STRUCTURED_FIELD, 0x00, 0x05, STRUCTURED_FIELD_READ_PARTITION, 0xFF, READ_PARTITION_QUERY]).unwrap();
+        assert_eq!(&[0x00, 0x50, 0x00, 0x18, 0x00, 0x84, 0x00, 0x1B], &inbound[inbound.len() - 8..]);
+        screen.process_record(&[COMMAND_ERASE_WRITE, WCC_KEYBOARD_RESTORE]);
+        assert_eq!((24, 80), (screen.get_rows(), screen.get_columns()));
+        assert_eq!((27, 132), screen.get_maximum_size());
+        assert_eq!(ScreenModel::Model5, screen.get_model());
+
+        assert!(ScreenModel::Oversize { rows: 62, columns: 160 }.validate().is_ok());
+        assert!(ScreenModel::Oversize { rows: 100, columns: 200 }.validate().is_err());
+        assert!(ScreenModel::Oversize { rows: 20, columns: 132 }.validate().is_err());
+    }
+
+    #[test]
+    fn tab_and_field_navigation() {
+        let mut screen = get_login_screen();
+        screen.move_cursor(0, 0).unwrap();
+        screen.tab();
+        assert_eq!((1, 7), screen.get_cursor());
+        screen.tab();
+        assert_eq!((1, 7), screen.get_cursor());
+
+        screen.type_character(0xC1).unwrap();
+        screen.type_character(0xC2).unwrap();
+        screen.field_end().unwrap();
+        assert_eq!((1, 9), screen.get_cursor());
+        screen.back_tab();
+        assert_eq!((1, 7), screen.get_cursor());
+
+        screen.move_cursor(1, 10).unwrap();
+        screen.delete_field().unwrap();
+        assert_eq!((1, 7), screen.get_cursor());
+        assert_eq!(String::from("      "), screen.get_text(1, 7, 6).unwrap());
+    }
+
+    #[test]
+    fn insert_delete_and_erase_within_field() {
+        let mut screen = get_login_screen();
+        for character in get_ebcdic("ACD") {
+            screen.type_character(character).unwrap();
+        }
+        screen.move_cursor(1, 8).unwrap();
+        screen.set_insert_mode(true);
+        screen.type_character(get_ebcdic("B")[0]).unwrap();
+        assert_eq!(String::from("ABCD  "), screen.get_text(1, 7, 6).unwrap());
+        assert_eq!((1, 9), screen.get_cursor());
+        for character in get_ebcdic("EF") {
+            screen.type_character(character).unwrap();
+        }
+        assert_eq!(String::from("ABEFCD"), screen.get_text(1, 7, 6).unwrap());
+        // the field is full, so nothing more fits
+        screen.move_cursor(1, 7).unwrap();
+        assert!(screen.type_character(get_ebcdic("Z")[0]).is_err());
+        screen.reset();
+
+        screen.delete_character().unwrap();
+        assert_eq!(String::from("BEFCD "), screen.get_text(1, 7, 6).unwrap());
+        screen.move_cursor(1, 9).unwrap();
+        screen.erase_previous_character().unwrap();
+        assert_eq!((1, 8), screen.get_cursor());
+        assert_eq!(String::from("BFCD  "), screen.get_text(1, 7, 6).unwrap());
+        screen.erase_end_of_field().unwrap();
+        assert_eq!(String::from("B     "), screen.get_text(1, 7, 6).unwrap());
+
+        screen.move_cursor(0, 5).unwrap();
+        screen.new_line();
+        assert_eq!((1, 7), screen.get_cursor());
+        screen.erase_input();
+        assert_eq!(String::from("      "), screen.get_text(1, 7, 6).unwrap());
+    }
+}
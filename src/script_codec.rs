@@ -0,0 +1,247 @@
+#![allow(dead_code)]
+
+use std::io::{Read, Write};
+use crate::{client_status::ClientStatus, transcript::TranscriptRecorder};
+
+const READ_CHUNK_LENGTH: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conclusion {
+    Ok,
+    Error
+}
+
+// everything the client writes in answer to one line of actions: "data: " lines, a status line, then "ok" or "error"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseFrame {
+    pub data_lines: Vec<String>,
+    pub status: ClientStatus,
+    // the status as it was received, kept for transcripts
+    pub status_line: String,
+    pub conclusion: Conclusion
+}
+
+#[derive(Debug)]
+pub enum FrameError {
+    // includes UnexpectedEof once the client closes the stream
+    Io(std::io::Error),
+    // a line that cannot appear where it did, after which the partial frame is dropped
+    Protocol(String)
+}
+
+// the frame read so far, kept when a read times out so that the rest can still be matched to it
+#[derive(Debug, Default)]
+struct PartialFrame {
+    data_lines: Vec<String>,
+    status: Option<(String, ClientStatus)>
+}
+
+// owns the scripting stream for the life of the connection, so that bytes read past the end of one frame belong to the next
+pub struct ScriptCodec<TStream: Read + Write> {
+    stream: TStream,
+    buffer: Vec<u8>,
+    // the start of the bytes in the buffer that have not been split into lines yet
+    buffer_offset: usize,
+    partial_frame: PartialFrame,
+    recorder: Option<TranscriptRecorder>
+}
+
+impl<TStream: Read + Write> ScriptCodec<TStream> {
+    pub fn new(stream: TStream) -> Self {
+        ScriptCodec {
+            stream,
+            buffer: Vec::with_capacity(READ_CHUNK_LENGTH),
+            buffer_offset: 0,
+            partial_frame: PartialFrame::default(),
+            recorder: None
+        }
+    }
+    // records every line written from now on with the frame that answers it, until replaced or set to None
+    pub fn set_recorder(&mut self, recorder: Option<TranscriptRecorder>) {
+        self.recorder = recorder;
+    }
+    pub fn get_ref(&self) -> &TStream {
+        &self.stream
+    }
+    pub fn get_mut(&mut self) -> &mut TStream {
+        &mut self.stream
+    }
+    // the bytes after a frame that have been read but not yet returned
+    pub fn get_buffered_length(&self) -> usize {
+        self.buffer.len() - self.buffer_offset
+    }
+    // true when a frame was started but a read failed before its conclusion
+    pub fn is_frame_partial(&self) -> bool {
+        !self.partial_frame.data_lines.is_empty() || self.partial_frame.status.is_some()
+    }
+    pub fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        self.write_lines(&[line])
+    }
+    // sends the lines with a single write so that they reach the client together
+    pub fn write_lines<TLine: AsRef<str>>(&mut self, lines: &[TLine]) -> std::io::Result<()> {
+        let mut bytes = Vec::new();
+        for line in lines {
+            bytes.extend_from_slice(line.as_ref().as_bytes());
+            bytes.push(b'\n');
+        }
+        self.stream.write_all(&bytes)?;
+        self.stream.flush()?;
+        if let Some(recorder) = self.recorder.as_mut() {
+            for line in lines {
+                recorder.record_command(line.as_ref());
+            }
+        }
+        Ok(())
+    }
+    pub fn read_frame(&mut self) -> Result<ResponseFrame, FrameError> {
+        loop {
+            let line = self.read_line()
+                .map_err(FrameError::Io)?;
+            tracing::trace!(%line, "received line");
+            if let Some(data) = line.strip_prefix("data: ") {
+                self.partial_frame.data_lines.push(String::from(data));
+            }
+            else if self.partial_frame.status.is_none() {
+                match ClientStatus::parse(&line) {
+                    Some(status) => {
+                        self.partial_frame.status = Some((line, status));
+                    },
+                    None => {
+                        self.partial_frame = PartialFrame::default();
+                        return Err(FrameError::Protocol(format!("expected a status line but received \"{}\"", line)));
+                    }
+                }
+            }
+            else {
+                let partial_frame = std::mem::take(&mut self.partial_frame);
+                let conclusion = match line.trim() {
+                    "ok" => Conclusion::Ok,
+                    "error" => Conclusion::Error,
+                    conclusion => {
+                        return Err(FrameError::Protocol(format!("expected \"ok\" or \"error\" but received \"{}\"", conclusion)));
+                    }
+                };
+                let (status_line, status) = partial_frame.status.expect("the status should precede the conclusion");
+                let frame = ResponseFrame {
+                    data_lines: partial_frame.data_lines,
+                    status,
+                    status_line,
+                    conclusion
+                };
+                if let Some(recorder) = self.recorder.as_mut() {
+                    recorder.record_frame(&frame);
+                }
+                return Ok(frame);
+            }
+        }
+    }
+    // the next line without its line ending, reading more of the stream only when the buffer holds no complete line
+    fn read_line(&mut self) -> std::io::Result<String> {
+        loop {
+            if let Some(line_length) = self.buffer[self.buffer_offset..].iter().position(|byte| *byte == b'\n') {
+                let line_end = self.buffer_offset + line_length;
+                let line = String::from_utf8_lossy(&self.buffer[self.buffer_offset..line_end])
+                    .trim_end_matches('\r')
+                    .to_string();
+                self.buffer_offset = line_end + 1;
+                return Ok(line);
+            }
+            // reuse the buffer by moving the unsplit bytes to its start
+            self.buffer.drain(..self.buffer_offset);
+            self.buffer_offset = 0;
+            let buffer_length = self.buffer.len();
+            self.buffer.resize(buffer_length + READ_CHUNK_LENGTH, 0);
+            let read_result = self.stream.read(&mut self.buffer[buffer_length..]);
+            let read_length = *read_result.as_ref().unwrap_or(&0);
+            self.buffer.truncate(buffer_length + read_length);
+            if read_result? == 0 {
+                return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "The client closed the stream."));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const STATUS: &str = "U F U C(localhost) I 4 43 80 0 0 0x0 0.000";
+
+    // a stream that hands out its bytes a few at a time, failing once between each chunk as a timed out read would
+    struct SlowStream {
+        chunks: Vec<Vec<u8>>,
+        is_next_read_failing: bool
+    }
+
+    impl Read for SlowStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.is_next_read_failing = !self.is_next_read_failing;
+            if !self.is_next_read_failing {
+                return Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "slow"));
+            }
+            if self.chunks.is_empty() {
+                return Ok(0);
+            }
+            let chunk = self.chunks.remove(0);
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    impl Write for SlowStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn keep_bytes_past_the_frame() {
+        // two frames arrive in a single read
+        let response = format!("data: first\n{STATUS}\nok\ndata: second\r\n{STATUS}\nerror\n");
+        let mut codec = ScriptCodec::new(Cursor::new(response.into_bytes()));
+
+        let frame = codec.read_frame().unwrap();
+        assert_eq!(vec![String::from("first")], frame.data_lines);
+        assert_eq!(Conclusion::Ok, frame.conclusion);
+        assert!(codec.get_buffered_length() > 0);
+        let frame = codec.read_frame().unwrap();
+        assert_eq!(vec![String::from("second")], frame.data_lines);
+        assert_eq!(Conclusion::Error, frame.conclusion);
+        assert!(matches!(codec.read_frame(), Err(FrameError::Io(error)) if error.kind() == std::io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn resume_frame_after_failed_read() {
+        let response = format!("data: split\n{STATUS}\nok\n").into_bytes();
+        let chunks = response.chunks(5).map(|chunk| chunk.to_vec()).collect();
+        let mut codec = ScriptCodec::new(SlowStream { chunks, is_next_read_failing: false });
+
+        let frame = loop {
+            match codec.read_frame() {
+                Ok(frame) => {
+                    break frame;
+                },
+                Err(FrameError::Io(error)) if error.kind() == std::io::ErrorKind::TimedOut => {
+                    // read again
+                },
+                Err(error) => {
+                    panic!("unexpected error: {:?}", error);
+                }
+            }
+        };
+        assert_eq!(vec![String::from("split")], frame.data_lines);
+        assert!(!codec.is_frame_partial());
+    }
+
+    #[test]
+    fn reject_missing_status() {
+        let mut codec = ScriptCodec::new(Cursor::new(b"ok\n".to_vec()));
+        assert!(matches!(codec.read_frame(), Err(FrameError::Protocol(_))));
+        assert!(!codec.is_frame_partial());
+    }
+}
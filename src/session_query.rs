@@ -0,0 +1,219 @@
+#![allow(dead_code)]
+
+// the answers of x3270's Query() actions, read into types
+
+// the host of the session, from Query(Host), such as "host mainframe.example.com 23"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostInfo {
+    pub name: String,
+    pub port: u16
+}
+
+impl HostInfo {
+    // None when the session is not connected
+    pub fn parse(data: &str) -> Result<Option<HostInfo>, String> {
+        let tokens = data.split_whitespace().collect::<Vec<&str>>();
+        match tokens.as_slice() {
+            [] | ["not", "connected"] => {
+                Ok(None)
+            },
+            ["host", name, port, ..] => {
+                let port = port.parse::<u16>()
+                    .map_err(|_| format!("\"{}\" is not a port number.", port))?;
+                Ok(Some(HostInfo {
+                    name: String::from(*name),
+                    port
+                }))
+            },
+            _ => {
+                Err(format!("\"{}\" does not describe a host.", data))
+            }
+        }
+    }
+}
+
+// None when the host assigned no LU name, which x3270 reports as "none"
+pub fn parse_lu_name(data: &str) -> Option<String> {
+    match data.trim() {
+        "" | "none" => None,
+        lu_name => Some(String::from(lu_name))
+    }
+}
+
+// the emulated terminal, from Query(Model), such as "IBM-3279-4-E"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalModel {
+    pub terminal_type: String,
+    // such as "3279"
+    pub device: String,
+    pub model_number: u8,
+    // the "-E" suffix of terminals that support extended attributes and structured fields
+    pub is_extended: bool
+}
+
+impl TerminalModel {
+    pub fn parse(data: &str) -> Result<TerminalModel, String> {
+        let terminal_type = data.trim();
+        let parts = terminal_type.trim_start_matches("IBM-")
+            .split('-')
+            .collect::<Vec<&str>>();
+        let (device, model_number, is_extended) = match parts.as_slice() {
+            [device, model_number] => (device, model_number, false),
+            [device, model_number, "E"] => (device, model_number, true),
+            _ => {
+                return Err(format!("\"{}\" is not a terminal model.", terminal_type));
+            }
+        };
+        Ok(TerminalModel {
+            terminal_type: String::from(terminal_type),
+            device: String::from(*device),
+            model_number: model_number.parse::<u8>()
+                .map_err(|_| format!("\"{}\" is not a model number.", model_number))?,
+            is_extended
+        })
+    }
+}
+
+// from Query(ScreenCurSize) and Query(ScreenMaxSize), such as "rows 24 columns 80"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScreenSize {
+    pub rows: usize,
+    pub columns: usize
+}
+
+impl ScreenSize {
+    pub fn parse(data: &str) -> Result<ScreenSize, String> {
+        let tokens = data.split_whitespace().collect::<Vec<&str>>();
+        let (rows, columns) = match tokens.as_slice() {
+            ["rows", rows, "columns", columns] | [rows, columns] => (rows, columns),
+            _ => {
+                return Err(format!("\"{}\" is not a screen size.", data));
+            }
+        };
+        Ok(ScreenSize {
+            rows: rows.parse::<usize>()
+                .map_err(|_| format!("\"{}\" is not a number of rows.", rows))?,
+            columns: columns.parse::<usize>()
+                .map_err(|_| format!("\"{}\" is not a number of columns.", columns))?
+        })
+    }
+}
+
+// from Query(Formatted)
+pub fn parse_formatted(data: &str) -> Result<bool, String> {
+    match data.trim() {
+        "formatted" => Ok(true),
+        "unformatted" => Ok(false),
+        data => Err(format!("\"{}\" is neither formatted nor unformatted.", data))
+    }
+}
+
+// from Query(Tls), such as "not secure" or "secure verified"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsState {
+    pub is_secure: bool,
+    // None when the emulator does not say whether the host certificate was verified
+    pub is_verified: Option<bool>,
+    // anything reported after that, such as the TLS provider
+    pub details: String
+}
+
+impl TlsState {
+    pub fn parse(data: &str) -> Result<TlsState, String> {
+        let data = data.trim();
+        let (is_secure, rest) = if let Some(rest) = data.strip_prefix("not secure") {
+            (false, rest)
+        }
+        else if let Some(rest) = data.strip_prefix("secure") {
+            (true, rest)
+        }
+        else {
+            return Err(format!("\"{}\" does not describe the TLS state.", data));
+        };
+        let mut tokens = rest.split_whitespace().peekable();
+        let is_verified = match tokens.peek() {
+            Some(&"verified") => Some(true),
+            Some(&"unverified") => Some(false),
+            _ => None
+        };
+        if is_verified.is_some() {
+            tokens.next();
+        }
+        Ok(TlsState {
+            is_secure,
+            is_verified,
+            details: tokens.collect::<Vec<&str>>().join(" ")
+        })
+    }
+}
+
+// the state of the session, from Query(ConnectionState), such as "connected-tn3270e"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionState {
+    NotConnected,
+    // resolving the host or negotiating TCP, TLS, a proxy or telnet, such as "tcp-pending"
+    Pending(String),
+    ConnectedNvt,
+    ConnectedNvtCharacter,
+    Connected3270,
+    // TN3270E, before the host has bound the LU
+    ConnectedUnbound,
+    ConnectedExtendedNvt,
+    ConnectedSscp,
+    ConnectedTn3270e
+}
+
+impl SessionState {
+    pub fn parse(data: &str) -> Result<SessionState, String> {
+        match data.trim() {
+            "not-connected" => Ok(SessionState::NotConnected),
+            "connected-nvt" => Ok(SessionState::ConnectedNvt),
+            "connected-nvt-charmode" => Ok(SessionState::ConnectedNvtCharacter),
+            "connected-3270" => Ok(SessionState::Connected3270),
+            "connected-unbound" => Ok(SessionState::ConnectedUnbound),
+            "connected-e-nvt" => Ok(SessionState::ConnectedExtendedNvt),
+            "connected-sscp" => Ok(SessionState::ConnectedSscp),
+            "connected-tn3270e" => Ok(SessionState::ConnectedTn3270e),
+            state if state == "resolving" || state == "reconnecting" || state.ends_with("-pending") => Ok(SessionState::Pending(String::from(state))),
+            state => Err(format!("\"{}\" is not a connection state.", state))
+        }
+    }
+    pub fn get_name(&self) -> &str {
+        match self {
+            SessionState::NotConnected => "not-connected",
+            SessionState::Pending(state) => state,
+            SessionState::ConnectedNvt => "connected-nvt",
+            SessionState::ConnectedNvtCharacter => "connected-nvt-charmode",
+            SessionState::Connected3270 => "connected-3270",
+            SessionState::ConnectedUnbound => "connected-unbound",
+            SessionState::ConnectedExtendedNvt => "connected-e-nvt",
+            SessionState::ConnectedSscp => "connected-sscp",
+            SessionState::ConnectedTn3270e => "connected-tn3270e"
+        }
+    }
+    pub fn is_connected(&self) -> bool {
+        !matches!(self, SessionState::NotConnected | SessionState::Pending(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_query_answers() {
+        assert_eq!(Some(HostInfo { name: String::from("mainframe.example.com"), port: 992 }), HostInfo::parse("host mainframe.example.com 992").unwrap());
+        assert_eq!(None, HostInfo::parse("").unwrap());
+        assert_eq!(None, parse_lu_name("none"));
+        let model = TerminalModel::parse("IBM-3279-4-E").unwrap();
+        assert_eq!(("3279", 4, true), (model.device.as_str(), model.model_number, model.is_extended));
+        assert_eq!(ScreenSize { rows: 43, columns: 80 }, ScreenSize::parse("rows 43 columns 80").unwrap());
+        assert!(!parse_formatted("unformatted").unwrap());
+        assert_eq!(TlsState { is_secure: false, is_verified: None, details: String::new() }, TlsState::parse("not secure").unwrap());
+        let tls_state = TlsState::parse("secure verified OpenSSL 3.0").unwrap();
+        assert_eq!((true, Some(true), "OpenSSL 3.0"), (tls_state.is_secure, tls_state.is_verified, tls_state.details.as_str()));
+        assert_eq!(SessionState::Pending(String::from("tls-pending")), SessionState::parse("tls-pending").unwrap());
+        assert!(SessionState::parse("connected-tn3270e").unwrap().is_connected());
+        assert!(SessionState::parse("sideways").is_err());
+    }
+}
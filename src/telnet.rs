@@ -0,0 +1,463 @@
+#![allow(dead_code)]
+
+use std::{io::{Read, Write}, collections::VecDeque};
+use crate::tn3270e::*;
+
+pub const IAC: u8 = 255;
+pub const DONT: u8 = 254;
+pub const DO: u8 = 253;
+pub const WONT: u8 = 252;
+pub const WILL: u8 = 251;
+pub const SB: u8 = 250;
+pub const SE: u8 = 240;
+pub const EOR: u8 = 239;
+pub const IP: u8 = 244;
+pub const BREAK: u8 = 243;
+
+pub const OPTION_BINARY: u8 = 0;
+pub const OPTION_TERMINAL_TYPE: u8 = 24;
+pub const OPTION_EOR: u8 = 25;
+pub const OPTION_START_TLS: u8 = 46;
+
+const TERMINAL_TYPE_IS: u8 = 0;
+const TERMINAL_TYPE_SEND: u8 = 1;
+const START_TLS_FOLLOWS: u8 = 1;
+
+enum ParserState {
+    Data,
+    Iac,
+    Negotiation(u8),
+    Subnegotiation,
+    SubnegotiationIac
+}
+
+// splits the telnet stream into 3270 records, answering option negotiation along the way
+pub struct TelnetConnection<T: Read + Write> {
+    stream: T,
+    terminal_type: String,
+    state: ParserState,
+    record: Vec<u8>,
+    subnegotiation: Vec<u8>,
+    records: VecDeque<Vec<u8>>,
+    local_options: [bool; 256],
+    remote_options: [bool; 256],
+    is_terminal_type_sent: bool,
+    tn3270e_negotiation: Option<Tn3270eNegotiation>,
+    sequence_number: u16,
+    bind_image: Option<Vec<u8>>,
+    is_start_tls_offered: bool,
+    is_start_tls_pending: bool
+}
+
+impl<T: Read + Write> TelnetConnection<T> {
+    pub fn new(stream: T, terminal_type: &str) -> Self {
+        TelnetConnection {
+            stream,
+            terminal_type: String::from(terminal_type),
+            state: ParserState::Data,
+            record: Vec::new(),
+            subnegotiation: Vec::new(),
+            records: VecDeque::new(),
+            local_options: [false; 256],
+            remote_options: [false; 256],
+            is_terminal_type_sent: false,
+            tn3270e_negotiation: None,
+            sequence_number: 0,
+            bind_image: None,
+            is_start_tls_offered: false,
+            is_start_tls_pending: false
+        }
+    }
+    // accepts the host's START_TLS offer, after which negotiate returns so that the caller can run the handshake
+    pub fn set_start_tls_offered(&mut self, is_start_tls_offered: bool) {
+        self.is_start_tls_offered = is_start_tls_offered;
+    }
+    pub fn is_start_tls_pending(&self) -> bool {
+        self.is_start_tls_pending
+    }
+    // telnet state does not survive the TLS handshake, so the caller builds a new connection over the upgraded stream
+    pub fn into_inner(self) -> T {
+        self.stream
+    }
+    // offers TN3270E to the host, which otherwise falls back to plain TN3270
+    pub fn set_tn3270e_configuration(&mut self, configuration: Tn3270eConfiguration) {
+        self.tn3270e_negotiation = Some(Tn3270eNegotiation::new(configuration));
+    }
+    pub fn get_tn3270e_negotiation(&self) -> Option<&Tn3270eNegotiation> {
+        self.tn3270e_negotiation
+            .as_ref()
+            .filter(|_| self.local_options[OPTION_TN3270E as usize])
+    }
+    pub fn is_tn3270e_mode(&self) -> bool {
+        self.get_tn3270e_negotiation()
+            .map(|negotiation| negotiation.is_complete())
+            .unwrap_or(false)
+    }
+    pub fn get_bind_image(&self) -> Option<&[u8]> {
+        self.bind_image.as_deref()
+    }
+    pub fn get_ref(&self) -> &T {
+        &self.stream
+    }
+    pub fn is_3270_mode(&self) -> bool {
+        self.is_tn3270e_mode() || (
+            self.is_terminal_type_sent &&
+            self.local_options[OPTION_BINARY as usize] &&
+            self.remote_options[OPTION_BINARY as usize] &&
+            self.local_options[OPTION_EOR as usize] &&
+            self.remote_options[OPTION_EOR as usize]
+        )
+    }
+    pub fn negotiate(&mut self) -> std::io::Result<()> {
+        while !self.is_3270_mode() && !self.is_start_tls_pending {
+            self.receive()?;
+        }
+        Ok(())
+    }
+    pub fn take_record(&mut self) -> Option<Vec<u8>> {
+        self.records.pop_front()
+    }
+    pub fn read_record(&mut self) -> std::io::Result<Vec<u8>> {
+        loop {
+            if let Some(record) = self.records.pop_front() {
+                return Ok(record);
+            }
+            self.receive()?;
+        }
+    }
+    pub fn write_record(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if self.is_tn3270e_mode() {
+            let header = Tn3270eHeader::new(DATA_TYPE_3270_DATA, RESPONSE_FLAG_NO_RESPONSE, self.sequence_number);
+            self.sequence_number = self.sequence_number.wrapping_add(1);
+            let mut record = header.to_bytes().to_vec();
+            record.extend(data);
+            return self.write_escaped_record(&record);
+        }
+        self.write_escaped_record(data)
+    }
+    // the attention key, which TN3270E hosts expect as an interrupt and plain TN3270 hosts as a break
+    pub fn write_attention(&mut self) -> std::io::Result<()> {
+        let command = if self.is_tn3270e_mode() { IP } else { BREAK };
+        self.stream.write_all(&[IAC, command])?;
+        self.stream.flush()
+    }
+    fn write_escaped_record(&mut self, data: &[u8]) -> std::io::Result<()> {
+        let mut bytes = Vec::with_capacity(data.len() + 2);
+        for byte in data.iter() {
+            if *byte == IAC {
+                bytes.push(IAC);
+            }
+            bytes.push(*byte);
+        }
+        bytes.push(IAC);
+        bytes.push(EOR);
+        self.stream.write_all(&bytes)?;
+        self.stream.flush()
+    }
+    // reads whatever is available from the stream, keeping partial records for the next call
+    pub fn receive(&mut self) -> std::io::Result<()> {
+        let mut buffer = [0u8; 4096];
+        let read_length = self.stream.read(&mut buffer)?;
+        if read_length == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "The host closed the connection."));
+        }
+        for byte in buffer[..read_length].iter() {
+            if self.is_start_tls_pending {
+                // the host waits for the handshake after FOLLOWS, so nothing else belongs to the clear session
+                break;
+            }
+            self.process_byte(*byte)?;
+        }
+        Ok(())
+    }
+    fn process_byte(&mut self, byte: u8) -> std::io::Result<()> {
+        match self.state {
+            ParserState::Data => {
+                if byte == IAC {
+                    self.state = ParserState::Iac;
+                }
+                else {
+                    self.record.push(byte);
+                }
+            },
+            ParserState::Iac => {
+                self.state = ParserState::Data;
+                match byte {
+                    IAC => {
+                        self.record.push(IAC);
+                    },
+                    EOR => {
+                        let record = std::mem::take(&mut self.record);
+                        self.process_record(record)?;
+                    },
+                    DO | DONT | WILL | WONT => {
+                        self.state = ParserState::Negotiation(byte);
+                    },
+                    SB => {
+                        self.subnegotiation.clear();
+                        self.state = ParserState::Subnegotiation;
+                    },
+                    _ => {
+                        // NOP, GA and the like carry no meaning for 3270 sessions
+                    }
+                }
+            },
+            ParserState::Negotiation(verb) => {
+                self.state = ParserState::Data;
+                self.process_negotiation(verb, byte)?;
+            },
+            ParserState::Subnegotiation => {
+                if byte == IAC {
+                    self.state = ParserState::SubnegotiationIac;
+                }
+                else {
+                    self.subnegotiation.push(byte);
+                }
+            },
+            ParserState::SubnegotiationIac => {
+                if byte == SE {
+                    self.state = ParserState::Data;
+                    let subnegotiation = std::mem::take(&mut self.subnegotiation);
+                    self.process_subnegotiation(&subnegotiation)?;
+                }
+                else {
+                    self.subnegotiation.push(byte);
+                    self.state = ParserState::Subnegotiation;
+                }
+            }
+        }
+        Ok(())
+    }
+    fn process_record(&mut self, record: Vec<u8>) -> std::io::Result<()> {
+        if !self.is_tn3270e_mode() {
+            self.records.push_back(record);
+            return Ok(());
+        }
+        let header = match Tn3270eHeader::parse(&record) {
+            Some(header) => {
+                header
+            },
+            None => {
+                // too short to carry a header
+                return Ok(());
+            }
+        };
+        match header.data_type {
+            DATA_TYPE_3270_DATA => {
+                self.records.push_back(record[5..].to_vec());
+                let is_response_enabled = self.tn3270e_negotiation
+                    .as_ref()
+                    .map(|negotiation| negotiation.is_function_enabled(Function::Responses))
+                    .unwrap_or(false);
+                if is_response_enabled && header.response_flag == RESPONSE_FLAG_ALWAYS_RESPONSE {
+                    self.write_escaped_record(&header.get_positive_response())?;
+                }
+            },
+            DATA_TYPE_BIND_IMAGE => {
+                self.bind_image = Some(record[5..].to_vec());
+            },
+            DATA_TYPE_UNBIND => {
+                self.bind_image = None;
+            },
+            _ => {
+                // SCS, NVT and SSCP-LU data are not presented on the screen
+            }
+        }
+        Ok(())
+    }
+    fn is_supported_option(&self, option: u8) -> bool {
+        option == OPTION_BINARY || option == OPTION_EOR || option == OPTION_TERMINAL_TYPE ||
+            (option == OPTION_TN3270E && self.tn3270e_negotiation.is_some()) ||
+            (option == OPTION_START_TLS && self.is_start_tls_offered)
+    }
+    fn process_negotiation(&mut self, verb: u8, option: u8) -> std::io::Result<()> {
+        match verb {
+            DO => {
+                if !self.is_supported_option(option) {
+                    self.send_negotiation(WONT, option)?;
+                }
+                else if !self.local_options[option as usize] {
+                    self.local_options[option as usize] = true;
+                    self.send_negotiation(WILL, option)?;
+                }
+            },
+            DONT => {
+                if self.local_options[option as usize] {
+                    self.local_options[option as usize] = false;
+                    self.send_negotiation(WONT, option)?;
+                }
+            },
+            WILL => {
+                if option == OPTION_TERMINAL_TYPE || option == OPTION_TN3270E || option == OPTION_START_TLS || !self.is_supported_option(option) {
+                    self.send_negotiation(DONT, option)?;
+                }
+                else if !self.remote_options[option as usize] {
+                    self.remote_options[option as usize] = true;
+                    self.send_negotiation(DO, option)?;
+                }
+            },
+            WONT => {
+                if self.remote_options[option as usize] {
+                    self.remote_options[option as usize] = false;
+                    self.send_negotiation(DONT, option)?;
+                }
+            },
+            _ => {
+                panic!("Unexpected negotiation verb {}.", verb);
+            }
+        }
+        Ok(())
+    }
+    fn process_subnegotiation(&mut self, subnegotiation: &[u8]) -> std::io::Result<()> {
+        if subnegotiation.first() == Some(&OPTION_TN3270E) {
+            if let Some(negotiation) = self.tn3270e_negotiation.as_mut() {
+                if let Some(reply) = negotiation.process_subnegotiation(subnegotiation)? {
+                    let mut bytes = vec![IAC, SB];
+                    bytes.extend(reply);
+                    bytes.push(IAC);
+                    bytes.push(SE);
+                    self.stream.write_all(&bytes)?;
+                    self.stream.flush()?;
+                }
+            }
+            return Ok(());
+        }
+        if subnegotiation == [OPTION_START_TLS, START_TLS_FOLLOWS] && self.local_options[OPTION_START_TLS as usize] {
+            self.stream.write_all(&[IAC, SB, OPTION_START_TLS, START_TLS_FOLLOWS, IAC, SE])?;
+            self.stream.flush()?;
+            self.is_start_tls_pending = true;
+            return Ok(());
+        }
+        if subnegotiation.len() >= 2 && subnegotiation[0] == OPTION_TERMINAL_TYPE && subnegotiation[1] == TERMINAL_TYPE_SEND {
+            let mut bytes = vec![IAC, SB, OPTION_TERMINAL_TYPE, TERMINAL_TYPE_IS];
+            bytes.extend(self.terminal_type.as_bytes());
+            bytes.push(IAC);
+            bytes.push(SE);
+            self.stream.write_all(&bytes)?;
+            self.stream.flush()?;
+            self.is_terminal_type_sent = true;
+        }
+        Ok(())
+    }
+    fn send_negotiation(&mut self, verb: u8, option: u8) -> std::io::Result<()> {
+        self.stream.write_all(&[IAC, verb, option])?;
+        self.stream.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    // an in-memory stream that serves the host bytes and records what the terminal sent
+    struct ScriptedStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>
+    }
+
+    impl Read for ScriptedStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for ScriptedStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn negotiate_then_read_record() {
+        let mut host_bytes = vec![
+            IAC, DO, OPTION_TERMINAL_TYPE,
+            IAC, SB, OPTION_TERMINAL_TYPE, TERMINAL_TYPE_SEND, IAC, SE,
+            IAC, DO, OPTION_EOR, IAC, WILL, OPTION_EOR,
+            IAC, DO, OPTION_BINARY, IAC, WILL, OPTION_BINARY,
+            IAC, DO, 1
+        ];
+        host_bytes.extend([0xF5, 0xC3, IAC, IAC, 0xC1, IAC, EOR]);
+        let stream = ScriptedStream {
+            input: Cursor::new(host_bytes),
+            output: Vec::new()
+        };
+        let mut connection = TelnetConnection::new(stream, "IBM-3278-2");
+        connection.negotiate().unwrap();
+        assert!(connection.is_3270_mode());
+
+        let record = connection.read_record().unwrap();
+        assert_eq!(vec![0xF5, 0xC3, IAC, 0xC1], record);
+
+        let mut expected_output = vec![IAC, WILL, OPTION_TERMINAL_TYPE, IAC, SB, OPTION_TERMINAL_TYPE, TERMINAL_TYPE_IS];
+        expected_output.extend(b"IBM-3278-2");
+        expected_output.extend([
+            IAC, SE,
+            IAC, WILL, OPTION_EOR, IAC, DO, OPTION_EOR,
+            IAC, WILL, OPTION_BINARY, IAC, DO, OPTION_BINARY,
+            IAC, WONT, 1
+        ]);
+        assert_eq!(expected_output, connection.get_ref().output);
+    }
+
+    #[test]
+    fn negotiate_tn3270e_then_acknowledge_record() {
+        let mut host_bytes = vec![IAC, DO, OPTION_TN3270E, IAC, SB, OPTION_TN3270E, SEND, DEVICE_TYPE, IAC, SE];
+        host_bytes.extend([IAC, SB, OPTION_TN3270E, DEVICE_TYPE, IS]);
+        host_bytes.extend(b"IBM-3278-2-E");
+        host_bytes.push(CONNECT);
+        host_bytes.extend(b"LU01");
+        host_bytes.extend([IAC, SE, IAC, SB, OPTION_TN3270E, FUNCTIONS, IS, 0, 2, IAC, SE]);
+        host_bytes.extend([DATA_TYPE_BIND_IMAGE, 0, 0, 0, 0, 0x31, IAC, EOR]);
+        host_bytes.extend([DATA_TYPE_3270_DATA, 0, RESPONSE_FLAG_ALWAYS_RESPONSE, 0, 7, 0xF5, 0xC3, IAC, EOR]);
+        let stream = ScriptedStream {
+            input: Cursor::new(host_bytes),
+            output: Vec::new()
+        };
+        let mut connection = TelnetConnection::new(stream, "IBM-3278-2");
+        connection.set_tn3270e_configuration(Tn3270eConfiguration::new_terminal("IBM-3278-2-E", Some("LU01")));
+        connection.negotiate().unwrap();
+        assert!(connection.is_tn3270e_mode());
+        assert_eq!(Some("LU01"), connection.get_tn3270e_negotiation().unwrap().get_device_name());
+        assert_eq!(Some(&[0x31u8][..]), connection.get_bind_image());
+        assert_eq!(vec![0xF5, 0xC3], connection.read_record().unwrap());
+
+        connection.write_record(&[0x7D]).unwrap();
+        let output = &connection.get_ref().output;
+        assert!(output.ends_with(&[
+            DATA_TYPE_RESPONSE, 0, RESPONSE_FLAG_POSITIVE_RESPONSE, 0, 7, 0, IAC, EOR,
+            DATA_TYPE_3270_DATA, 0, 0, 0, 0, 0x7D, IAC, EOR
+        ]));
+    }
+
+    #[test]
+    fn report_rejected_device_type() {
+        let host_bytes = vec![
+            IAC, DO, OPTION_TN3270E, IAC, SB, OPTION_TN3270E, SEND, DEVICE_TYPE, IAC, SE,
+            IAC, SB, OPTION_TN3270E, DEVICE_TYPE, REJECT, REASON, 3, IAC, SE
+        ];
+        let stream = ScriptedStream {
+            input: Cursor::new(host_bytes),
+            output: Vec::new()
+        };
+        let mut connection = TelnetConnection::new(stream, "IBM-3278-2");
+        connection.set_tn3270e_configuration(Tn3270eConfiguration::new_terminal("IBM-3278-2-E", Some("NOSUCHLU")));
+        let error = connection.negotiate().err().unwrap();
+        assert_eq!(Some(&Tn3270eError::DeviceTypeRejected(RejectReason::InvalidName)), Tn3270eError::from_io_error(&error));
+    }
+
+    #[test]
+    fn write_record_escapes_iac() {
+        let stream = ScriptedStream {
+            input: Cursor::new(Vec::new()),
+            output: Vec::new()
+        };
+        let mut connection = TelnetConnection::new(stream, "IBM-3278-2");
+        connection.write_record(&[0x7D, IAC, 0x40]).unwrap();
+        assert_eq!(vec![0x7D, IAC, IAC, 0x40, IAC, EOR], connection.get_ref().output);
+    }
+}
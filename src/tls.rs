@@ -0,0 +1,408 @@
+#![allow(dead_code)]
+
+use std::{io::{Read, Write, BufReader}, net::TcpStream, path::PathBuf, sync::Arc};
+use rustls::{
+    client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier},
+    client::WebPkiServerVerifier,
+    crypto::WebPkiSupportedAlgorithms,
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime},
+    AlertDescription, CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme, StreamOwned
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TlsMode {
+    // the handshake starts as soon as the socket connects, as x3270 does for "L:" hosts
+    Implicit,
+    // the session starts in the clear and upgrades once the host offers the telnet START_TLS option
+    StartTls
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    Tls12,
+    Tls13
+}
+
+impl TlsVersion {
+    pub fn get_x3270_name(&self) -> &'static str {
+        match self {
+            TlsVersion::Tls12 => "TLS1_2",
+            TlsVersion::Tls13 => "TLS1_3"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostnameVerification {
+    // the certificate must be issued to the host in the mainframe address
+    Host,
+    // the certificate must be issued to this name instead, such as when connecting through an IP address
+    Name(String),
+    // the certificate chain is still verified but the name it was issued to is not
+    Any
+}
+
+#[derive(Debug, Clone)]
+pub enum TlsError {
+    Certificate(String),
+    Configuration(String),
+    StartTlsRefused
+}
+
+impl std::fmt::Display for TlsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TlsError::Certificate(message) => {
+                write!(f, "The certificate was not accepted: {}", message)
+            },
+            TlsError::Configuration(message) => {
+                write!(f, "The TLS settings could not be used: {}", message)
+            },
+            TlsError::StartTlsRefused => {
+                write!(f, "The host did not offer STARTTLS.")
+            }
+        }
+    }
+}
+
+impl std::error::Error for TlsError {}
+
+impl TlsError {
+    // finds the TLS failure carried by an io::Error, including the certificate failures that rustls reports
+    pub fn from_io_error(error: &std::io::Error) -> Option<TlsError> {
+        let inner = error.get_ref()?;
+        if let Some(tls_error) = inner.downcast_ref::<TlsError>() {
+            return Some(tls_error.clone());
+        }
+        match inner.downcast_ref::<rustls::Error>()? {
+            rustls::Error::InvalidCertificate(certificate_error) => {
+                Some(TlsError::Certificate(format!("{:?}", certificate_error)))
+            },
+            rustls::Error::NoCertificatesPresented => {
+                Some(TlsError::Certificate(String::from("the host presented no certificate")))
+            },
+            rustls::Error::AlertReceived(alert) if Self::is_certificate_alert(alert) => {
+                Some(TlsError::Certificate(format!("the host rejected the client certificate with {:?}", alert)))
+            },
+            _ => {
+                None
+            }
+        }
+    }
+    pub fn is_certificate_error(error: &std::io::Error) -> bool {
+        matches!(Self::from_io_error(error), Some(TlsError::Certificate(_)))
+    }
+    fn is_certificate_alert(alert: &AlertDescription) -> bool {
+        matches!(alert,
+            AlertDescription::BadCertificate |
+            AlertDescription::UnsupportedCertificate |
+            AlertDescription::CertificateRevoked |
+            AlertDescription::CertificateExpired |
+            AlertDescription::CertificateUnknown |
+            AlertDescription::UnknownCA |
+            AlertDescription::CertificateRequired
+        )
+    }
+}
+
+impl From<TlsError> for std::io::Error {
+    fn from(error: TlsError) -> Self {
+        let kind = match error {
+            TlsError::Certificate(_) => std::io::ErrorKind::InvalidData,
+            TlsError::Configuration(_) => std::io::ErrorKind::InvalidInput,
+            TlsError::StartTlsRefused => std::io::ErrorKind::ConnectionRefused
+        };
+        std::io::Error::new(kind, error)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TlsConfiguration {
+    pub mode: TlsMode,
+    // PEM file of the certificate authorities to trust instead of the bundled web roots
+    pub ca_bundle_path: Option<PathBuf>,
+    pub client_certificate_path: Option<PathBuf>,
+    pub client_key_path: Option<PathBuf>,
+    pub hostname_verification: HostnameVerification,
+    // when false the host certificate is accepted without any verification, which is only fit for test systems
+    pub is_certificate_verified: bool,
+    pub minimum_version: TlsVersion
+}
+
+impl TlsConfiguration {
+    pub fn new(mode: TlsMode) -> Self {
+        TlsConfiguration {
+            mode,
+            ca_bundle_path: None,
+            client_certificate_path: None,
+            client_key_path: None,
+            hostname_verification: HostnameVerification::Host,
+            is_certificate_verified: true,
+            minimum_version: TlsVersion::Tls12
+        }
+    }
+    // the x3270 options that apply these settings, leaving the "L:" host prefix to the caller
+    pub fn get_x3270_arguments(&self) -> Vec<String> {
+        let mut arguments = Vec::new();
+        if let Some(ca_bundle_path) = &self.ca_bundle_path {
+            arguments.push(String::from("-cafile"));
+            arguments.push(ca_bundle_path.display().to_string());
+        }
+        if let Some(client_certificate_path) = &self.client_certificate_path {
+            arguments.push(String::from("-certfile"));
+            arguments.push(client_certificate_path.display().to_string());
+        }
+        if let Some(client_key_path) = &self.client_key_path {
+            arguments.push(String::from("-keyfile"));
+            arguments.push(client_key_path.display().to_string());
+        }
+        match &self.hostname_verification {
+            HostnameVerification::Host => {
+                // verified against the host by default
+            },
+            HostnameVerification::Name(name) => {
+                arguments.push(String::from("-accepthostname"));
+                arguments.push(name.clone());
+            },
+            HostnameVerification::Any => {
+                arguments.push(String::from("-accepthostname"));
+                arguments.push(String::from("any"));
+            }
+        }
+        if !self.is_certificate_verified {
+            arguments.push(String::from("-noverifycert"));
+        }
+        arguments.push(String::from("-tlsminprotocol"));
+        arguments.push(String::from(self.minimum_version.get_x3270_name()));
+        arguments
+    }
+    fn read_pem_file(path: &PathBuf) -> std::io::Result<BufReader<std::fs::File>> {
+        std::fs::File::open(path)
+            .map(BufReader::new)
+            .map_err(|error| TlsError::Configuration(format!("unable to open {}: {}", path.display(), error)).into())
+    }
+    fn get_root_certificate_store(&self) -> std::io::Result<RootCertStore> {
+        let mut root_certificate_store = RootCertStore::empty();
+        match &self.ca_bundle_path {
+            Some(ca_bundle_path) => {
+                let mut reader = Self::read_pem_file(ca_bundle_path)?;
+                for certificate in rustls_pemfile::certs(&mut reader) {
+                    root_certificate_store.add(certificate?)
+                        .map_err(|error| TlsError::Configuration(format!("{} holds an unusable certificate: {}", ca_bundle_path.display(), error)))?;
+                }
+                if root_certificate_store.is_empty() {
+                    return Err(TlsError::Configuration(format!("{} holds no certificates", ca_bundle_path.display())).into());
+                }
+            },
+            None => {
+                root_certificate_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+            }
+        }
+        Ok(root_certificate_store)
+    }
+    fn get_client_configuration(&self) -> std::io::Result<ClientConfig> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let protocol_versions: &[&'static rustls::SupportedProtocolVersion] = match self.minimum_version {
+            TlsVersion::Tls12 => &[&rustls::version::TLS13, &rustls::version::TLS12],
+            TlsVersion::Tls13 => &[&rustls::version::TLS13]
+        };
+        let builder = ClientConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(protocol_versions)
+            .map_err(|error| TlsError::Configuration(error.to_string()))?;
+        let web_pki_verifier = WebPkiServerVerifier::builder_with_provider(Arc::new(self.get_root_certificate_store()?), provider.clone())
+            .build()
+            .map_err(|error| TlsError::Configuration(error.to_string()))?;
+        let builder = builder
+            .dangerous()
+            .with_custom_certificate_verifier(Arc::new(ConfiguredServerCertVerifier {
+                web_pki_verifier,
+                supported_algorithms: provider.signature_verification_algorithms,
+                is_name_verified: self.hostname_verification != HostnameVerification::Any,
+                is_certificate_verified: self.is_certificate_verified
+            }));
+        let client_configuration = match (&self.client_certificate_path, &self.client_key_path) {
+            (Some(client_certificate_path), Some(client_key_path)) => {
+                let certificates = rustls_pemfile::certs(&mut Self::read_pem_file(client_certificate_path)?)
+                    .collect::<Result<Vec<CertificateDer<'static>>, std::io::Error>>()?;
+                let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut Self::read_pem_file(client_key_path)?)?
+                    .ok_or_else(|| TlsError::Configuration(format!("{} holds no private key", client_key_path.display())))?;
+                builder.with_client_auth_cert(certificates, key)
+                    .map_err(|error| TlsError::Configuration(error.to_string()))?
+            },
+            (None, None) => {
+                builder.with_no_client_auth()
+            },
+            _ => {
+                return Err(TlsError::Configuration(String::from("a client certificate and key must be provided together")).into());
+            }
+        };
+        Ok(client_configuration)
+    }
+    fn get_server_name(&self, mainframe_address: &str) -> std::io::Result<ServerName<'static>> {
+        let name = match &self.hostname_verification {
+            HostnameVerification::Name(name) => {
+                name.clone()
+            },
+            _ => {
+                // strip the port and any brackets around an IPv6 address
+                let host = match mainframe_address.rsplit_once(':') {
+                    Some((host, port)) if port.parse::<u16>().is_ok() => host,
+                    _ => mainframe_address
+                };
+                String::from(host.trim_start_matches('[').trim_end_matches(']'))
+            }
+        };
+        ServerName::try_from(name)
+            .map_err(|error| TlsError::Configuration(error.to_string()).into())
+    }
+    // performs the client handshake over an already connected socket
+    pub fn connect(&self, mainframe_address: &str, mut stream: TcpStream) -> std::io::Result<MainframeStream> {
+        let server_name = self.get_server_name(mainframe_address)?;
+        let mut connection = ClientConnection::new(Arc::new(self.get_client_configuration()?), server_name)
+            .map_err(|error| TlsError::Configuration(error.to_string()))?;
+        while connection.is_handshaking() {
+            connection.complete_io(&mut stream)
+                .map_err(|error| match TlsError::from_io_error(&error) {
+                    Some(tls_error) => tls_error.into(),
+                    None => error
+                })?;
+        }
+        Ok(MainframeStream::Tls(Box::new(StreamOwned::new(connection, stream))))
+    }
+}
+
+// applies the hostname and verification overrides on top of the usual webpki checks
+#[derive(Debug)]
+struct ConfiguredServerCertVerifier {
+    web_pki_verifier: Arc<WebPkiServerVerifier>,
+    supported_algorithms: WebPkiSupportedAlgorithms,
+    is_name_verified: bool,
+    is_certificate_verified: bool
+}
+
+impl ServerCertVerifier for ConfiguredServerCertVerifier {
+    fn verify_server_cert(&self, end_entity: &CertificateDer<'_>, intermediates: &[CertificateDer<'_>], server_name: &ServerName<'_>, ocsp_response: &[u8], now: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        if !self.is_certificate_verified {
+            return Ok(ServerCertVerified::assertion());
+        }
+        match self.web_pki_verifier.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now) {
+            Err(rustls::Error::InvalidCertificate(CertificateError::NotValidForName | CertificateError::NotValidForNameContext { .. })) if !self.is_name_verified => {
+                Ok(ServerCertVerified::assertion())
+            },
+            result => {
+                result
+            }
+        }
+    }
+    fn verify_tls12_signature(&self, message: &[u8], certificate: &CertificateDer<'_>, signature: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, certificate, signature, &self.supported_algorithms)
+    }
+    fn verify_tls13_signature(&self, message: &[u8], certificate: &CertificateDer<'_>, signature: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, certificate, signature, &self.supported_algorithms)
+    }
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.supported_algorithms.supported_schemes()
+    }
+}
+
+// the socket under a telnet connection, which is either in the clear or wrapped in TLS
+pub enum MainframeStream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>)
+}
+
+impl MainframeStream {
+    pub fn get_tcp_stream(&self) -> &TcpStream {
+        match self {
+            MainframeStream::Plain(stream) => {
+                stream
+            },
+            MainframeStream::Tls(stream) => {
+                stream.get_ref()
+            }
+        }
+    }
+    pub fn is_tls(&self) -> bool {
+        matches!(self, MainframeStream::Tls(_))
+    }
+    // gives back the socket of a plain stream so that it can be upgraded
+    pub fn into_tcp_stream(self) -> Option<TcpStream> {
+        match self {
+            MainframeStream::Plain(stream) => {
+                Some(stream)
+            },
+            MainframeStream::Tls(_) => {
+                None
+            }
+        }
+    }
+}
+
+impl Read for MainframeStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            MainframeStream::Plain(stream) => {
+                stream.read(buf)
+            },
+            MainframeStream::Tls(stream) => {
+                stream.read(buf)
+            }
+        }
+    }
+}
+
+impl Write for MainframeStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            MainframeStream::Plain(stream) => {
+                stream.write(buf)
+            },
+            MainframeStream::Tls(stream) => {
+                stream.write(buf)
+            }
+        }
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            MainframeStream::Plain(stream) => {
+                stream.flush()
+            },
+            MainframeStream::Tls(stream) => {
+                stream.flush()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn map_settings_to_x3270_arguments() {
+        let mut tls_configuration = TlsConfiguration::new(TlsMode::Implicit);
+        tls_configuration.ca_bundle_path = Some(PathBuf::from("/etc/ca.pem"));
+        tls_configuration.hostname_verification = HostnameVerification::Name(String::from("mainframe.example.com"));
+        tls_configuration.minimum_version = TlsVersion::Tls13;
+        assert_eq!(vec![
+            "-cafile", "/etc/ca.pem",
+            "-accepthostname", "mainframe.example.com",
+            "-tlsminprotocol", "TLS1_3"
+        ], tls_configuration.get_x3270_arguments());
+    }
+
+    #[test]
+    fn derive_server_name_from_address() {
+        let tls_configuration = TlsConfiguration::new(TlsMode::Implicit);
+        assert_eq!(ServerName::try_from("mainframe.example.com").unwrap(), tls_configuration.get_server_name("mainframe.example.com:992").unwrap());
+        assert_eq!(ServerName::try_from("::1").unwrap(), tls_configuration.get_server_name("[::1]:992").unwrap());
+    }
+
+    #[test]
+    fn require_client_certificate_and_key_together() {
+        let mut tls_configuration = TlsConfiguration::new(TlsMode::Implicit);
+        tls_configuration.client_certificate_path = Some(PathBuf::from("/etc/client.pem"));
+        let error = tls_configuration.get_client_configuration().unwrap_err();
+        assert!(matches!(TlsError::from_io_error(&error), Some(TlsError::Configuration(_))));
+    }
+}
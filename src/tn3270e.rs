@@ -0,0 +1,368 @@
+#![allow(dead_code)]
+
+// TN3270E (RFC 2355) negotiation vocabulary and the header that prefixes every record once it is active
+
+pub const OPTION_TN3270E: u8 = 40;
+
+pub const ASSOCIATE: u8 = 0;
+pub const CONNECT: u8 = 1;
+pub const DEVICE_TYPE: u8 = 2;
+pub const FUNCTIONS: u8 = 3;
+pub const IS: u8 = 4;
+pub const REASON: u8 = 5;
+pub const REJECT: u8 = 6;
+pub const REQUEST: u8 = 7;
+pub const SEND: u8 = 8;
+
+pub const DATA_TYPE_3270_DATA: u8 = 0;
+pub const DATA_TYPE_SCS_DATA: u8 = 1;
+pub const DATA_TYPE_RESPONSE: u8 = 2;
+pub const DATA_TYPE_BIND_IMAGE: u8 = 3;
+pub const DATA_TYPE_UNBIND: u8 = 4;
+pub const DATA_TYPE_NVT_DATA: u8 = 5;
+pub const DATA_TYPE_REQUEST: u8 = 6;
+pub const DATA_TYPE_SSCP_LU_DATA: u8 = 7;
+pub const DATA_TYPE_PRINT_EOJ: u8 = 8;
+
+pub const RESPONSE_FLAG_NO_RESPONSE: u8 = 0;
+pub const RESPONSE_FLAG_ERROR_RESPONSE: u8 = 1;
+pub const RESPONSE_FLAG_ALWAYS_RESPONSE: u8 = 2;
+pub const RESPONSE_FLAG_POSITIVE_RESPONSE: u8 = 0;
+pub const RESPONSE_FLAG_NEGATIVE_RESPONSE: u8 = 1;
+
+const RESPONSE_DEVICE_END: u8 = 0x00;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Function {
+    BindImage,
+    DataStreamControl,
+    Responses,
+    ScsControlCodes,
+    SysReq
+}
+
+impl Function {
+    pub fn get_code(&self) -> u8 {
+        match self {
+            Function::BindImage => 0,
+            Function::DataStreamControl => 1,
+            Function::Responses => 2,
+            Function::ScsControlCodes => 3,
+            Function::SysReq => 4
+        }
+    }
+    pub fn from_code(code: u8) -> Option<Function> {
+        match code {
+            0 => Some(Function::BindImage),
+            1 => Some(Function::DataStreamControl),
+            2 => Some(Function::Responses),
+            3 => Some(Function::ScsControlCodes),
+            4 => Some(Function::SysReq),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    ConnectionPartner,
+    DeviceInUse,
+    InvalidAssociate,
+    InvalidName,
+    InvalidDeviceType,
+    TypeNameError,
+    UnknownError,
+    UnsupportedRequest,
+    Other(u8)
+}
+
+impl RejectReason {
+    pub fn from_code(code: u8) -> RejectReason {
+        match code {
+            0 => RejectReason::ConnectionPartner,
+            1 => RejectReason::DeviceInUse,
+            2 => RejectReason::InvalidAssociate,
+            3 => RejectReason::InvalidName,
+            4 => RejectReason::InvalidDeviceType,
+            5 => RejectReason::TypeNameError,
+            6 => RejectReason::UnknownError,
+            7 => RejectReason::UnsupportedRequest,
+            code => RejectReason::Other(code)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Tn3270eError {
+    DeviceTypeRejected(RejectReason),
+    FunctionsNotAgreed(Vec<u8>),
+    MalformedSubnegotiation(Vec<u8>)
+}
+
+impl std::fmt::Display for Tn3270eError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Tn3270eError::DeviceTypeRejected(reason) => {
+                write!(f, "The host rejected the TN3270E device type request: {:?}", reason)
+            },
+            Tn3270eError::FunctionsNotAgreed(codes) => {
+                write!(f, "The host and terminal could not agree on TN3270E functions: {:?}", codes)
+            },
+            Tn3270eError::MalformedSubnegotiation(bytes) => {
+                write!(f, "The host sent a malformed TN3270E subnegotiation: {:?}", bytes)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Tn3270eError {}
+
+impl Tn3270eError {
+    // negotiation failures travel inside std::io::Error so that the telnet layer can keep its io::Result signatures
+    pub fn from_io_error(error: &std::io::Error) -> Option<&Tn3270eError> {
+        error.get_ref()
+            .and_then(|inner| inner.downcast_ref::<Tn3270eError>())
+    }
+}
+
+impl From<Tn3270eError> for std::io::Error {
+    fn from(error: Tn3270eError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::ConnectionRefused, error)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tn3270eConfiguration {
+    pub device_type: String,
+    // the LU to CONNECT to, or for printers, the terminal LU to ASSOCIATE with
+    pub lu_name: Option<String>,
+    pub is_associate: bool,
+    pub functions: Vec<Function>
+}
+
+impl Tn3270eConfiguration {
+    pub fn new_terminal(device_type: &str, lu_name: Option<&str>) -> Self {
+        Tn3270eConfiguration {
+            device_type: String::from(device_type),
+            lu_name: lu_name.map(String::from),
+            is_associate: false,
+            functions: vec![Function::BindImage, Function::Responses, Function::SysReq]
+        }
+    }
+    pub fn new_printer(associated_lu_name: &str) -> Self {
+        Tn3270eConfiguration {
+            device_type: String::from("IBM-3287-1"),
+            lu_name: Some(String::from(associated_lu_name)),
+            is_associate: true,
+            functions: vec![Function::ScsControlCodes, Function::DataStreamControl, Function::Responses]
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tn3270eHeader {
+    pub data_type: u8,
+    pub request_flag: u8,
+    pub response_flag: u8,
+    pub sequence_number: u16
+}
+
+impl Tn3270eHeader {
+    pub fn new(data_type: u8, response_flag: u8, sequence_number: u16) -> Self {
+        Tn3270eHeader {
+            data_type,
+            request_flag: 0,
+            response_flag,
+            sequence_number
+        }
+    }
+    pub fn parse(bytes: &[u8]) -> Option<Tn3270eHeader> {
+        if bytes.len() < 5 {
+            return None;
+        }
+        Some(Tn3270eHeader {
+            data_type: bytes[0],
+            request_flag: bytes[1],
+            response_flag: bytes[2],
+            sequence_number: ((bytes[3] as u16) << 8) | bytes[4] as u16
+        })
+    }
+    pub fn to_bytes(self) -> [u8; 5] {
+        [self.data_type, self.request_flag, self.response_flag, (self.sequence_number >> 8) as u8, (self.sequence_number & 0xFF) as u8]
+    }
+    // the positive response that acknowledges this record
+    pub fn get_positive_response(&self) -> Vec<u8> {
+        let mut response = Tn3270eHeader::new(DATA_TYPE_RESPONSE, RESPONSE_FLAG_POSITIVE_RESPONSE, self.sequence_number).to_bytes().to_vec();
+        response.push(RESPONSE_DEVICE_END);
+        response
+    }
+}
+
+// the state of the TN3270E negotiation as seen by the terminal
+pub struct Tn3270eNegotiation {
+    configuration: Tn3270eConfiguration,
+    device_type: Option<String>,
+    device_name: Option<String>,
+    functions: Option<Vec<Function>>
+}
+
+impl Tn3270eNegotiation {
+    pub fn new(configuration: Tn3270eConfiguration) -> Self {
+        Tn3270eNegotiation {
+            configuration,
+            device_type: None,
+            device_name: None,
+            functions: None
+        }
+    }
+    pub fn is_complete(&self) -> bool {
+        self.device_type.is_some() && self.functions.is_some()
+    }
+    pub fn get_device_type(&self) -> Option<&str> {
+        self.device_type.as_deref()
+    }
+    pub fn get_device_name(&self) -> Option<&str> {
+        self.device_name.as_deref()
+    }
+    pub fn is_function_enabled(&self, function: Function) -> bool {
+        self.functions
+            .as_ref()
+            .map(|functions| functions.contains(&function))
+            .unwrap_or(false)
+    }
+    fn get_function_request(functions: &[Function]) -> Vec<u8> {
+        let mut reply = vec![OPTION_TN3270E, FUNCTIONS, REQUEST];
+        reply.extend(functions.iter().map(|function| function.get_code()));
+        reply
+    }
+    // processes the body of an IAC SB TN3270E ... IAC SE, returning the body of the reply to send, if any
+    pub fn process_subnegotiation(&mut self, subnegotiation: &[u8]) -> Result<Option<Vec<u8>>, Tn3270eError> {
+        let malformed = || Tn3270eError::MalformedSubnegotiation(subnegotiation.to_vec());
+        match subnegotiation.get(1..3) {
+            Some([SEND, DEVICE_TYPE]) => {
+                let mut reply = vec![OPTION_TN3270E, DEVICE_TYPE, REQUEST];
+                reply.extend(self.configuration.device_type.as_bytes());
+                if let Some(lu_name) = &self.configuration.lu_name {
+                    reply.push(if self.configuration.is_associate { ASSOCIATE } else { CONNECT });
+                    reply.extend(lu_name.as_bytes());
+                }
+                Ok(Some(reply))
+            },
+            Some([DEVICE_TYPE, IS]) => {
+                let body = &subnegotiation[3..];
+                let (device_type, device_name) = match body.iter().position(|byte| *byte == CONNECT) {
+                    Some(index) => {
+                        (&body[..index], Some(&body[index + 1..]))
+                    },
+                    None => {
+                        (body, None)
+                    }
+                };
+                self.device_type = Some(String::from_utf8_lossy(device_type).into_owned());
+                self.device_name = device_name.map(|device_name| String::from_utf8_lossy(device_name).into_owned());
+                Ok(Some(Self::get_function_request(&self.configuration.functions)))
+            },
+            Some([DEVICE_TYPE, REJECT]) => {
+                match subnegotiation.get(3..5) {
+                    Some([REASON, code]) => {
+                        Err(Tn3270eError::DeviceTypeRejected(RejectReason::from_code(*code)))
+                    },
+                    _ => {
+                        Err(malformed())
+                    }
+                }
+            },
+            Some([FUNCTIONS, IS]) => {
+                self.functions = Some(subnegotiation[3..]
+                    .iter()
+                    .filter_map(|code| Function::from_code(*code))
+                    .collect());
+                Ok(None)
+            },
+            Some([FUNCTIONS, REQUEST]) => {
+                // the host proposed a different set, which is acceptable only if it is a subset of ours
+                let codes = &subnegotiation[3..];
+                let proposed = codes.iter()
+                    .map(|code| Function::from_code(*code).filter(|function| self.configuration.functions.contains(function)))
+                    .collect::<Option<Vec<Function>>>();
+                match proposed {
+                    Some(functions) => {
+                        let mut reply = vec![OPTION_TN3270E, FUNCTIONS, IS];
+                        reply.extend(codes);
+                        self.functions = Some(functions);
+                        Ok(Some(reply))
+                    },
+                    None => {
+                        let agreeable = codes.iter()
+                            .filter_map(|code| Function::from_code(*code))
+                            .filter(|function| self.configuration.functions.contains(function))
+                            .collect::<Vec<Function>>();
+                        if agreeable.is_empty() && !codes.is_empty() {
+                            return Err(Tn3270eError::FunctionsNotAgreed(codes.to_vec()));
+                        }
+                        Ok(Some(Self::get_function_request(&agreeable)))
+                    }
+                }
+            },
+            _ => {
+                Err(malformed())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate_device_type_and_functions() {
+        let mut negotiation = Tn3270eNegotiation::new(Tn3270eConfiguration::new_terminal("IBM-3278-2-E", Some("LU01")));
+        let reply = negotiation.process_subnegotiation(&[OPTION_TN3270E, SEND, DEVICE_TYPE]).unwrap().unwrap();
+        let mut expected = vec![OPTION_TN3270E, DEVICE_TYPE, REQUEST];
+        expected.extend(b"IBM-3278-2-E");
+        expected.push(CONNECT);
+        expected.extend(b"LU01");
+        assert_eq!(expected, reply);
+
+        let mut device_type_is = vec![OPTION_TN3270E, DEVICE_TYPE, IS];
+        device_type_is.extend(b"IBM-3278-2-E");
+        device_type_is.push(CONNECT);
+        device_type_is.extend(b"LU01");
+        let reply = negotiation.process_subnegotiation(&device_type_is).unwrap().unwrap();
+        assert_eq!(vec![OPTION_TN3270E, FUNCTIONS, REQUEST, 0, 2, 4], reply);
+        assert_eq!(Some("LU01"), negotiation.get_device_name());
+        assert!(!negotiation.is_complete());
+
+        // the host counter-proposes a subset
+        let reply = negotiation.process_subnegotiation(&[OPTION_TN3270E, FUNCTIONS, REQUEST, 2]).unwrap().unwrap();
+        assert_eq!(vec![OPTION_TN3270E, FUNCTIONS, IS, 2], reply);
+        assert!(negotiation.is_complete());
+        assert!(negotiation.is_function_enabled(Function::Responses));
+        assert!(!negotiation.is_function_enabled(Function::BindImage));
+    }
+
+    #[test]
+    fn surface_reject_reason() {
+        let mut negotiation = Tn3270eNegotiation::new(Tn3270eConfiguration::new_printer("LU01"));
+        let reply = negotiation.process_subnegotiation(&[OPTION_TN3270E, SEND, DEVICE_TYPE]).unwrap().unwrap();
+        assert_eq!(Some(&ASSOCIATE), reply.get(3 + "IBM-3287-1".len()));
+        let error = negotiation.process_subnegotiation(&[OPTION_TN3270E, DEVICE_TYPE, REJECT, REASON, 1]).err().unwrap();
+        assert_eq!(Tn3270eError::DeviceTypeRejected(RejectReason::DeviceInUse), error);
+
+        let io_error: std::io::Error = error.into();
+        assert_eq!(Some(&Tn3270eError::DeviceTypeRejected(RejectReason::DeviceInUse)), Tn3270eError::from_io_error(&io_error));
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let header = Tn3270eHeader::parse(&[DATA_TYPE_3270_DATA, 0, RESPONSE_FLAG_ALWAYS_RESPONSE, 0x01, 0x02, 0xF5]).unwrap();
+        assert_eq!(Tn3270eHeader {
+            data_type: DATA_TYPE_3270_DATA,
+            request_flag: 0,
+            response_flag: RESPONSE_FLAG_ALWAYS_RESPONSE,
+            sequence_number: 0x0102
+        }, header);
+        assert_eq!(vec![DATA_TYPE_RESPONSE, 0, RESPONSE_FLAG_POSITIVE_RESPONSE, 0x01, 0x02, 0x00], header.get_positive_response());
+    }
+}
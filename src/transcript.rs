@@ -0,0 +1,365 @@
+#![allow(dead_code)]
+
+use std::{collections::VecDeque, io::Write, path::Path, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+use crate::{client_interface::*, client_status::ClientStatus, code_page::CodePage, error::Rs3270Error, logging::*, script_codec::*};
+
+// a transcript holds one entry per command, where times are milliseconds since the Unix epoch:
+//   command 1760000000000 Query(Cursor)
+//   digest 6f3a...
+//   data 3 4
+//   status U F U C(localhost) I 4 43 80 3 4 0x0 0.001
+//   ok 1760000000002
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptEntry {
+    // redacted per the RedactionPolicy in effect while recording
+    pub command: String,
+    // the SHA-256 of the command as it was sent, so that redacted typed text is still compared on replay
+    pub command_digest: Option<String>,
+    pub sent_time: SystemTime,
+    pub frame: ResponseFrame,
+    pub received_time: SystemTime
+}
+
+impl TranscriptEntry {
+    fn write_to(&self, writer: &mut dyn Write) -> std::io::Result<()> {
+        let mut text = format!("command {} {}\n", get_epoch_milliseconds(self.sent_time), self.command);
+        if let Some(command_digest) = &self.command_digest {
+            text.push_str(&format!("digest {}\n", command_digest));
+        }
+        for data_line in self.frame.data_lines.iter() {
+            text.push_str(&format!("data {}\n", data_line));
+        }
+        text.push_str(&format!("status {}\n", self.frame.status_line));
+        let conclusion = match self.frame.conclusion {
+            Conclusion::Ok => "ok",
+            Conclusion::Error => "error"
+        };
+        text.push_str(&format!("{} {}\n", conclusion, get_epoch_milliseconds(self.received_time)));
+        writer.write_all(text.as_bytes())?;
+        writer.flush()
+    }
+}
+
+// the hexadecimal SHA-256 of the command as it was sent
+fn get_command_digest(command: &str) -> String {
+    ring::digest::digest(&ring::digest::SHA256, command.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn get_epoch_milliseconds(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+fn parse_epoch_milliseconds(text: &str) -> Option<SystemTime> {
+    let milliseconds = text.parse::<u64>().ok()?;
+    Some(UNIX_EPOCH + Duration::from_millis(milliseconds))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcript {
+    pub entries: Vec<TranscriptEntry>
+}
+
+impl Transcript {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut entries = Vec::new();
+        let mut command: Option<(String, SystemTime)> = None;
+        let mut command_digest: Option<String> = None;
+        let mut data_lines = Vec::new();
+        let mut status: Option<(String, ClientStatus)> = None;
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            let (kind, rest) = line.split_once(' ')
+                .unwrap_or((line, ""));
+            match (kind, command.is_some(), status.is_some()) {
+                ("command", false, _) => {
+                    let (sent_time, text) = rest.split_once(' ')
+                        .unwrap_or((rest, ""));
+                    let sent_time = parse_epoch_milliseconds(sent_time)
+                        .ok_or_else(|| format!("Line {line_number} does not start with a time."))?;
+                    command = Some((String::from(text), sent_time));
+                },
+                ("digest", true, false) if data_lines.is_empty() && command_digest.is_none() => {
+                    command_digest = Some(String::from(rest));
+                },
+                ("data", true, false) => {
+                    data_lines.push(String::from(rest));
+                },
+                ("status", true, false) => {
+                    let client_status = ClientStatus::parse(rest)
+                        .ok_or_else(|| format!("Line {line_number} is not a status line."))?;
+                    status = Some((String::from(rest), client_status));
+                },
+                ("ok" | "error", true, true) => {
+                    let received_time = parse_epoch_milliseconds(rest)
+                        .ok_or_else(|| format!("Line {line_number} does not have a time."))?;
+                    let (command, sent_time) = command.take().expect("the command should precede its conclusion");
+                    let (status_line, status) = status.take().expect("the status should precede the conclusion");
+                    entries.push(TranscriptEntry {
+                        command,
+                        command_digest: command_digest.take(),
+                        sent_time,
+                        frame: ResponseFrame {
+                            data_lines: std::mem::take(&mut data_lines),
+                            status,
+                            status_line,
+                            conclusion: if kind == "ok" { Conclusion::Ok } else { Conclusion::Error }
+                        },
+                        received_time
+                    });
+                },
+                ("", false, _) => {
+                    // blank lines between entries
+                },
+                _ => {
+                    return Err(format!("Line {line_number} is out of place: \"{line}\""));
+                }
+            }
+        }
+        if command.is_some() {
+            return Err(String::from("The last command has no conclusion."));
+        }
+        Ok(Transcript {
+            entries
+        })
+    }
+    pub fn load(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        Transcript::parse(&text)
+            .map_err(|message| std::io::Error::new(std::io::ErrorKind::InvalidData, message))
+    }
+}
+
+// pairs each command written to the client with the frame that answers it, writing the pair out once it is complete
+pub struct TranscriptRecorder {
+    writer: Box<dyn Write>,
+    sent_commands: VecDeque<(String, String, SystemTime)>
+}
+
+impl TranscriptRecorder {
+    pub fn new(writer: impl Write + 'static) -> Self {
+        TranscriptRecorder {
+            writer: Box::new(writer),
+            sent_commands: VecDeque::new()
+        }
+    }
+    pub(crate) fn record_command(&mut self, command: &str) {
+        self.sent_commands.push_back((redact_command(command), get_command_digest(command), SystemTime::now()));
+    }
+    pub(crate) fn record_frame(&mut self, frame: &ResponseFrame) {
+        let (command, command_digest, sent_time) = match self.sent_commands.pop_front() {
+            Some(sent_command) => {
+                sent_command
+            },
+            None => {
+                tracing::warn!("received a frame that no recorded command was waiting for");
+                return;
+            }
+        };
+        let transcript_entry = TranscriptEntry {
+            command,
+            command_digest: Some(command_digest),
+            sent_time,
+            frame: frame.clone(),
+            received_time: SystemTime::now()
+        };
+        // a failure to record should not fail the session being recorded
+        if let Err(error) = transcript_entry.write_to(self.writer.as_mut()) {
+            tracing::warn!(%error, "unable to write to the transcript");
+        }
+    }
+}
+
+// a CommandExecutor that answers each command with the response recorded for it, for regression tests without a host
+// once a command differs from the recording every later command fails too, since the replay can no longer follow the session
+pub struct ReplayCommandExecutor {
+    entries: VecDeque<TranscriptEntry>,
+    status: Option<ClientStatus>,
+    timeouts: Timeouts,
+    code_page: CodePage,
+    divergence: Option<String>
+}
+
+impl ReplayCommandExecutor {
+    pub fn new(transcript: Transcript) -> Self {
+        ReplayCommandExecutor {
+            entries: transcript.entries.into(),
+            status: None,
+            timeouts: Timeouts::default(),
+            code_page: CodePage::Cp037,
+            divergence: None
+        }
+    }
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Ok(ReplayCommandExecutor::new(Transcript::load(path)?))
+    }
+    // the recorded commands that have not been replayed, which should be none at the end of a faithful replay
+    pub fn get_remaining_count(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn get_divergence(&self) -> Option<&str> {
+        self.divergence.as_deref()
+    }
+    fn replay<TOutput>(&mut self, command: impl CommandBuilder<TOutput>, client_message: &str) -> Result<TOutput, Rs3270Error> {
+        let redacted_command = redact_command(client_message);
+        if let Err(message) = command.validate(&self.code_page) {
+            return Err(Rs3270Error::Rejected { command: redacted_command, message });
+        }
+        if let Some(divergence) = &self.divergence {
+            return Err(Rs3270Error::Protocol { command: redacted_command, message: format!("The replay already diverged: {}", divergence) });
+        }
+        let entry = match self.entries.front() {
+            Some(entry) if entry.command == redacted_command && entry.command_digest.as_ref().is_some_and(|command_digest| *command_digest != get_command_digest(client_message)) => {
+                let divergence = format!("\"{}\" was sent with different text than the recording.", redacted_command);
+                tracing::error!(%divergence, "the replay diverged from the recording");
+                self.divergence = Some(divergence.clone());
+                return Err(Rs3270Error::Protocol { command: redacted_command, message: divergence });
+            },
+            // transcripts recorded without digests are matched on the redacted command alone
+            Some(entry) if entry.command == redacted_command => {
+                self.entries.pop_front().expect("the entry was just found")
+            },
+            Some(entry) => {
+                let divergence = format!("\"{}\" was sent where the recording has \"{}\".", redacted_command, entry.command);
+                tracing::error!(%divergence, "the replay diverged from the recording");
+                self.divergence = Some(divergence.clone());
+                return Err(Rs3270Error::Protocol { command: redacted_command, message: divergence });
+            },
+            None => {
+                let divergence = format!("\"{}\" was sent after the end of the recording.", redacted_command);
+                tracing::error!(%divergence, "the replay diverged from the recording");
+                self.divergence = Some(divergence.clone());
+                return Err(Rs3270Error::Protocol { command: redacted_command, message: divergence });
+            }
+        };
+        self.status = Some(entry.frame.status.clone());
+        command.complete(entry.frame, redacted_command)
+    }
+}
+
+impl CommandExecutor for ReplayCommandExecutor {
+    // replays the transcript at the given path
+    fn connect_to_client_process(transcript_path: &str) -> Option<Self> {
+        match ReplayCommandExecutor::open(transcript_path) {
+            Ok(replay_command_executor) => {
+                Some(replay_command_executor)
+            },
+            Err(error) => {
+                tracing::error!(transcript_path, %error, "unable to open the transcript");
+                None
+            }
+        }
+    }
+    fn execute_with_timeouts<TOutput>(&mut self, command: impl CommandBuilder<TOutput>, _timeouts: &Timeouts) -> Result<TOutput, Rs3270Error> {
+        let client_message = command.get_client_message();
+        let span = start_command_span(&client_message);
+        let _entered = span.enter();
+        let start_time = Instant::now();
+        let execution_result = self.replay(command, &client_message);
+        record_command_outcome(&span, start_time, &execution_result);
+        execution_result
+    }
+    fn get_timeouts(&self) -> &Timeouts {
+        &self.timeouts
+    }
+    fn set_timeouts(&mut self, timeouts: Timeouts) {
+        self.timeouts = timeouts;
+    }
+    fn resynchronize(&mut self) -> Result<(), Rs3270Error> {
+        Ok(())
+    }
+    fn get_status(&self) -> Option<&ClientStatus> {
+        self.status.as_ref()
+    }
+    fn get_code_page(&self) -> CodePage {
+        self.code_page
+    }
+    fn set_code_page(&mut self, code_page: CodePage) {
+        self.code_page = code_page;
+    }
+    fn disconnect(&mut self) {
+        // nothing to release
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
+
+    // a writer whose bytes can still be read after it is handed to a recorder
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    const TRANSCRIPT: &str = "command 1760000000000 Query(Cursor)\n\
+        data 3 4\n\
+        status U F U C(localhost) I 4 43 80 3 4 0x0 0.001\n\
+        ok 1760000000002\n\
+        command 1760000000003 Tab\n\
+        data Tab failed\n\
+        status U F U C(localhost) I 4 43 80 3 4 0x0 0.001\n\
+        error 1760000000004\n";
+
+    #[test]
+    fn record_then_parse_transcript() {
+        let buffer = SharedBuffer::default();
+        let mut recorder = TranscriptRecorder::new(buffer.clone());
+        recorder.record_command("MoveCursor(1,2)");
+        recorder.record_command("String(\"hunter2\")");
+        let status_line = String::from("U F U C(localhost) I 4 43 80 1 2 0x0 0.000");
+        let frame = ResponseFrame {
+            data_lines: Vec::new(),
+            status: ClientStatus::parse(&status_line).unwrap(),
+            status_line,
+            conclusion: Conclusion::Ok
+        };
+        recorder.record_frame(&frame);
+        recorder.record_frame(&frame);
+
+        let text = String::from_utf8(buffer.0.borrow().clone()).unwrap();
+        let transcript = Transcript::parse(&text).unwrap();
+        assert_eq!(2, transcript.entries.len());
+        assert_eq!("MoveCursor(1,2)", transcript.entries[0].command);
+        // typed text never reaches the transcript
+        assert_eq!("String(<redacted>)", transcript.entries[1].command);
+        assert_eq!(Some(get_command_digest("String(\"hunter2\")")), transcript.entries[1].command_digest);
+        assert_eq!(frame, transcript.entries[1].frame);
+    }
+
+    #[test]
+    fn replay_then_fail_on_divergence() {
+        let mut executor = ReplayCommandExecutor::new(Transcript::parse(TRANSCRIPT).unwrap());
+
+        assert_eq!((3, 4), executor.execute(GetCursorCommand::new()).unwrap());
+        assert_eq!(Some(3), executor.get_status().map(|status| status.cursor_row));
+        let error = executor.execute(SendEnterKeyCommand::new()).unwrap_err();
+        assert!(matches!(error, Rs3270Error::Protocol { .. }));
+        assert!(executor.get_divergence().unwrap().contains("\"Tab\""));
+        // the recorded Tab is not served once the replay has diverged
+        assert!(executor.execute(MoveCursorToNextFieldCommand::new()).is_err());
+        assert_eq!(1, executor.get_remaining_count());
+    }
+
+    #[test]
+    fn reject_malformed_transcript() {
+        assert!(Transcript::parse("data 3 4\n").is_err());
+        assert!(Transcript::parse("command 1760000000000 Enter\nstatus U F U N N 4 43 80 0 0 0x0 -\n").is_err());
+    }
+}